@@ -0,0 +1,445 @@
+//! Column-oriented copy of a record set with a small query API
+//!
+//! A `ColumnStore` keeps identity columns and every numeric column of a
+//! `WolfRecord` type as flat vectors, with missing values stored as `NaN`.
+//! Queries filter rows, group them and aggregate into an export `Table`,
+//! which can be printed, written with the export writers or plotted.
+//!
+//! ```no_run
+//! # use wolf_project_210::columnar::{Aggregate, GroupKey, Predicate};
+//! # use wolf_project_210::dataset::WolfDataset;
+//! # let dataset = WolfDataset::default();
+//! let table = dataset
+//!     .denning_columns()
+//!     .query()
+//!     .years(2000..=2010)
+//!     .filter("winter_swe", Predicate::Gt(100.0))
+//!     .group_by(&[GroupKey::Country])
+//!     .aggregate(&[Aggregate::Count, Aggregate::Mean("denning_doy".into())]);
+//! println!("{}", table);
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use crate::data::PackKey;
+use crate::export::{Column, ColumnType, Table, Value};
+use crate::missing::median;
+use crate::record::WolfRecord;
+use crate::study::{Country, StudyArea, StudyId, StudyRegistry};
+
+/// A test on a single numeric value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Predicate {
+    Lt(f64),
+    Le(f64),
+    Gt(f64),
+    Ge(f64),
+    Eq(f64),
+    Ne(f64),
+    /// Inclusive on both ends
+    Between(f64, f64),
+}
+
+impl Predicate {
+    pub fn test(&self, value: f64) -> bool {
+        match *self {
+            Predicate::Lt(x) => value < x,
+            Predicate::Le(x) => value <= x,
+            Predicate::Gt(x) => value > x,
+            Predicate::Ge(x) => value >= x,
+            Predicate::Eq(x) => value == x,
+            Predicate::Ne(x) => value != x,
+            Predicate::Between(lo, hi) => (lo..=hi).contains(&value),
+        }
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Predicate::Lt(x) => write!(f, "< {}", x),
+            Predicate::Le(x) => write!(f, "<= {}", x),
+            Predicate::Gt(x) => write!(f, "> {}", x),
+            Predicate::Ge(x) => write!(f, ">= {}", x),
+            Predicate::Eq(x) => write!(f, "= {}", x),
+            Predicate::Ne(x) => write!(f, "!= {}", x),
+            Predicate::Between(lo, hi) => write!(f, "in [{}, {}]", lo, hi),
+        }
+    }
+}
+
+/// What to group rows by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GroupKey {
+    Study,
+    Year,
+    /// Study and pack id, as two output columns
+    Pack,
+    Country,
+}
+
+/// A summary of one column over a group of rows. Missing values are
+/// skipped, except by `Count`, which counts rows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Aggregate {
+    Count,
+    Mean(String),
+    Median(String),
+    /// Sample standard deviation
+    Sd(String),
+    Min(String),
+    Max(String),
+    /// Share of non-missing values that satisfy the predicate
+    Proportion(String, Predicate),
+}
+
+impl Aggregate {
+    fn column(&self) -> Option<&str> {
+        match self {
+            Aggregate::Count => None,
+            Aggregate::Mean(c)
+            | Aggregate::Median(c)
+            | Aggregate::Sd(c)
+            | Aggregate::Min(c)
+            | Aggregate::Max(c)
+            | Aggregate::Proportion(c, _) => Some(c),
+        }
+    }
+
+    /// Output column name, e.g. `mean(denning_doy)`
+    pub fn name(&self) -> String {
+        match self {
+            Aggregate::Count => "count".to_string(),
+            Aggregate::Mean(c) => format!("mean({})", c),
+            Aggregate::Median(c) => format!("median({})", c),
+            Aggregate::Sd(c) => format!("sd({})", c),
+            Aggregate::Min(c) => format!("min({})", c),
+            Aggregate::Max(c) => format!("max({})", c),
+            Aggregate::Proportion(c, p) => format!("prop({} {})", c, p),
+        }
+    }
+
+    fn compute(&self, rows: usize, values: &mut [f64]) -> Value {
+        let n = values.len();
+        let float = |v: Option<f64>| v.map_or(Value::Null, Value::Float);
+        match self {
+            Aggregate::Count => Value::Integer(rows as i64),
+            _ if n == 0 => Value::Null,
+            Aggregate::Mean(_) => Value::Float(values.iter().sum::<f64>() / n as f64),
+            Aggregate::Median(_) => float(median(values)),
+            Aggregate::Sd(_) => float((n > 1).then(|| {
+                let mean = values.iter().sum::<f64>() / n as f64;
+                let ss: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+                (ss / (n - 1) as f64).sqrt()
+            })),
+            Aggregate::Min(_) => Value::Float(values.iter().copied().fold(f64::INFINITY, f64::min)),
+            Aggregate::Max(_) => {
+                Value::Float(values.iter().copied().fold(f64::NEG_INFINITY, f64::max))
+            }
+            Aggregate::Proportion(_, predicate) => {
+                let hits = values.iter().filter(|v| predicate.test(**v)).count();
+                Value::Float(hits as f64 / n as f64)
+            }
+        }
+    }
+}
+
+/// Struct-of-arrays copy of a record set
+#[derive(Debug, Clone, Default)]
+pub struct ColumnStore {
+    uid: Vec<u32>,
+    study: Vec<StudyId>,
+    pack_id: Vec<u32>,
+    year: Vec<i32>,
+    numeric: BTreeMap<&'static str, Vec<f64>>,
+    /// Shared with the `WolfDataset` that owns the store, if any
+    studies: Arc<StudyRegistry>,
+}
+
+impl ColumnStore {
+    /// Copies `records` into columns; studies are looked up in (and added
+    /// to a copy of) `studies`
+    pub fn from_records<T: WolfRecord>(records: &[T], studies: &StudyRegistry) -> Self {
+        let mut studies = studies.clone();
+        let mut store = ColumnStore {
+            uid: Vec::with_capacity(records.len()),
+            study: Vec::with_capacity(records.len()),
+            pack_id: Vec::with_capacity(records.len()),
+            year: Vec::with_capacity(records.len()),
+            numeric: T::NUMERIC_COLUMNS
+                .iter()
+                .map(|c| (*c, Vec::with_capacity(records.len())))
+                .collect(),
+            studies: Arc::default(),
+        };
+        for record in records {
+            store.uid.push(record.uid());
+            store.study.push(studies.intern(record.study()));
+            store.pack_id.push(record.pack_id());
+            store.year.push(record.season_year());
+            for (name, column) in store.numeric.iter_mut() {
+                column.push(record.covariate(name).unwrap_or(f64::NAN));
+            }
+        }
+        store.studies = Arc::new(studies);
+        store
+    }
+
+    /// Replaces the store's registry with a shared one that holds every
+    /// study of the store under the same ids, e.g. after new metadata
+    pub(crate) fn share_studies(&mut self, studies: &Arc<StudyRegistry>) {
+        debug_assert!(self
+            .studies
+            .areas()
+            .iter()
+            .all(|a| studies.id_of(&a.name) == Some(a.id)));
+        self.studies = Arc::clone(studies);
+    }
+
+    /// Study areas of the rows, with any metadata loaded into the dataset
+    pub fn studies(&self) -> &StudyRegistry {
+        &self.studies
+    }
+
+    pub fn len(&self) -> usize {
+        self.uid.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uid.is_empty()
+    }
+
+    /// Names of the numeric columns
+    pub fn column_names(&self) -> Vec<&'static str> {
+        self.numeric.keys().copied().collect()
+    }
+
+    /// A numeric column, with `NaN` for missing values
+    pub fn column(&self, name: &str) -> Option<&[f64]> {
+        self.numeric.get(name).map(Vec::as_slice)
+    }
+
+    pub fn uid(&self, row: usize) -> u32 {
+        self.uid[row]
+    }
+
+    pub fn year(&self, row: usize) -> i32 {
+        self.year[row]
+    }
+
+    pub fn study(&self, row: usize) -> &str {
+        &self.studies.get(self.study[row]).name
+    }
+
+    pub fn pack_key(&self, row: usize) -> PackKey {
+        PackKey::new(self.study(row), self.pack_id[row])
+    }
+
+    pub fn country(&self, row: usize) -> Country {
+        self.study_area(row).country
+    }
+
+    pub fn study_area(&self, row: usize) -> &StudyArea {
+        self.studies.get(self.study[row])
+    }
+
+    /// Starts a query over every row
+    pub fn query(&self) -> Query<'_> {
+        Query {
+            store: self,
+            rows: (0..self.len()).collect(),
+        }
+    }
+}
+
+/// A filtered selection of rows
+#[derive(Debug, Clone)]
+pub struct Query<'a> {
+    store: &'a ColumnStore,
+    rows: Vec<usize>,
+}
+
+impl<'a> Query<'a> {
+    fn retain(mut self, keep: impl Fn(&ColumnStore, usize) -> bool) -> Self {
+        self.rows.retain(|&row| keep(self.store, row));
+        self
+    }
+
+    pub fn study(self, name: &str) -> Self {
+        let id = self.store.studies.id_of(name);
+        self.retain(|store, row| Some(store.study[row]) == id)
+    }
+
+    pub fn country(self, country: Country) -> Self {
+        self.retain(|store, row| store.country(row) == country)
+    }
+
+    pub fn years(self, years: RangeInclusive<i32>) -> Self {
+        self.retain(|store, row| years.contains(&store.year[row]))
+    }
+
+    pub fn pack(self, key: &PackKey) -> Self {
+        self.retain(|store, row| store.pack_id[row] == key.pack_id && store.study(row) == key.study)
+    }
+
+    /// Keeps rows whose `column` is present and satisfies `predicate`; an
+    /// unknown column keeps no rows
+    pub fn filter(self, column: &str, predicate: Predicate) -> Self {
+        let values = self.store.column(column);
+        self.retain(|_, row| {
+            values.is_some_and(|values| !values[row].is_nan() && predicate.test(values[row]))
+        })
+    }
+
+    /// Row positions in the store, in their original order
+    pub fn rows(&self) -> &[usize] {
+        &self.rows
+    }
+
+    pub fn count(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Distinct packs of the selected rows, in order of first appearance
+    pub fn packs(&self) -> Vec<PackKey> {
+        let mut packs: Vec<PackKey> = Vec::new();
+        for &row in &self.rows {
+            let key = self.store.pack_key(row);
+            if !packs.contains(&key) {
+                packs.push(key);
+            }
+        }
+        packs
+    }
+
+    /// Non-missing values of a column over the selected rows
+    pub fn values(&self, column: &str) -> Vec<f64> {
+        self.store.column(column).map_or_else(Vec::new, |values| {
+            self.rows
+                .iter()
+                .map(|&row| values[row])
+                .filter(|v| !v.is_nan())
+                .collect()
+        })
+    }
+
+    pub fn group_by(self, keys: &[GroupKey]) -> Grouped<'a> {
+        let mut groups: BTreeMap<Vec<GroupCell>, Vec<usize>> = BTreeMap::new();
+        for &row in &self.rows {
+            let cells = keys
+                .iter()
+                .flat_map(|key| group_cells(self.store, *key, row))
+                .collect();
+            groups.entry(cells).or_default().push(row);
+        }
+        Grouped {
+            store: self.store,
+            keys: keys.to_vec(),
+            groups,
+        }
+    }
+
+    /// Aggregates every selected row into a one-row table
+    pub fn aggregate(self, aggregates: &[Aggregate]) -> Table {
+        self.group_by(&[]).aggregate(aggregates)
+    }
+}
+
+/// A group-by key value; ordered so output tables are sorted by key
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum GroupCell {
+    Integer(i64),
+    Text(String),
+    Country(Country),
+}
+
+fn group_cells(store: &ColumnStore, key: GroupKey, row: usize) -> Vec<GroupCell> {
+    match key {
+        GroupKey::Study => vec![GroupCell::Text(store.study(row).to_string())],
+        GroupKey::Year => vec![GroupCell::Integer(i64::from(store.year[row]))],
+        GroupKey::Pack => vec![
+            GroupCell::Text(store.study(row).to_string()),
+            GroupCell::Integer(i64::from(store.pack_id[row])),
+        ],
+        GroupKey::Country => vec![GroupCell::Country(store.country(row))],
+    }
+}
+
+fn group_columns(key: GroupKey) -> Vec<Column> {
+    match key {
+        GroupKey::Study => vec![Column::new("study", ColumnType::Text)],
+        GroupKey::Year => vec![Column::new("year", ColumnType::Integer)],
+        GroupKey::Pack => vec![
+            Column::new("study", ColumnType::Text),
+            Column::new("pack_id", ColumnType::Integer),
+        ],
+        GroupKey::Country => vec![Column::new("country", ColumnType::Text)],
+    }
+}
+
+/// Rows split into groups, ready to aggregate
+#[derive(Debug, Clone)]
+pub struct Grouped<'a> {
+    store: &'a ColumnStore,
+    keys: Vec<GroupKey>,
+    groups: BTreeMap<Vec<GroupCell>, Vec<usize>>,
+}
+
+impl Grouped<'_> {
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// One row per group, sorted by key: the key columns, then one column
+    /// per aggregate
+    pub fn aggregate(self, aggregates: &[Aggregate]) -> Table {
+        let mut columns: Vec<Column> = self.keys.iter().flat_map(|k| group_columns(*k)).collect();
+        columns.extend(aggregates.iter().map(|a| {
+            let kind = match a {
+                Aggregate::Count => ColumnType::Integer,
+                _ => ColumnType::Float,
+            };
+            Column::new(a.name(), kind)
+        }));
+        let mut table = Table::new(columns);
+
+        // An ungrouped query still yields its one summary row
+        let mut groups = self.groups;
+        if self.keys.is_empty() && groups.is_empty() {
+            groups.insert(Vec::new(), Vec::new());
+        }
+        for (cells, rows) in groups {
+            let mut row: Vec<Value> = cells
+                .into_iter()
+                .map(|cell| match cell {
+                    GroupCell::Integer(v) => Value::Integer(v),
+                    GroupCell::Text(v) => Value::Text(v),
+                    GroupCell::Country(v) => Value::Text(v.to_string()),
+                })
+                .collect();
+            for aggregate in aggregates {
+                let mut values: Vec<f64> = aggregate
+                    .column()
+                    .and_then(|c| self.store.column(c))
+                    .map_or_else(Vec::new, |column| {
+                        rows.iter()
+                            .map(|&r| column[r])
+                            .filter(|v| !v.is_nan())
+                            .collect()
+                    });
+                row.push(aggregate.compute(rows.len(), &mut values));
+            }
+            table
+                .push_row(row)
+                .expect("one value per key column and aggregate");
+        }
+        table
+    }
+}
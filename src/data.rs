@@ -1,13 +1,24 @@
-use std::error::Error;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
 
-use chrono::NaiveDate;
-use serde::{Deserialize, Deserializer};
+use chrono::{Datelike, NaiveDate};
+use csv::StringRecord;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_path_to_error::Segment;
+
+use crate::error::WolfDataError;
+use crate::missing::{impute, Imputable, MissingPolicy, MissingReport};
+use crate::schema::{check_header, ColumnMapping, SchemaReport};
+use crate::units::{Celsius, Millimetres, Quantity, SweMm};
+use crate::validation::{Severity, Validated, ValidationReport, Validator};
 
 /// Struct for reproductive success data
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ReproductiveSuccess {
     pub uid: u32,
     pub study: String,
@@ -22,19 +33,19 @@ pub struct ReproductiveSuccess {
 
     pub success: u8,
 
-    #[serde(deserialize_with = "parse_optional_u32")]
-    pub summer_prcp: Option<u32>,
-    #[serde(deserialize_with = "parse_optional_u32")]
-    pub fall_prcp: Option<u32>,
-    #[serde(deserialize_with = "parse_optional_u32")]
-    pub winter_swe: Option<u32>,
+    #[serde(deserialize_with = "parse_optional_quantity")]
+    pub summer_prcp: Option<Millimetres>,
+    #[serde(deserialize_with = "parse_optional_quantity")]
+    pub fall_prcp: Option<Millimetres>,
+    #[serde(deserialize_with = "parse_optional_quantity")]
+    pub winter_swe: Option<SweMm>,
 
-    #[serde(deserialize_with = "parse_optional_i8")]
-    pub fall_tmax: Option<i8>,
-    #[serde(deserialize_with = "parse_optional_i8")]
-    pub summer_tmax: Option<i8>,
-    #[serde(deserialize_with = "parse_optional_i8")]
-    pub winter_tmax: Option<i8>,
+    #[serde(deserialize_with = "parse_optional_quantity")]
+    pub fall_tmax: Option<Celsius>,
+    #[serde(deserialize_with = "parse_optional_quantity")]
+    pub summer_tmax: Option<Celsius>,
+    #[serde(deserialize_with = "parse_optional_quantity")]
+    pub winter_tmax: Option<Celsius>,
 
     #[serde(rename = "tiNDVI_prev1", deserialize_with = "parse_optional_f64")]
     pub ti_ndvi_prev1: Option<f64>,
@@ -52,7 +63,7 @@ pub struct ReproductiveSuccess {
 
 /// Struct for denning phenology data
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct DenningPhenology {
     pub uid: u32,
     pub study: String,
@@ -66,19 +77,19 @@ pub struct DenningPhenology {
     pub denning_doy: u16,
     pub denned: i8,
 
-    #[serde(deserialize_with = "parse_optional_i8")]
-    pub fall_tmax: Option<i8>,
-    #[serde(deserialize_with = "parse_optional_i8")]
-    pub summer_tmax_prev1: Option<i8>,
-    #[serde(deserialize_with = "parse_optional_i8")]
-    pub winter_tmax: Option<i8>,
+    #[serde(deserialize_with = "parse_optional_quantity")]
+    pub fall_tmax: Option<Celsius>,
+    #[serde(deserialize_with = "parse_optional_quantity")]
+    pub summer_tmax_prev1: Option<Celsius>,
+    #[serde(deserialize_with = "parse_optional_quantity")]
+    pub winter_tmax: Option<Celsius>,
 
-    #[serde(deserialize_with = "parse_optional_u32")]
-    pub fall_prcp: Option<u32>,
-    #[serde(deserialize_with = "parse_optional_u32")]
-    pub summer_prcp_prev1: Option<u32>,
-    #[serde(deserialize_with = "parse_optional_u32")]
-    pub winter_swe: Option<u32>,
+    #[serde(deserialize_with = "parse_optional_quantity")]
+    pub fall_prcp: Option<Millimetres>,
+    #[serde(deserialize_with = "parse_optional_quantity")]
+    pub summer_prcp_prev1: Option<Millimetres>,
+    #[serde(deserialize_with = "parse_optional_quantity")]
+    pub winter_swe: Option<SweMm>,
 
     #[serde(rename = "tiNDVI_prev1", deserialize_with = "parse_optional_f64")]
     pub ti_ndvi_prev1: Option<f64>,
@@ -94,77 +105,905 @@ pub struct DenningPhenology {
     pub latitude_individual: f64,
 }
 
-pub fn read_reproductive_csv(path: &str) -> Result<Vec<ReproductiveSuccess>, Box<dyn Error>> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let mut csv_reader = csv::Reader::from_reader(reader);
+/// Identity of a pack. Pack numbers are assigned per study, so `pack_id`
+/// alone does not identify a pack across the ABoVE files.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PackKey {
+    pub study: String,
+    pub pack_id: u32,
+}
 
-    let mut records = Vec::new();
-    for (i, result) in csv_reader.deserialize::<ReproductiveSuccess>().enumerate() {
-        match result {
-            Ok(record) if record.has_no_missing_fields() => records.push(record),
-            Ok(_) => eprintln!("Skipping line {} due to missing fields", i + 1),
-            Err(e) => eprintln!("Error deserializing reproductive data (line {}): {}", i + 1, e),
+impl PackKey {
+    pub fn new(study: impl Into<String>, pack_id: u32) -> Self {
+        PackKey {
+            study: study.into(),
+            pack_id,
+        }
+    }
+}
+
+impl fmt::Display for PackKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} / pack {}", self.study, self.pack_id)
+    }
+}
+
+impl ReproductiveSuccess {
+    pub fn pack_key(&self) -> PackKey {
+        PackKey::new(self.study.clone(), self.pack_id)
+    }
+
+    /// Pack and season year, taken from `start_date`
+    pub fn season_key(&self) -> (PackKey, i32) {
+        (self.pack_key(), self.start_date.year())
+    }
+}
+
+impl DenningPhenology {
+    pub fn pack_key(&self) -> PackKey {
+        PackKey::new(self.study.clone(), self.pack_id)
+    }
+
+    /// Pack and season year, taken from `denning_date`
+    pub fn season_key(&self) -> (PackKey, i32) {
+        (self.pack_key(), self.denning_date.year())
+    }
+}
+
+/// Finds pack ids that are used by more than one study.
+///
+/// # Returns
+/// * `BTreeMap<pack_id, studies using it>`, containing only reused ids
+pub fn find_reused_pack_ids<I>(keys: I) -> BTreeMap<u32, BTreeSet<String>>
+where
+    I: IntoIterator<Item = PackKey>,
+{
+    let mut studies_by_id: BTreeMap<u32, BTreeSet<String>> = BTreeMap::new();
+    for key in keys {
+        studies_by_id.entry(key.pack_id).or_default().insert(key.study);
+    }
+    studies_by_id.retain(|_, studies| studies.len() > 1);
+    studies_by_id
+}
+
+/// Which rows `join_pack_seasons` keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    /// Only denning records with a reproductive record for the same season
+    Inner,
+    /// Every denning record, with the reproductive record when there is one
+    Left,
+    /// Only denning records without a reproductive record for the season
+    Anti,
+}
+
+/// A pack's denning record paired with that season's reproductive outcome
+#[derive(Debug, Clone)]
+pub struct PackSeason {
+    pub key: PackKey,
+    pub year: i32,
+    pub denning: DenningPhenology,
+    pub reproduction: Option<ReproductiveSuccess>,
+}
+
+/// Result of `join_pack_seasons`, including the rows that found no partner
+#[derive(Debug, Clone, Default)]
+pub struct PackSeasonJoin {
+    pub seasons: Vec<PackSeason>,
+    pub unmatched_denning: Vec<DenningPhenology>,
+    pub unmatched_reproduction: Vec<ReproductiveSuccess>,
+}
+
+/// Pairs each denning record with the reproductive record for the same
+/// pack and season year (`denning_date.year()` with `start_date.year()`).
+///
+/// When a pack-season has several rows on either side, every combination
+/// is returned, as in a SQL join.
+///
+/// # Arguments
+/// * `denning_data` - List of denning records
+/// * `reproductive_data` - List of reproductive records
+/// * `kind` - Which denning rows to keep
+pub fn join_pack_seasons(
+    denning_data: &[DenningPhenology],
+    reproductive_data: &[ReproductiveSuccess],
+    kind: JoinKind,
+) -> PackSeasonJoin {
+    let mut by_season: HashMap<(PackKey, i32), Vec<usize>> = HashMap::new();
+    for (i, r) in reproductive_data.iter().enumerate() {
+        by_season.entry(r.season_key()).or_default().push(i);
+    }
+
+    let mut join = PackSeasonJoin::default();
+    let mut matched_reproduction = vec![false; reproductive_data.len()];
+    for d in denning_data {
+        let (key, year) = d.season_key();
+        let partners = by_season.get(&(key.clone(), year)).map_or(&[][..], Vec::as_slice);
+
+        if partners.is_empty() {
+            join.unmatched_denning.push(d.clone());
+            if kind != JoinKind::Inner {
+                join.seasons.push(PackSeason {
+                    key,
+                    year,
+                    denning: d.clone(),
+                    reproduction: None,
+                });
+            }
+            continue;
+        }
+
+        for &i in partners {
+            matched_reproduction[i] = true;
+            if kind != JoinKind::Anti {
+                join.seasons.push(PackSeason {
+                    key: key.clone(),
+                    year,
+                    denning: d.clone(),
+                    reproduction: Some(reproductive_data[i].clone()),
+                });
+            }
+        }
+    }
+
+    join.unmatched_reproduction = reproductive_data
+        .iter()
+        .zip(matched_reproduction)
+        .filter(|(_, matched)| !matched)
+        .map(|(r, _)| r.clone())
+        .collect();
+    join
+}
+
+/// Why a single field caused a row to be rejected
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RejectReason {
+    /// The raw value could not be parsed into the column's type
+    Parse(String),
+    /// The value was the `-9999` missing-data sentinel
+    Sentinel,
+    /// The value was `NA` or `NaN`
+    NotAvailable,
+    /// The cell was empty
+    Empty,
+    /// A required identifier or count was zero (e.g. `uid == 0`)
+    Zero,
+    /// The row itself could not be read (wrong field count, bad UTF-8, I/O)
+    Malformed(String),
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectReason::Parse(msg) => write!(f, "parse error: {}", msg),
+            RejectReason::Sentinel => write!(f, "sentinel -9999"),
+            RejectReason::NotAvailable => write!(f, "NA"),
+            RejectReason::Empty => write!(f, "empty"),
+            RejectReason::Zero => write!(f, "zero value"),
+            RejectReason::Malformed(msg) => write!(f, "malformed row: {}", msg),
         }
     }
+}
 
-    Ok(records)
+/// A single failing column within a rejected row
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldIssue {
+    pub column: String,
+    /// The raw cell text, trimmed
+    pub value: String,
+    pub reason: RejectReason,
 }
 
-pub fn read_denning_csv(path: &str) -> Result<Vec<DenningPhenology>, Box<dyn Error>> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let mut csv_reader = csv::Reader::from_reader(reader);
+/// A CSV row that could not be turned into a record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordError {
+    /// 1-based line number in the source file (the header is line 1)
+    pub line: u64,
+    pub uid: Option<u32>,
+    pub study: Option<String>,
+    pub issues: Vec<FieldIssue>,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}", self.line)?;
+        if let Some(uid) = self.uid {
+            write!(f, " (uid {})", uid)?;
+        }
+        let issues: Vec<_> = self
+            .issues
+            .iter()
+            .map(|issue| format!("{}: {}", issue.column, issue.reason))
+            .collect();
+        write!(f, ": {}", issues.join(", "))
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+/// Summary of what a loader kept and what it dropped
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoadReport {
+    pub total_rows: usize,
+    pub accepted: usize,
+    pub rejected: Vec<RecordError>,
+    /// Number of failing values per column
+    pub by_column: BTreeMap<String, usize>,
+    /// Number of rejected rows per study
+    pub by_study: BTreeMap<String, usize>,
+    /// How the file's header matched the expected columns
+    pub schema: SchemaReport,
+    /// The missing-value policy used and what it did
+    pub missing: MissingReport,
+}
+
+impl LoadReport {
+    pub fn rejected_count(&self) -> usize {
+        self.rejected.len()
+    }
+
+    /// Fraction of rows that were rejected (0.0 for an empty file)
+    pub fn rejection_rate(&self) -> f64 {
+        if self.total_rows == 0 {
+            0.0
+        } else {
+            self.rejected.len() as f64 / self.total_rows as f64
+        }
+    }
+
+    /// Fails if more than `max_rate` of the rows were rejected
+    pub fn ensure_rejection_rate(&self, max_rate: f64) -> Result<(), WolfDataError> {
+        let rate = self.rejection_rate();
+        if rate > max_rate {
+            return Err(WolfDataError::Validation {
+                line: None,
+                column: None,
+                message: format!(
+                    "{} of {} rows rejected ({:.1}%), above the {:.1}% threshold",
+                    self.rejected.len(),
+                    self.total_rows,
+                    rate * 100.0,
+                    max_rate * 100.0
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    fn accept(&mut self) {
+        self.total_rows += 1;
+        self.accepted += 1;
+    }
+
+    fn reject(&mut self, row: RecordError) {
+        self.total_rows += 1;
+        for issue in &row.issues {
+            *self.by_column.entry(issue.column.clone()).or_default() += 1;
+        }
+        let study = row.study.clone().unwrap_or_else(|| "<unknown>".to_string());
+        *self.by_study.entry(study).or_default() += 1;
+        self.rejected.push(row);
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} rows accepted", self.accepted, self.total_rows)?;
+        if !self.by_column.is_empty() {
+            let columns: Vec<_> = self
+                .by_column
+                .iter()
+                .map(|(column, n)| format!("{} ({})", column, n))
+                .collect();
+            write!(f, "; rejected on {}", columns.join(", "))?;
+        }
+        write!(f, "; {}", self.missing)
+    }
+}
+
+/// Record types that can be loaded by `RecordStream`
+trait CsvRecord: DeserializeOwned + Imputable {
+    /// Expected CSV columns, in struct field order
+    const COLUMNS: &'static [&'static str];
+}
+
+impl CsvRecord for ReproductiveSuccess {
+    const COLUMNS: &'static [&'static str] = ReproductiveSuccess::COLUMNS;
+}
+
+impl CsvRecord for DenningPhenology {
+    const COLUMNS: &'static [&'static str] = DenningPhenology::COLUMNS;
+}
+
+/// Settings shared by the CSV loaders.
+///
+/// The free `read_*` and `stream_*` functions use `LoadOptions::default()`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LoadOptions {
+    /// Renames applied to the file's header before it is checked
+    pub mapping: ColumnMapping,
+    /// Which rows with missing values are dropped, kept or imputed.
+    /// Streaming loaders cannot impute and keep such values as `None`.
+    pub missing: MissingPolicy,
+}
+
+impl LoadOptions {
+    pub fn with_mapping(mapping: ColumnMapping) -> Self {
+        LoadOptions {
+            mapping,
+            ..LoadOptions::default()
+        }
+    }
+
+    pub fn with_missing_policy(missing: MissingPolicy) -> Self {
+        LoadOptions {
+            missing,
+            ..LoadOptions::default()
+        }
+    }
+
+    pub fn read_reproductive<R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<(Vec<ReproductiveSuccess>, LoadReport), WolfDataError> {
+        Ok(collect_records(RecordStream::new(reader, self)?))
+    }
+
+    pub fn read_denning<R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<(Vec<DenningPhenology>, LoadReport), WolfDataError> {
+        Ok(collect_records(RecordStream::new(reader, self)?))
+    }
+
+    /// Reads reproductive records, each paired with its 1-based line in the
+    /// source file
+    pub fn read_reproductive_with_lines<R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<(Vec<(u64, ReproductiveSuccess)>, LoadReport), WolfDataError> {
+        Ok(collect_with_lines(RecordStream::new(reader, self)?))
+    }
+
+    /// Reads denning records, each paired with its 1-based line in the
+    /// source file
+    pub fn read_denning_with_lines<R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<(Vec<(u64, DenningPhenology)>, LoadReport), WolfDataError> {
+        Ok(collect_with_lines(RecordStream::new(reader, self)?))
+    }
+
+    /// Reads reproductive records, moving rejected rows and rows that fail
+    /// `validator` with an error to `quarantine`
+    pub fn quarantine_reproductive<R: Read, W: Write>(
+        &self,
+        reader: R,
+        validator: &Validator<ReproductiveSuccess>,
+        quarantine: W,
+    ) -> Result<(Vec<ReproductiveSuccess>, LoadReport, ValidationReport), WolfDataError> {
+        quarantine_records(RecordStream::new(reader, self)?, validator, quarantine)
+    }
 
+    /// Reads denning records, moving rejected rows and rows that fail
+    /// `validator` with an error to `quarantine`
+    pub fn quarantine_denning<R: Read, W: Write>(
+        &self,
+        reader: R,
+        validator: &Validator<DenningPhenology>,
+        quarantine: W,
+    ) -> Result<(Vec<DenningPhenology>, LoadReport, ValidationReport), WolfDataError> {
+        quarantine_records(RecordStream::new(reader, self)?, validator, quarantine)
+    }
+
+    pub fn stream_reproductive<R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<impl Iterator<Item = Result<ReproductiveSuccess, RecordError>>, WolfDataError> {
+        RecordStream::new(reader, self)
+    }
+
+    pub fn stream_denning<R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<impl Iterator<Item = Result<DenningPhenology, RecordError>>, WolfDataError> {
+        RecordStream::new(reader, self)
+    }
+}
+
+pub fn read_reproductive_csv(
+    path: &str,
+) -> Result<(Vec<ReproductiveSuccess>, LoadReport), WolfDataError> {
+    let file = File::open(path).map_err(|e| WolfDataError::io(path, e))?;
+    read_reproductive_from_reader(file)
+}
+
+pub fn read_denning_csv(path: &str) -> Result<(Vec<DenningPhenology>, LoadReport), WolfDataError> {
+    let file = File::open(path).map_err(|e| WolfDataError::io(path, e))?;
+    read_denning_from_reader(file)
+}
+
+/// Reads reproductive records from any CSV source (file, stdin, bytes, ...)
+pub fn read_reproductive_from_reader<R: Read>(
+    reader: R,
+) -> Result<(Vec<ReproductiveSuccess>, LoadReport), WolfDataError> {
+    LoadOptions::default().read_reproductive(reader)
+}
+
+/// Reads denning records from any CSV source (file, stdin, bytes, ...)
+pub fn read_denning_from_reader<R: Read>(
+    reader: R,
+) -> Result<(Vec<DenningPhenology>, LoadReport), WolfDataError> {
+    LoadOptions::default().read_denning(reader)
+}
+
+/// Yields reproductive records one row at a time without collecting them.
+///
+/// The header is checked up front; each item is then either a complete
+/// record or the reason its row was rejected.
+pub fn stream_reproductive_records<R: Read>(
+    reader: R,
+) -> Result<impl Iterator<Item = Result<ReproductiveSuccess, RecordError>>, WolfDataError> {
+    LoadOptions::default().stream_reproductive(reader)
+}
+
+/// Yields denning records one row at a time without collecting them.
+///
+/// The header is checked up front; each item is then either a complete
+/// record or the reason its row was rejected.
+pub fn stream_denning_records<R: Read>(
+    reader: R,
+) -> Result<impl Iterator<Item = Result<DenningPhenology, RecordError>>, WolfDataError> {
+    LoadOptions::default().stream_denning(reader)
+}
+
+fn collect_records<R: Read, T: CsvRecord>(stream: RecordStream<R, T>) -> (Vec<T>, LoadReport) {
+    let (records, report, _) = collect_rows(stream, false);
+    (records, report)
+}
+
+fn collect_with_lines<R: Read, T: CsvRecord>(
+    stream: RecordStream<R, T>,
+) -> (Vec<(u64, T)>, LoadReport) {
+    let line_offset = stream.line_offset;
+    let (records, report, raw_rows) = collect_rows(stream, true);
+    let lines = raw_rows
+        .into_iter()
+        .filter(|(_, rejection)| rejection.is_none())
+        .map(|(raw, _)| line_of(raw.position(), line_offset));
+    (lines.zip(records).collect(), report)
+}
+
+/// Collects accepted records, optionally keeping their raw rows, and the
+/// raw rows the loader rejected together with the reason
+#[allow(clippy::type_complexity)]
+fn collect_rows<R: Read, T: CsvRecord>(
+    mut stream: RecordStream<R, T>,
+    keep_raw: bool,
+) -> (Vec<T>, LoadReport, Vec<(StringRecord, Option<RecordError>)>) {
     let mut records = Vec::new();
-    for (i, result) in csv_reader.deserialize::<DenningPhenology>().enumerate() {
+    let mut raw_rows = Vec::new();
+    let mut report = LoadReport {
+        schema: std::mem::take(&mut stream.schema),
+        missing: MissingReport::new(stream.policy.clone()),
+        ..LoadReport::default()
+    };
+    let policy = stream.policy.clone();
+    while let Some(result) = stream.next() {
         match result {
-            Ok(record) if record.has_no_missing_fields() => records.push(record),
-            Ok(_) => eprintln!("Skipping line {} due to missing fields", i + 1),
-            Err(e) => eprintln!("Error deserializing denning data (line {}): {}", i + 1, e),
+            Ok(record) => {
+                records.push(record);
+                if keep_raw {
+                    raw_rows.push((stream.raw.clone(), None));
+                }
+                report.accept();
+            }
+            Err(e) => {
+                let parse_failure = e.issues.iter().any(|issue| {
+                    matches!(issue.reason, RejectReason::Parse(_) | RejectReason::Malformed(_))
+                });
+                if !parse_failure {
+                    report.missing.dropped += 1;
+                }
+                if keep_raw {
+                    raw_rows.push((stream.raw.clone(), Some(e.clone())));
+                }
+                report.reject(e)
+            }
+        }
+    }
+
+    if let MissingPolicy::Impute(method) = policy {
+        report.missing.imputed = impute(&mut records, method);
+    }
+    (records, report, raw_rows)
+}
+
+/// Loads records, validates them and moves every row that was rejected or
+/// has an error-level finding to `quarantine`.
+///
+/// The quarantine file keeps the source header and rows unchanged, with a
+/// trailing `quarantine_reason` column.
+fn quarantine_records<R, W, T>(
+    stream: RecordStream<R, T>,
+    validator: &Validator<T>,
+    quarantine: W,
+) -> Result<(Vec<T>, LoadReport, ValidationReport), WolfDataError>
+where
+    R: Read,
+    W: Write,
+    T: CsvRecord + Validated,
+{
+    let mut writer = csv::Writer::from_writer(quarantine);
+    let mut header = stream.header.clone();
+    header.push_field("quarantine_reason");
+    let line_offset = stream.line_offset;
+    writer.write_record(&header)?;
+
+    let (records, load_report, raw_rows) = collect_rows(stream, true);
+    let mut raw_accepted = Vec::with_capacity(records.len());
+    for (raw, rejection) in raw_rows {
+        match rejection {
+            Some(e) => write_quarantined(&mut writer, &raw, &e.to_string())?,
+            None => raw_accepted.push(raw),
+        }
+    }
+
+    let mut validation = validator.validate(&records);
+    for finding in &mut validation.findings {
+        finding.line = Some(line_of(raw_accepted[finding.row].position(), line_offset));
+    }
+    let failing = validation.failing_rows();
+    let mut kept = Vec::with_capacity(records.len() - failing.len());
+    for (row, record) in records.into_iter().enumerate() {
+        if !failing.contains(&row) {
+            kept.push(record);
+            continue;
+        }
+        let reasons: Vec<String> = validation
+            .with_severity(Severity::Error)
+            .filter(|f| f.row == row)
+            .map(|f| format!("{}: {}", f.rule, f.message))
+            .collect();
+        write_quarantined(&mut writer, &raw_accepted[row], &reasons.join("; "))?;
+    }
+    writer.flush()?;
+
+    validation.quarantined = failing.len() + load_report.rejected_count();
+    Ok((kept, load_report, validation))
+}
+
+fn write_quarantined<W: Write>(
+    writer: &mut csv::Writer<W>,
+    raw: &StringRecord,
+    reason: &str,
+) -> Result<(), WolfDataError> {
+    let mut row = raw.clone();
+    row.push_field(reason);
+    writer.write_record(&row)?;
+    Ok(())
+}
+
+/// Row-by-row reader behind both the streaming and collecting loaders
+struct RecordStream<R, T> {
+    reader: csv::Reader<R>,
+    /// Position of each of `T::COLUMNS` in the file's header
+    positions: Vec<usize>,
+    /// The file's header as written
+    header: StringRecord,
+    schema: SchemaReport,
+    policy: MissingPolicy,
+    raw: StringRecord,
+    /// Added to the reader's line numbers; see `line_of`
+    line_offset: u64,
+    done: bool,
+    _record: PhantomData<T>,
+}
+
+impl<R: Read, T: CsvRecord> RecordStream<R, T> {
+    /// Reads and checks the header; no rows are parsed if it does not match
+    fn new(reader: R, options: &LoadOptions) -> Result<Self, WolfDataError> {
+        let mut reader = csv::Reader::from_reader(reader);
+        let headers = reader.headers()?.clone();
+        // With CRLF endings the reader stops at `\r` and only counts the
+        // `\n` once it starts on the next row, so every row would be
+        // reported one line early
+        let header_lines = 1 + headers.iter().map(|h| h.matches('\n').count() as u64).sum::<u64>();
+        let line_offset = u64::from(reader.position().line() < header_lines + 1);
+
+        let schema = check_header(headers.iter(), T::COLUMNS, &options.mapping);
+        if !schema.is_ok() {
+            return Err(WolfDataError::Schema(schema));
+        }
+        let positions = T::COLUMNS
+            .iter()
+            .filter_map(|column| {
+                headers
+                    .iter()
+                    .position(|h| options.mapping.target(h.trim()) == *column)
+            })
+            .collect();
+
+        Ok(RecordStream {
+            reader,
+            positions,
+            header: headers,
+            schema,
+            policy: options.missing.clone(),
+            raw: StringRecord::new(),
+            line_offset,
+            done: false,
+            _record: PhantomData,
+        })
+    }
+
+    fn parse_row(&self) -> Result<T, RecordError> {
+        let line = line_of(self.raw.position(), self.line_offset);
+
+        // Reorder into struct field order so a failing field maps back to its column
+        let row: StringRecord = self
+            .positions
+            .iter()
+            .map(|&i| self.raw.get(i).unwrap_or(""))
+            .collect();
+        let field = |name: &str| {
+            T::COLUMNS
+                .iter()
+                .position(|c| *c == name)
+                .and_then(|i| row.get(i))
+                .map(str::trim)
+        };
+
+        let issues = match row.deserialize::<Located<T>>(None) {
+            Ok(Located(record)) => {
+                let missing: Vec<_> = Imputable::missing_fields(&record)
+                    .into_iter()
+                    .filter(|column| self.policy.drops_on(column))
+                    .collect();
+                if missing.is_empty() {
+                    return Ok(record);
+                }
+                missing
+                    .into_iter()
+                    .map(|column| {
+                        let value = field(column).unwrap_or("");
+                        FieldIssue {
+                            column: column.to_string(),
+                            value: value.to_string(),
+                            reason: classify_missing(value),
+                        }
+                    })
+                    .collect()
+            }
+            Err(e) => {
+                let (column, message) = parse_failure(&e);
+                vec![FieldIssue {
+                    value: field(&column).unwrap_or("").to_string(),
+                    column,
+                    reason: RejectReason::Parse(message),
+                }]
+            }
+        };
+
+        Err(RecordError {
+            line,
+            uid: field("uid").and_then(|s| s.parse().ok()),
+            study: field("study").filter(|s| !s.is_empty()).map(str::to_string),
+            issues,
+        })
+    }
+}
+
+impl<R: Read, T: CsvRecord> Iterator for RecordStream<R, T> {
+    type Item = Result<T, RecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.reader.read_record(&mut self.raw) {
+            Ok(true) => Some(self.parse_row()),
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                // An I/O error would repeat forever, so stop after reporting it
+                let line = line_of(e.position(), self.line_offset);
+                if matches!(e.kind(), csv::ErrorKind::Io(_)) {
+                    self.done = true;
+                }
+                Some(Err(RecordError {
+                    line,
+                    uid: None,
+                    study: None,
+                    issues: vec![FieldIssue {
+                        column: "<row>".to_string(),
+                        value: String::new(),
+                        reason: RejectReason::Malformed(e.to_string()),
+                    }],
+                }))
+            }
         }
     }
+}
 
-    Ok(records)
+/// 1-based source line of a row from the reader's position, 0 if unknown
+fn line_of(position: Option<&csv::Position>, offset: u64) -> u64 {
+    position.map_or(0, |p| p.line() + offset)
+}
+
+/// Works out which kind of missing value a raw cell holds
+fn classify_missing(raw: &str) -> RejectReason {
+    if raw.is_empty() {
+        RejectReason::Empty
+    } else if raw == "-9999" {
+        RejectReason::Sentinel
+    } else if raw.eq_ignore_ascii_case("na") || raw.eq_ignore_ascii_case("nan") {
+        RejectReason::NotAvailable
+    } else {
+        RejectReason::Zero
+    }
+}
+
+/// Separates the failing column from the message in a `Located` error
+const COLUMN_SEPARATOR: char = '\u{1f}';
+
+/// Deserializes a positional record, prefixing any error with its column.
+///
+/// The custom field parsers below report errors without a field index, so
+/// csv alone cannot say which column was bad.
+struct Located<T>(T);
+
+impl<'de, T: CsvRecord> Deserialize<'de> for Located<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        serde_path_to_error::deserialize(deserializer)
+            .map(Located)
+            .map_err(|e| {
+                let column = match e.path().iter().next() {
+                    Some(Segment::Seq { index }) => {
+                        T::COLUMNS.get(*index).copied().unwrap_or("<row>")
+                    }
+                    _ => "<row>",
+                };
+                let message = e.into_inner().to_string();
+                // csv prefixes the errors it can place itself with "field N: "
+                let message = match message.split_once(": ") {
+                    Some((prefix, rest)) if prefix.starts_with("field ") => rest.to_string(),
+                    _ => message,
+                };
+                serde::de::Error::custom(format!("{}{}{}", column, COLUMN_SEPARATOR, message))
+            })
+    }
+}
+
+/// Splits a csv deserialization error into the failing column and message
+fn parse_failure(error: &csv::Error) -> (String, String) {
+    let message = match error.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.kind().to_string(),
+        _ => error.to_string(),
+    };
+    match message.split_once(COLUMN_SEPARATOR) {
+        Some((column, message)) => (column.to_string(), message.to_string()),
+        None => ("<row>".to_string(), message),
+    }
 }
 
 impl ReproductiveSuccess {
+    /// Columns of the ABoVE reproductive-success export, in struct field order
+    pub const COLUMNS: &'static [&'static str] = &[
+        "uid",
+        "study",
+        "longitude_study",
+        "latitude_study",
+        "pack_id",
+        "start_date",
+        "end_date",
+        "success",
+        "summer_prcp",
+        "fall_prcp",
+        "winter_swe",
+        "fall_tmax",
+        "summer_tmax",
+        "winter_tmax",
+        "tiNDVI_prev1",
+        "tiNDVI",
+        "annual_pdo",
+        "annual_ao",
+        "home_range_area",
+        "denning_match_growing_season",
+    ];
+
     pub fn has_no_missing_fields(&self) -> bool {
-        self.uid != 0
-            && !self.study.is_empty()
-            && self.summer_prcp.is_some()
-            && self.fall_prcp.is_some()
-            && self.winter_swe.is_some()
-            && self.fall_tmax.is_some()
-            && self.summer_tmax.is_some()
-            && self.winter_tmax.is_some()
-            && self.ti_ndvi_prev1.is_some()
-            && self.ti_ndvi.is_some()
-            && self.annual_pdo.is_some()
-            && self.annual_ao.is_some()
-            && self.home_range_area.is_some()
-            && self.denning_match_growing_season.is_some()
+        self.missing_fields().is_empty()
+    }
+
+    /// Names of the columns that are missing or zero in this record
+    pub fn missing_fields(&self) -> Vec<&'static str> {
+        let checks = [
+            ("uid", self.uid != 0),
+            ("study", !self.study.is_empty()),
+            ("summer_prcp", self.summer_prcp.is_some()),
+            ("fall_prcp", self.fall_prcp.is_some()),
+            ("winter_swe", self.winter_swe.is_some()),
+            ("fall_tmax", self.fall_tmax.is_some()),
+            ("summer_tmax", self.summer_tmax.is_some()),
+            ("winter_tmax", self.winter_tmax.is_some()),
+            ("tiNDVI_prev1", self.ti_ndvi_prev1.is_some()),
+            ("tiNDVI", self.ti_ndvi.is_some()),
+            ("annual_pdo", self.annual_pdo.is_some()),
+            ("annual_ao", self.annual_ao.is_some()),
+            ("home_range_area", self.home_range_area.is_some()),
+            (
+                "denning_match_growing_season",
+                self.denning_match_growing_season.is_some(),
+            ),
+        ];
+        checks
+            .iter()
+            .filter(|(_, ok)| !ok)
+            .map(|(name, _)| *name)
+            .collect()
     }
 }
 
 impl DenningPhenology {
+    /// Columns of the ABoVE denning-phenology export, in struct field order
+    pub const COLUMNS: &'static [&'static str] = &[
+        "uid",
+        "study",
+        "longitude_study",
+        "latitude_study",
+        "pack_id",
+        "denning_date",
+        "denning_doy",
+        "denned",
+        "fall_tmax",
+        "summer_tmax_prev1",
+        "winter_tmax",
+        "fall_prcp",
+        "summer_prcp_prev1",
+        "winter_swe",
+        "tiNDVI_prev1",
+        "annual_pdo",
+        "annual_ao",
+        "sos_prev1",
+        "los_prev1",
+        "latitude_individual",
+    ];
+
     pub fn has_no_missing_fields(&self) -> bool {
-        self.uid != 0
-            && !self.study.is_empty()
-            && self.denning_doy != 0
-            && self.denned != 0
-            && self.fall_tmax.is_some()
-            && self.summer_tmax_prev1.is_some()
-            && self.winter_tmax.is_some()
-            && self.fall_prcp.is_some()
-            && self.summer_prcp_prev1.is_some()
-            && self.winter_swe.is_some()
-            && self.ti_ndvi_prev1.is_some()
-            && self.annual_pdo.is_some()
-            && self.annual_ao.is_some()
-            && self.sos_prev1.is_some()
-            && self.los_prev1.is_some()
-            && self.latitude_individual != 0.0
+        self.missing_fields().is_empty()
+    }
+
+    /// Names of the columns that are missing or zero in this record
+    pub fn missing_fields(&self) -> Vec<&'static str> {
+        let checks = [
+            ("uid", self.uid != 0),
+            ("study", !self.study.is_empty()),
+            ("denning_doy", self.denning_doy != 0),
+            ("denned", self.denned != 0),
+            ("fall_tmax", self.fall_tmax.is_some()),
+            ("summer_tmax_prev1", self.summer_tmax_prev1.is_some()),
+            ("winter_tmax", self.winter_tmax.is_some()),
+            ("fall_prcp", self.fall_prcp.is_some()),
+            ("summer_prcp_prev1", self.summer_prcp_prev1.is_some()),
+            ("winter_swe", self.winter_swe.is_some()),
+            ("tiNDVI_prev1", self.ti_ndvi_prev1.is_some()),
+            ("annual_pdo", self.annual_pdo.is_some()),
+            ("annual_ao", self.annual_ao.is_some()),
+            ("sos_prev1", self.sos_prev1.is_some()),
+            ("los_prev1", self.los_prev1.is_some()),
+            ("latitude_individual", self.latitude_individual != 0.0),
+        ];
+        checks
+            .iter()
+            .filter(|(_, ok)| !ok)
+            .map(|(name, _)| *name)
+            .collect()
     }
 }
 
@@ -182,55 +1021,31 @@ where
     } else {
         s.parse::<f64>()
             .map(Some)
-            .map_err(|_| serde::de::Error::custom(format!("Invalid f64 value: {}", s)))
-    }
-}
-
-/// Parse optional i8 field, rounding float to integer
-fn parse_optional_i8<'de, D>(deserializer: D) -> Result<Option<i8>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let s: String = Deserialize::deserialize(deserializer)?;
-    let s = s.trim();
-
-    // Handle invalid cases
-    if s.is_empty() || s == "-9999" || s.eq_ignore_ascii_case("na") {
-        Ok(None)
-    } else {
-        // Try to parse as f64, then round to i8
-        s.parse::<f64>()
-            .map(|f| Some(f.round() as i8)) // Round to nearest i8
-            .map_err(|_| serde::de::Error::custom(format!("Invalid i8 value: {}", s)))
+            .map_err(|_| serde::de::Error::custom("expected a number"))
     }
 }
 
-/// Parse optional u32 field, rounding float to integer
-fn parse_optional_u32<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+/// Parse an optional covariate into a unit type, rejecting values it cannot hold
+fn parse_optional_quantity<'de, D, Q>(deserializer: D) -> Result<Option<Q>, D::Error>
 where
     D: Deserializer<'de>,
+    Q: Quantity,
 {
-    let s: String = Deserialize::deserialize(deserializer)?;
-    let s = s.trim();
-
-    if s.is_empty() || s == "-9999" || s.eq_ignore_ascii_case("na") {
-        Ok(None)
-    } else {
-        // Try to parse as f64, then round to u32
-        s.parse::<f64>()
-            .map(|f| Some(f.round() as u32)) // Round to nearest u32
-            .map_err(|_| serde::de::Error::custom(format!("Invalid u32 value: {}", s)))
+    match parse_optional_f64(deserializer)? {
+        None => Ok(None),
+        Some(value) => Q::new(value)
+            .map(Some)
+            .map_err(|e| serde::de::Error::custom(e.to_string())),
     }
 }
 
-
-
 /// Parse date
 fn parse_date<'de, D>(deserializer: D) -> Result<NaiveDate, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s: &str = Deserialize::deserialize(deserializer)?;
-    NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").map_err(serde::de::Error::custom)
+    NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d")
+        .map_err(|e| serde::de::Error::custom(format!("expected a YYYY-MM-DD date ({})", e)))
 }
 
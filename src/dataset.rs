@@ -0,0 +1,242 @@
+//! Indexed container for the two wolf datasets
+//!
+//! Holds the denning and reproductive records together with hash indexes by
+//! pack, study and season year, so analyses can look up related rows
+//! without scanning the other dataset. Each record set is also kept as a
+//! `ColumnStore` for filtering, grouping and aggregating.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::columnar::ColumnStore;
+use crate::data::{
+    join_pack_seasons, DenningPhenology, JoinKind, PackKey, PackSeasonJoin, ReproductiveSuccess,
+};
+use crate::error::WolfDataError;
+use crate::record::WolfRecord;
+use crate::study::StudyRegistry;
+
+/// Both record sets plus lookup indexes into them
+#[derive(Debug, Clone, Default)]
+pub struct WolfDataset {
+    denning: Vec<DenningPhenology>,
+    reproduction: Vec<ReproductiveSuccess>,
+    denning_index: RecordIndex,
+    reproduction_index: RecordIndex,
+    denning_columns: ColumnStore,
+    reproduction_columns: ColumnStore,
+    /// One registry shared with both column stores
+    study_areas: Arc<StudyRegistry>,
+}
+
+/// Row positions grouped by pack, study and season year
+#[derive(Debug, Clone, Default)]
+struct RecordIndex {
+    by_pack: HashMap<PackKey, Vec<usize>>,
+    by_study: HashMap<String, Vec<usize>>,
+    by_year: HashMap<i32, Vec<usize>>,
+    by_season: HashMap<(PackKey, i32), Vec<usize>>,
+}
+
+impl RecordIndex {
+    fn build<T: WolfRecord>(records: &[T]) -> Self {
+        let mut index = RecordIndex::default();
+        for (i, record) in records.iter().enumerate() {
+            let (key, year) = record.season_key();
+            push(&mut index.by_study, key.study.clone(), i);
+            push(&mut index.by_pack, key.clone(), i);
+            push(&mut index.by_year, year, i);
+            push(&mut index.by_season, (key, year), i);
+        }
+        index
+    }
+}
+
+fn push<K: Eq + Hash>(map: &mut HashMap<K, Vec<usize>>, key: K, i: usize) {
+    map.entry(key).or_default().push(i);
+}
+
+/// Looks up `key` in an index and yields the matching records
+fn lookup<'a, T, K, Q>(
+    records: &'a [T],
+    index: &'a HashMap<K, Vec<usize>>,
+    key: &Q,
+) -> impl Iterator<Item = &'a T> + 'a
+where
+    K: Eq + Hash + std::borrow::Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+{
+    index
+        .get(key)
+        .map_or(&[][..], Vec::as_slice)
+        .iter()
+        .map(move |&i| &records[i])
+}
+
+impl WolfDataset {
+    pub fn new(denning: Vec<DenningPhenology>, reproduction: Vec<ReproductiveSuccess>) -> Self {
+        let denning_index = RecordIndex::build(&denning);
+        let reproduction_index = RecordIndex::build(&reproduction);
+        let mut study_areas = StudyRegistry::new();
+        for record in &denning {
+            study_areas.register(record);
+        }
+        for record in &reproduction {
+            study_areas.register(record);
+        }
+        let study_areas = Arc::new(study_areas);
+        let mut denning_columns = ColumnStore::from_records(&denning, &study_areas);
+        let mut reproduction_columns = ColumnStore::from_records(&reproduction, &study_areas);
+        denning_columns.share_studies(&study_areas);
+        reproduction_columns.share_studies(&study_areas);
+        WolfDataset {
+            denning,
+            reproduction,
+            denning_index,
+            reproduction_index,
+            denning_columns,
+            reproduction_columns,
+            study_areas,
+        }
+    }
+
+    pub fn denning(&self) -> &[DenningPhenology] {
+        &self.denning
+    }
+
+    pub fn reproduction(&self) -> &[ReproductiveSuccess] {
+        &self.reproduction
+    }
+
+    /// Denning records as columns, for queries
+    pub fn denning_columns(&self) -> &ColumnStore {
+        &self.denning_columns
+    }
+
+    /// Reproductive records as columns, for queries
+    pub fn reproduction_columns(&self) -> &ColumnStore {
+        &self.reproduction_columns
+    }
+
+    /// Whether the pack has at least one denning record
+    pub fn has_denning(&self, key: &PackKey) -> bool {
+        self.denning_index.by_pack.contains_key(key)
+    }
+
+    pub fn denning_for_pack<'a>(
+        &'a self,
+        key: &PackKey,
+    ) -> impl Iterator<Item = &'a DenningPhenology> + 'a {
+        lookup(&self.denning, &self.denning_index.by_pack, key)
+    }
+
+    pub fn reproduction_for_pack<'a>(
+        &'a self,
+        key: &PackKey,
+    ) -> impl Iterator<Item = &'a ReproductiveSuccess> + 'a {
+        lookup(&self.reproduction, &self.reproduction_index.by_pack, key)
+    }
+
+    pub fn denning_for_study<'a>(
+        &'a self,
+        study: &str,
+    ) -> impl Iterator<Item = &'a DenningPhenology> + 'a {
+        lookup(&self.denning, &self.denning_index.by_study, study)
+    }
+
+    pub fn reproduction_for_study<'a>(
+        &'a self,
+        study: &str,
+    ) -> impl Iterator<Item = &'a ReproductiveSuccess> + 'a {
+        lookup(&self.reproduction, &self.reproduction_index.by_study, study)
+    }
+
+    pub fn denning_for_year(&self, year: i32) -> impl Iterator<Item = &DenningPhenology> + '_ {
+        lookup(&self.denning, &self.denning_index.by_year, &year)
+    }
+
+    pub fn reproduction_for_year(
+        &self,
+        year: i32,
+    ) -> impl Iterator<Item = &ReproductiveSuccess> + '_ {
+        lookup(&self.reproduction, &self.reproduction_index.by_year, &year)
+    }
+
+    /// Denning records for one pack in one season year
+    pub fn denning_for_season<'a>(
+        &'a self,
+        key: &PackKey,
+        year: i32,
+    ) -> impl Iterator<Item = &'a DenningPhenology> + 'a {
+        lookup(
+            &self.denning,
+            &self.denning_index.by_season,
+            &(key.clone(), year),
+        )
+    }
+
+    /// Reproductive records for one pack in one season year
+    pub fn reproduction_for_season<'a>(
+        &'a self,
+        key: &PackKey,
+        year: i32,
+    ) -> impl Iterator<Item = &'a ReproductiveSuccess> + 'a {
+        lookup(
+            &self.reproduction,
+            &self.reproduction_index.by_season,
+            &(key.clone(), year),
+        )
+    }
+
+    /// Study names present in either dataset, sorted
+    pub fn studies(&self) -> Vec<&str> {
+        let mut studies: Vec<&str> = self
+            .denning_index
+            .by_study
+            .keys()
+            .chain(self.reproduction_index.by_study.keys())
+            .map(String::as_str)
+            .collect();
+        studies.sort_unstable();
+        studies.dedup();
+        studies
+    }
+
+    /// Study areas of both datasets, in the order first seen
+    pub fn study_areas(&self) -> &StudyRegistry {
+        &self.study_areas
+    }
+
+    /// Merges a study metadata sidecar file, returning keys that matched no
+    /// study. The column stores see the new metadata too.
+    pub fn load_study_metadata(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<Vec<String>, WolfDataError> {
+        let unmatched = Arc::make_mut(&mut self.study_areas).load_metadata(path)?;
+        self.denning_columns.share_studies(&self.study_areas);
+        self.reproduction_columns.share_studies(&self.study_areas);
+        Ok(unmatched)
+    }
+
+    /// Season years present in either dataset, sorted
+    pub fn years(&self) -> Vec<i32> {
+        let mut years: Vec<i32> = self
+            .denning_index
+            .by_year
+            .keys()
+            .chain(self.reproduction_index.by_year.keys())
+            .copied()
+            .collect();
+        years.sort_unstable();
+        years.dedup();
+        years
+    }
+
+    /// Pairs each denning record with its season's reproductive outcome
+    pub fn join_pack_seasons(&self, kind: JoinKind) -> PackSeasonJoin {
+        join_pack_seasons(&self.denning, &self.reproduction, kind)
+    }
+}
@@ -0,0 +1,420 @@
+//! Differences between two releases of the wolf datasets
+//!
+//! Records are matched by `uid`, or by pack and season year when uids were
+//! renumbered between releases. The diff lists added and removed records,
+//! every changed field and, per study, how much each numeric covariate moved.
+//! A `DatasetDiff` renders as Markdown or serializes to JSON.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Write;
+
+use serde::{Serialize, Serializer};
+
+use crate::data::PackKey;
+use crate::dataset::WolfDataset;
+use crate::error::WolfDataError;
+use crate::export::{Table, Tabular, Value};
+use crate::record::WolfRecord;
+
+/// How records of the two releases are matched
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKey {
+    #[default]
+    Uid,
+    /// Study, pack id and season year
+    Season,
+}
+
+impl DiffKey {
+    /// Columns that make up the key. Under `Season` the season is placed by
+    /// its date, so the denning day of year is part of the key as well.
+    pub fn columns(self) -> &'static [&'static str] {
+        match self {
+            DiffKey::Uid => &["uid"],
+            DiffKey::Season => &[
+                "study",
+                "pack_id",
+                "denning_date",
+                "denning_doy",
+                "start_date",
+            ],
+        }
+    }
+}
+
+impl fmt::Display for DiffKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffKey::Uid => write!(f, "uid"),
+            DiffKey::Season => write!(f, "study, pack and season year"),
+        }
+    }
+}
+
+/// The key of one record
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RowKey {
+    Uid(u32),
+    Season(PackKey, i32),
+}
+
+impl RowKey {
+    fn of<T: WolfRecord>(record: &T, key: DiffKey) -> Self {
+        match key {
+            DiffKey::Uid => RowKey::Uid(record.uid()),
+            DiffKey::Season => RowKey::Season(record.pack_key(), record.season_year()),
+        }
+    }
+}
+
+impl fmt::Display for RowKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RowKey::Uid(uid) => write!(f, "uid {}", uid),
+            RowKey::Season(key, year) => write!(f, "{}, {}", key, year),
+        }
+    }
+}
+
+impl Serialize for RowKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A record present in only one release
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RowSummary {
+    pub key: RowKey,
+    pub study: String,
+}
+
+/// One field whose value differs between releases
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub column: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// A record present in both releases with at least one changed field
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RowChange {
+    pub key: RowKey,
+    pub study: String,
+    pub changes: Vec<FieldChange>,
+}
+
+/// How one numeric covariate moved within one study, over the records whose
+/// value changed and is present in both releases. Identifiers and key
+/// columns are listed as changes but never summarized here.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CovariateDelta {
+    pub study: String,
+    pub column: String,
+    pub changed: usize,
+    /// Mean of `new - old`
+    pub mean_delta: f64,
+    pub min_delta: f64,
+    pub max_delta: f64,
+}
+
+/// Differences in one record type
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecordDiff {
+    pub key: DiffKey,
+    pub old_rows: usize,
+    pub new_rows: usize,
+    pub added: Vec<RowSummary>,
+    pub removed: Vec<RowSummary>,
+    pub changed: Vec<RowChange>,
+    pub unchanged: usize,
+    /// Keys held by more than one record in a release; only the first
+    /// record with each key is compared
+    pub duplicate_keys: Vec<RowKey>,
+    pub deltas: Vec<CovariateDelta>,
+}
+
+impl RecordDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for RecordDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} removed, {} changed, {} unchanged",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len(),
+            self.unchanged
+        )
+    }
+}
+
+/// Differences in both record types
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DatasetDiff {
+    pub denning: RecordDiff,
+    pub reproduction: RecordDiff,
+}
+
+impl DatasetDiff {
+    pub fn is_empty(&self) -> bool {
+        self.denning.is_empty() && self.reproduction.is_empty()
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<(), WolfDataError> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    pub fn write_markdown<W: Write>(&self, mut writer: W) -> Result<(), WolfDataError> {
+        writeln!(writer, "# Dataset diff")?;
+        write_section(&mut writer, "Denning records", &self.denning)?;
+        write_section(&mut writer, "Reproductive records", &self.reproduction)?;
+        Ok(())
+    }
+}
+
+/// Settings for comparing two releases.
+///
+/// `diff_datasets` uses `DiffOptions::default()`, which matches records by
+/// `uid`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiffOptions {
+    pub key: DiffKey,
+}
+
+impl DiffOptions {
+    pub fn diff_datasets(
+        &self,
+        old: &WolfDataset,
+        new: &WolfDataset,
+    ) -> Result<DatasetDiff, WolfDataError> {
+        Ok(DatasetDiff {
+            denning: self.diff_records(old.denning(), new.denning())?,
+            reproduction: self.diff_records(old.reproduction(), new.reproduction())?,
+        })
+    }
+
+    /// Compares two record sets of the same type, field by field in export
+    /// column order
+    pub fn diff_records<T: Tabular + WolfRecord>(
+        &self,
+        old: &[T],
+        new: &[T],
+    ) -> Result<RecordDiff, WolfDataError> {
+        let old_table = Table::from_records(old)?;
+        let new_table = Table::from_records(new)?;
+        let mut duplicate_keys = Vec::new();
+        let old_index = index_by_key(old, self.key, &mut duplicate_keys);
+        let new_index = index_by_key(new, self.key, &mut duplicate_keys);
+        duplicate_keys.sort();
+        duplicate_keys.dedup();
+
+        let summary = |record: &T| RowSummary {
+            key: RowKey::of(record, self.key),
+            study: record.study().to_string(),
+        };
+        let mut added: Vec<RowSummary> = new_index
+            .iter()
+            .filter(|(key, _)| !old_index.contains_key(*key))
+            .map(|(_, &i)| summary(&new[i]))
+            .collect();
+        let mut removed: Vec<RowSummary> = old_index
+            .iter()
+            .filter(|(key, _)| !new_index.contains_key(*key))
+            .map(|(_, &i)| summary(&old[i]))
+            .collect();
+        added.sort_by(|a, b| a.key.cmp(&b.key));
+        removed.sort_by(|a, b| a.key.cmp(&b.key));
+
+        let mut matched: Vec<(&RowKey, usize, usize)> = old_index
+            .iter()
+            .filter_map(|(key, &i)| new_index.get(key).map(|&j| (key, i, j)))
+            .collect();
+        matched.sort();
+
+        let columns = old_table.columns();
+        let summarized = |column: &str| {
+            T::NUMERIC_COLUMNS.contains(&column) && !self.key.columns().contains(&column)
+        };
+        let mut changed = Vec::new();
+        let mut deltas: BTreeMap<(String, String), Vec<f64>> = BTreeMap::new();
+        for (key, i, j) in matched {
+            let (old_row, new_row) = (&old_table.rows()[i], &new_table.rows()[j]);
+            let changes: Vec<FieldChange> = columns
+                .iter()
+                .zip(old_row.iter().zip(new_row))
+                .filter(|(_, (a, b))| a != b)
+                .map(|(column, (a, b))| FieldChange {
+                    column: column.name.clone(),
+                    old: a.clone(),
+                    new: b.clone(),
+                })
+                .collect();
+            if changes.is_empty() {
+                continue;
+            }
+            let study = new[j].study().to_string();
+            for change in changes.iter().filter(|c| summarized(&c.column)) {
+                if let (Some(a), Some(b)) = (change.old.as_f64(), change.new.as_f64()) {
+                    deltas
+                        .entry((study.clone(), change.column.clone()))
+                        .or_default()
+                        .push(b - a);
+                }
+            }
+            changed.push(RowChange {
+                key: key.clone(),
+                study,
+                changes,
+            });
+        }
+
+        let deltas = deltas
+            .into_iter()
+            .map(|((study, column), values)| CovariateDelta {
+                study,
+                column,
+                changed: values.len(),
+                mean_delta: values.iter().sum::<f64>() / values.len() as f64,
+                min_delta: values.iter().copied().fold(f64::INFINITY, f64::min),
+                max_delta: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            })
+            .collect();
+
+        let compared = old_index.len() - removed.len();
+        Ok(RecordDiff {
+            key: self.key,
+            old_rows: old.len(),
+            new_rows: new.len(),
+            unchanged: compared - changed.len(),
+            added,
+            removed,
+            changed,
+            duplicate_keys,
+            deltas,
+        })
+    }
+}
+
+/// Compares two releases, matching records by `uid`
+pub fn diff_datasets(old: &WolfDataset, new: &WolfDataset) -> Result<DatasetDiff, WolfDataError> {
+    DiffOptions::default().diff_datasets(old, new)
+}
+
+/// Position of the first record with each key, noting keys seen twice
+fn index_by_key<T: WolfRecord>(
+    records: &[T],
+    key: DiffKey,
+    duplicates: &mut Vec<RowKey>,
+) -> HashMap<RowKey, usize> {
+    let mut index = HashMap::with_capacity(records.len());
+    for (i, record) in records.iter().enumerate() {
+        let row_key = RowKey::of(record, key);
+        match index.entry(row_key) {
+            Entry::Occupied(entry) => duplicates.push(entry.key().clone()),
+            Entry::Vacant(entry) => {
+                entry.insert(i);
+            }
+        }
+    }
+    index
+}
+
+fn write_section<W: Write>(
+    writer: &mut W,
+    title: &str,
+    diff: &RecordDiff,
+) -> Result<(), WolfDataError> {
+    writeln!(writer, "\n## {}\n", title)?;
+    writeln!(
+        writer,
+        "Matched by {}: {} rows before, {} after; {}.",
+        diff.key, diff.old_rows, diff.new_rows, diff
+    )?;
+    if !diff.duplicate_keys.is_empty() {
+        let keys: Vec<String> = diff.duplicate_keys.iter().map(RowKey::to_string).collect();
+        writeln!(
+            writer,
+            "\nDuplicate keys (only the first record compared): {}",
+            keys.join("; ")
+        )?;
+    }
+
+    for (heading, rows) in [("Added", &diff.added), ("Removed", &diff.removed)] {
+        if rows.is_empty() {
+            continue;
+        }
+        writeln!(
+            writer,
+            "\n### {}\n\n| key | study |\n| --- | --- |",
+            heading
+        )?;
+        for row in rows {
+            writeln!(
+                writer,
+                "| {} | {} |",
+                cell(&row.key.to_string()),
+                cell(&row.study)
+            )?;
+        }
+    }
+
+    if !diff.changed.is_empty() {
+        writeln!(
+            writer,
+            "\n### Changed\n\n| key | study | column | old | new |\n| --- | --- | --- | --- | --- |"
+        )?;
+        for row in &diff.changed {
+            for change in &row.changes {
+                writeln!(
+                    writer,
+                    "| {} | {} | {} | {} | {} |",
+                    cell(&row.key.to_string()),
+                    cell(&row.study),
+                    change.column,
+                    cell(&change.old.to_string()),
+                    cell(&change.new.to_string())
+                )?;
+            }
+        }
+    }
+
+    if !diff.deltas.is_empty() {
+        writeln!(
+            writer,
+            "\n### Covariate deltas by study\n\n\
+             | study | column | changed | mean Δ | min Δ | max Δ |\n\
+             | --- | --- | ---: | ---: | ---: | ---: |"
+        )?;
+        for delta in &diff.deltas {
+            writeln!(
+                writer,
+                "| {} | {} | {} | {:.3} | {:.3} | {:.3} |",
+                cell(&delta.study),
+                delta.column,
+                delta.changed,
+                delta.mean_delta,
+                delta.min_delta,
+                delta.max_delta
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Escapes a Markdown table cell; empty cells read as "(missing)"
+fn cell(text: &str) -> String {
+    match text {
+        "" => "(missing)".to_string(),
+        text => text.replace('|', "\\|"),
+    }
+}
@@ -0,0 +1,186 @@
+//! Error type shared by the data loaders and analysis entry points
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::data::{RecordError, RejectReason};
+use crate::schema::SchemaReport;
+
+/// Everything that can go wrong while loading or analysing the wolf data
+#[derive(Debug)]
+pub enum WolfDataError {
+    /// A file could not be opened, read or written
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// The CSV itself was unreadable (bad quoting, wrong field count, bad UTF-8)
+    Csv { line: Option<u64>, message: String },
+    /// The header does not have the columns the record type needs
+    Schema(SchemaReport),
+    /// A single field could not be parsed into its column's type
+    Parse {
+        line: u64,
+        column: String,
+        value: String,
+        message: String,
+    },
+    /// The data parsed but failed a check (missing values, rejection threshold, ...)
+    Validation {
+        line: Option<u64>,
+        column: Option<String>,
+        message: String,
+    },
+    /// A chart could not be drawn
+    Plot(String),
+    /// A table could not be encoded for export
+    Export(String),
+    /// An SQL statement failed to prepare or run
+    Sql(String),
+}
+
+impl WolfDataError {
+    /// Attaches the path being read to an I/O error
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        WolfDataError::Io {
+            path: Some(path.into()),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for WolfDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WolfDataError::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "{}: {}", path.display(), source),
+            WolfDataError::Io { path: None, source } => write!(f, "I/O error: {}", source),
+            WolfDataError::Csv {
+                line: Some(line),
+                message,
+            } => write!(f, "line {}: invalid CSV: {}", line, message),
+            WolfDataError::Csv {
+                line: None,
+                message,
+            } => write!(f, "invalid CSV: {}", message),
+            WolfDataError::Schema(report) => write!(f, "header does not match: {}", report),
+            WolfDataError::Parse {
+                line,
+                column,
+                value,
+                message,
+            } => write!(
+                f,
+                "line {}: cannot parse `{}` = {:?}: {}",
+                line, column, value, message
+            ),
+            WolfDataError::Validation {
+                line,
+                column,
+                message,
+            } => {
+                if let Some(line) = line {
+                    write!(f, "line {}: ", line)?;
+                }
+                if let Some(column) = column {
+                    write!(f, "`{}`: ", column)?;
+                }
+                write!(f, "{}", message)
+            }
+            WolfDataError::Plot(message) => write!(f, "plotting failed: {}", message),
+            WolfDataError::Export(message) => write!(f, "export failed: {}", message),
+            WolfDataError::Sql(message) => write!(f, "SQL error: {}", message),
+        }
+    }
+}
+
+impl Error for WolfDataError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WolfDataError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WolfDataError {
+    fn from(source: io::Error) -> Self {
+        WolfDataError::Io { path: None, source }
+    }
+}
+
+impl From<csv::Error> for WolfDataError {
+    fn from(error: csv::Error) -> Self {
+        let line = error.position().map(|p| p.line());
+        let message = error.to_string();
+        match error.into_kind() {
+            csv::ErrorKind::Io(source) => WolfDataError::Io { path: None, source },
+            _ => WolfDataError::Csv { line, message },
+        }
+    }
+}
+
+impl From<serde_json::Error> for WolfDataError {
+    fn from(error: serde_json::Error) -> Self {
+        match error.io_error_kind() {
+            Some(_) => WolfDataError::Io {
+                path: None,
+                source: error.into(),
+            },
+            None => WolfDataError::Export(error.to_string()),
+        }
+    }
+}
+
+impl From<parquet::errors::ParquetError> for WolfDataError {
+    fn from(error: parquet::errors::ParquetError) -> Self {
+        WolfDataError::Export(error.to_string())
+    }
+}
+
+#[cfg(feature = "sql")]
+impl From<rusqlite::Error> for WolfDataError {
+    fn from(error: rusqlite::Error) -> Self {
+        WolfDataError::Sql(error.to_string())
+    }
+}
+
+/// Converts a rejected row into the error for its first failing field
+impl From<RecordError> for WolfDataError {
+    fn from(error: RecordError) -> Self {
+        let Some(issue) = error.issues.into_iter().next() else {
+            return WolfDataError::Validation {
+                line: Some(error.line),
+                column: None,
+                message: "row rejected".to_string(),
+            };
+        };
+        match issue.reason {
+            RejectReason::Parse(message) => WolfDataError::Parse {
+                line: error.line,
+                column: issue.column,
+                value: issue.value,
+                message,
+            },
+            RejectReason::Malformed(message) => WolfDataError::Csv {
+                line: Some(error.line),
+                message,
+            },
+            reason => WolfDataError::Validation {
+                line: Some(error.line),
+                column: Some(issue.column),
+                message: reason.to_string(),
+            },
+        }
+    }
+}
+
+impl<E: Error + Send + Sync> From<plotters::drawing::DrawingAreaErrorKind<E>> for WolfDataError {
+    fn from(error: plotters::drawing::DrawingAreaErrorKind<E>) -> Self {
+        WolfDataError::Plot(error.to_string())
+    }
+}
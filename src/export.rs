@@ -0,0 +1,496 @@
+//! Writers for record sets and joined tables
+//!
+//! Records are converted to a `Table` through their `Serialize` impls, so
+//! exported columns carry the original ABoVE header names. A `Table` can
+//! then be written as CSV, JSON Lines or Parquet; dates are written as
+//! ISO-8601 (`YYYY-MM-DD`) in text formats and as Parquet `DATE` columns.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+use parquet::basic::{LogicalType, Repetition, Type as PhysicalType};
+use parquet::column::writer::ColumnWriterImpl;
+use parquet::data_type::{ByteArray, ByteArrayType, DataType, DoubleType, Int32Type, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type;
+use serde::ser::{SerializeMap, Serializer};
+use serde::Serialize;
+
+use crate::data::{DenningPhenology, PackSeason, ReproductiveSuccess};
+use crate::error::WolfDataError;
+
+/// Storage type of a table column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Text,
+    Date,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Column {
+    pub name: String,
+    pub kind: ColumnType,
+}
+
+impl Column {
+    pub fn new(name: impl Into<String>, kind: ColumnType) -> Self {
+        Column {
+            name: name.into(),
+            kind,
+        }
+    }
+}
+
+/// A single cell; `Null` is written as an empty CSV field, JSON `null` or
+/// a Parquet null
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    Date(NaiveDate),
+}
+
+impl Value {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(v) => Some(*v as f64),
+            Value::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Converts a serialized field to the column's type
+    fn from_json(value: &serde_json::Value, kind: ColumnType) -> Value {
+        match (value, kind) {
+            (serde_json::Value::Null, _) => Value::Null,
+            (v, ColumnType::Integer) => v.as_i64().map_or(Value::Null, Value::Integer),
+            (v, ColumnType::Float) => v.as_f64().map_or(Value::Null, Value::Float),
+            (serde_json::Value::String(s), ColumnType::Date) => {
+                NaiveDate::parse_from_str(s, "%Y-%m-%d").map_or(Value::Null, Value::Date)
+            }
+            (serde_json::Value::String(s), _) => Value::Text(s.clone()),
+            (v, _) => Value::Text(v.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Integer(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{}", v),
+            Value::Text(v) => write!(f, "{}", v),
+            Value::Date(v) => write!(f, "{}", v.format("%Y-%m-%d")),
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Null => serializer.serialize_none(),
+            Value::Integer(v) => serializer.serialize_i64(*v),
+            Value::Float(v) => serializer.serialize_f64(*v),
+            Value::Text(v) => serializer.serialize_str(v),
+            Value::Date(v) => serializer.collect_str(&v.format("%Y-%m-%d")),
+        }
+    }
+}
+
+/// Rows of typed cells under named columns
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn new(columns: Vec<Column>) -> Self {
+        Table {
+            columns,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Appends a row, which must have one value per column
+    pub fn push_row(&mut self, row: Vec<Value>) -> Result<(), WolfDataError> {
+        if row.len() != self.columns.len() {
+            return Err(WolfDataError::Validation {
+                line: None,
+                column: None,
+                message: format!(
+                    "row has {} values but the table has {} columns",
+                    row.len(),
+                    self.columns.len()
+                ),
+            });
+        }
+        self.rows.push(row);
+        Ok(())
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn rows(&self) -> &[Vec<Value>] {
+        &self.rows
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Position of the column called `name`
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.name == name)
+    }
+
+    /// `(x, y)` pairs from two numeric columns, skipping rows where either
+    /// is missing; for plotting
+    pub fn series(&self, x: &str, y: &str) -> Vec<(f64, f64)> {
+        match (self.column_index(x), self.column_index(y)) {
+            (Some(x), Some(y)) => self
+                .rows
+                .iter()
+                .filter_map(|row| Some((row[x].as_f64()?, row[y].as_f64()?)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Builds a table with one row per record and the record type's columns
+    pub fn from_records<T: Tabular>(records: &[T]) -> Result<Self, WolfDataError> {
+        let mut table = Table::new(T::columns());
+        for record in records {
+            let object = serde_json::to_value(record)?;
+            let row = table
+                .columns
+                .iter()
+                .map(|c| Value::from_json(&object[c.name.as_str()], c.kind))
+                .collect();
+            table.rows.push(row);
+        }
+        Ok(table)
+    }
+
+    /// Builds the joined pack-season table.
+    ///
+    /// Denning columns keep their ABoVE names. Reproductive columns keep
+    /// theirs too, except the ones the denning file also has, which get a
+    /// `repro_` prefix. Seasons without a reproductive record have nulls
+    /// in the reproductive columns.
+    pub fn from_pack_seasons(seasons: &[PackSeason]) -> Result<Self, WolfDataError> {
+        let denning_columns = DenningPhenology::columns();
+        let reproduction_columns: Vec<Column> = ReproductiveSuccess::columns()
+            .into_iter()
+            .filter(|c| c.name != "study" && c.name != "pack_id")
+            .collect();
+        let renamed = |c: &Column| {
+            if denning_columns.iter().any(|d| d.name == c.name) {
+                Column::new(format!("repro_{}", c.name), c.kind)
+            } else {
+                c.clone()
+            }
+        };
+
+        let mut columns = vec![Column::new("season_year", ColumnType::Integer)];
+        columns.extend(denning_columns.iter().cloned());
+        columns.extend(reproduction_columns.iter().map(renamed));
+        let mut table = Table::new(columns);
+
+        for season in seasons {
+            let denning = serde_json::to_value(&season.denning)?;
+            let reproduction = match &season.reproduction {
+                Some(r) => serde_json::to_value(r)?,
+                None => serde_json::Value::Null,
+            };
+            let mut row = vec![Value::Integer(i64::from(season.year))];
+            row.extend(
+                denning_columns
+                    .iter()
+                    .map(|c| Value::from_json(&denning[c.name.as_str()], c.kind)),
+            );
+            row.extend(
+                reproduction_columns
+                    .iter()
+                    .map(|c| Value::from_json(&reproduction[c.name.as_str()], c.kind)),
+            );
+            table.rows.push(row);
+        }
+        Ok(table)
+    }
+}
+
+/// Plain-text rendering with aligned columns; floats are shown to three
+/// decimals and missing cells as `-`
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|value| match value {
+                        Value::Null => "-".to_string(),
+                        Value::Float(v) => format!("{:.3}", v),
+                        other => other.to_string(),
+                    })
+                    .collect()
+            })
+            .collect();
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(j, c)| {
+                cells
+                    .iter()
+                    .map(|row| row[j].chars().count())
+                    .fold(c.name.chars().count(), usize::max)
+            })
+            .collect();
+
+        let header: Vec<String> = self
+            .columns
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{:<w$}", c.name, w = w))
+            .collect();
+        writeln!(f, "{}", header.join("  ").trim_end())?;
+        let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        write!(f, "{}", rule.join("  "))?;
+        for row in &cells {
+            let line: Vec<String> = row
+                .iter()
+                .zip(self.columns.iter().zip(&widths))
+                .map(|(cell, (c, w))| match c.kind {
+                    ColumnType::Integer | ColumnType::Float => format!("{:>w$}", cell, w = w),
+                    _ => format!("{:<w$}", cell, w = w),
+                })
+                .collect();
+            write!(f, "\n{}", line.join("  ").trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Records that can be turned into a `Table`
+pub trait Tabular: Serialize {
+    /// Columns in export order, named as in the source files
+    fn columns() -> Vec<Column>;
+}
+
+fn typed_columns(names: &[&str], kind: impl Fn(&str) -> ColumnType) -> Vec<Column> {
+    names.iter().map(|name| Column::new(*name, kind(name))).collect()
+}
+
+impl Tabular for DenningPhenology {
+    fn columns() -> Vec<Column> {
+        typed_columns(DenningPhenology::COLUMNS, |name| match name {
+            "uid" | "pack_id" | "denning_doy" | "denned" => ColumnType::Integer,
+            "study" => ColumnType::Text,
+            "denning_date" => ColumnType::Date,
+            _ => ColumnType::Float,
+        })
+    }
+}
+
+impl Tabular for ReproductiveSuccess {
+    fn columns() -> Vec<Column> {
+        typed_columns(ReproductiveSuccess::COLUMNS, |name| match name {
+            "uid" | "pack_id" | "success" => ColumnType::Integer,
+            "study" => ColumnType::Text,
+            "start_date" | "end_date" => ColumnType::Date,
+            _ => ColumnType::Float,
+        })
+    }
+}
+
+/// File formats a `Table` can be written as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+    Parquet,
+}
+
+impl ExportFormat {
+    /// Picks the format from a file extension (`csv`, `jsonl`/`ndjson`, `parquet`)
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "jsonl" | "ndjson" => Some(ExportFormat::JsonLines),
+            "parquet" => Some(ExportFormat::Parquet),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `table` to `path`, choosing the format from its extension
+pub fn export_table(table: &Table, path: impl AsRef<Path>) -> Result<(), WolfDataError> {
+    let path = path.as_ref();
+    let format = ExportFormat::from_path(path).ok_or_else(|| WolfDataError::Validation {
+        line: None,
+        column: None,
+        message: format!(
+            "cannot tell the export format of {}; use .csv, .jsonl or .parquet",
+            path.display()
+        ),
+    })?;
+    let file = File::create(path).map_err(|e| WolfDataError::io(path, e))?;
+    write_table(table, format, BufWriter::new(file))
+}
+
+pub fn write_table<W: Write + Send>(
+    table: &Table,
+    format: ExportFormat,
+    writer: W,
+) -> Result<(), WolfDataError> {
+    match format {
+        ExportFormat::Csv => write_csv(table, writer),
+        ExportFormat::JsonLines => write_json_lines(table, writer),
+        ExportFormat::Parquet => write_parquet(table, writer),
+    }
+}
+
+pub fn write_csv<W: Write>(table: &Table, writer: W) -> Result<(), WolfDataError> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(table.columns.iter().map(|c| &c.name))?;
+    for row in &table.rows {
+        writer.write_record(row.iter().map(Value::to_string))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes one JSON object per row, with keys in column order
+pub fn write_json_lines<W: Write>(table: &Table, mut writer: W) -> Result<(), WolfDataError> {
+    for row in &table.rows {
+        serde_json::to_writer(
+            &mut writer,
+            &JsonRow {
+                columns: &table.columns,
+                values: row,
+            },
+        )?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+struct JsonRow<'a> {
+    columns: &'a [Column],
+    values: &'a [Value],
+}
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.columns.len()))?;
+        for (column, value) in self.columns.iter().zip(self.values) {
+            map.serialize_entry(&column.name, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Writes the table as a single Parquet row group with nullable columns
+pub fn write_parquet<W: Write + Send>(table: &Table, writer: W) -> Result<(), WolfDataError> {
+    let fields = table
+        .columns
+        .iter()
+        .map(|c| {
+            let (physical, logical) = match c.kind {
+                ColumnType::Integer => (PhysicalType::INT64, None),
+                ColumnType::Float => (PhysicalType::DOUBLE, None),
+                ColumnType::Text => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+                ColumnType::Date => (PhysicalType::INT32, Some(LogicalType::Date)),
+            };
+            Type::primitive_type_builder(&c.name, physical)
+                .with_repetition(Repetition::OPTIONAL)
+                .with_logical_type(logical)
+                .build()
+                .map(Arc::new)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let schema = Type::group_type_builder("schema")
+        .with_fields(fields)
+        .build()?;
+
+    let properties = Arc::new(WriterProperties::builder().build());
+    let mut file = SerializedFileWriter::new(writer, Arc::new(schema), properties)?;
+    let mut row_group = file.next_row_group()?;
+    let mut index = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        let cells = table.rows.iter().map(|row| &row[index]);
+        match table.columns[index].kind {
+            ColumnType::Integer => {
+                let cells: Vec<_> = cells
+                    .map(|v| match v {
+                        Value::Integer(v) => Some(*v),
+                        _ => None,
+                    })
+                    .collect();
+                write_column(column.typed::<Int64Type>(), &cells)?;
+            }
+            ColumnType::Float => {
+                let cells: Vec<_> = cells.map(Value::as_f64).collect();
+                write_column(column.typed::<DoubleType>(), &cells)?;
+            }
+            ColumnType::Text => {
+                let cells: Vec<_> = cells
+                    .map(|v| match v {
+                        Value::Null => None,
+                        v => Some(ByteArray::from(v.to_string().as_str())),
+                    })
+                    .collect();
+                write_column(column.typed::<ByteArrayType>(), &cells)?;
+            }
+            ColumnType::Date => {
+                let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).expect("valid date");
+                let cells: Vec<_> = cells
+                    .map(|v| match v {
+                        Value::Date(d) => i32::try_from((*d - epoch).num_days()).ok(),
+                        _ => None,
+                    })
+                    .collect();
+                write_column(column.typed::<Int32Type>(), &cells)?;
+            }
+        }
+        column.close()?;
+        index += 1;
+    }
+    row_group.close()?;
+    file.close()?;
+    Ok(())
+}
+
+/// Writes nullable cells as values plus definition levels
+fn write_column<T: DataType>(
+    writer: &mut ColumnWriterImpl<'_, T>,
+    cells: &[Option<T::T>],
+) -> Result<(), WolfDataError>
+where
+    T::T: Clone,
+{
+    let levels: Vec<i16> = cells.iter().map(|c| i16::from(c.is_some())).collect();
+    let values: Vec<T::T> = cells.iter().flatten().cloned().collect();
+    writer.write_batch(&values, Some(&levels), None)?;
+    Ok(())
+}
@@ -0,0 +1,176 @@
+//! Numeric design matrices for modelling
+//!
+//! `to_feature_matrix` turns any `WolfRecord` slice (denning records,
+//! reproductive records or joined pack-seasons) into an `Array2<f64>` with
+//! one row per kept record. Row ids map model output back to packs.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use ndarray::Array2;
+
+use crate::data::PackKey;
+use crate::error::WolfDataError;
+use crate::missing::{impute_column, MissingPolicy, SeasonKey};
+use crate::record::WolfRecord;
+
+/// Identifies the record behind a matrix row
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RowId {
+    /// Position of the record in the input slice
+    pub index: usize,
+    /// `uid` of the record; the denning record's for joined pack-seasons
+    pub uid: u32,
+    pub key: PackKey,
+    pub year: i32,
+}
+
+impl fmt::Display for RowId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "uid {} ({}, {})", self.uid, self.key, self.year)
+    }
+}
+
+/// How `study` enters the matrix
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum StudyEncoding {
+    /// No study columns
+    #[default]
+    Omit,
+    /// One 0/1 column per study
+    OneHot,
+    /// One 0/1 column per study except the first, which becomes the
+    /// reference level; use this with models that have an intercept
+    Dummy,
+}
+
+/// The matrix, its column names and the id of each row
+pub type FeatureMatrix = (Array2<f64>, Vec<String>, Vec<RowId>);
+
+/// Settings for building a feature matrix.
+///
+/// `to_feature_matrix` uses `FeatureOptions::default()`: rows with any
+/// missing feature are dropped, values are left on their own scale and
+/// study is omitted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeatureOptions {
+    /// `DropAny` and `DropIfMissing` remove rows, `Impute` fills values by
+    /// study or pack; anything still missing is `NaN`
+    pub missing: MissingPolicy,
+    /// Centre each covariate column and scale it to unit (sample) standard
+    /// deviation; study columns are left as 0/1
+    pub standardize: bool,
+    pub study: StudyEncoding,
+}
+
+impl FeatureOptions {
+    pub fn to_matrix<T: WolfRecord>(
+        &self,
+        records: &[T],
+        names: &[&str],
+    ) -> Result<FeatureMatrix, WolfDataError> {
+        if let Some(unknown) = names.iter().find(|n| !T::NUMERIC_COLUMNS.contains(n)) {
+            return Err(WolfDataError::Validation {
+                line: None,
+                column: Some(unknown.to_string()),
+                message: format!(
+                    "not a numeric column of this record type; expected one of {}",
+                    T::NUMERIC_COLUMNS.join(", ")
+                ),
+            });
+        }
+
+        let required: Vec<&str> = match &self.missing {
+            MissingPolicy::DropAny => names.to_vec(),
+            MissingPolicy::DropIfMissing(columns) => columns.iter().map(String::as_str).collect(),
+            MissingPolicy::KeepNone | MissingPolicy::Impute(_) => Vec::new(),
+        };
+        let kept: Vec<(usize, &T)> = records
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| required.iter().all(|c| r.covariate(c).is_some()))
+            .collect();
+
+        let mut values: Vec<Vec<Option<f64>>> = kept
+            .iter()
+            .map(|(_, r)| names.iter().map(|n| r.covariate(n)).collect())
+            .collect();
+        if let MissingPolicy::Impute(method) = self.missing {
+            // Imputed the same way `MissingPolicy::Impute` fills loaded records
+            let keys: Vec<SeasonKey> = kept
+                .iter()
+                .map(|(_, r)| (r.pack_key(), r.season_date()))
+                .collect();
+            for j in 0..names.len() {
+                let mut column: Vec<Option<f64>> = values.iter().map(|row| row[j]).collect();
+                impute_column(&keys, &mut column, method);
+                for (row, value) in values.iter_mut().zip(column) {
+                    row[j] = value;
+                }
+            }
+        }
+
+        let studies: Vec<&str> = match self.study {
+            StudyEncoding::Omit => Vec::new(),
+            encoding => {
+                let all: BTreeSet<&str> = kept.iter().map(|(_, r)| r.study()).collect();
+                let skip = usize::from(encoding == StudyEncoding::Dummy);
+                all.into_iter().skip(skip).collect()
+            }
+        };
+
+        let mut matrix = Array2::from_elem((kept.len(), names.len() + studies.len()), f64::NAN);
+        for (i, row) in values.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                if let Some(value) = value {
+                    matrix[[i, j]] = *value;
+                }
+            }
+            let study = kept[i].1.study();
+            for (k, name) in studies.iter().enumerate() {
+                matrix[[i, names.len() + k]] = if *name == study { 1.0 } else { 0.0 };
+            }
+        }
+
+        if self.standardize {
+            for j in 0..names.len() {
+                let mut column = matrix.column_mut(j);
+                let observed: Vec<f64> = column.iter().copied().filter(|v| !v.is_nan()).collect();
+                if observed.is_empty() {
+                    continue;
+                }
+                let n = observed.len() as f64;
+                let mean = observed.iter().sum::<f64>() / n;
+                let sd = if observed.len() > 1 {
+                    (observed.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+                } else {
+                    0.0
+                };
+                // A constant column is only centred
+                let scale = if sd > 0.0 { sd } else { 1.0 };
+                column.mapv_inplace(|v| (v - mean) / scale);
+            }
+        }
+
+        let mut columns: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        columns.extend(studies.iter().map(|s| format!("study:{}", s)));
+        let rows = kept
+            .iter()
+            .map(|(index, r)| RowId {
+                index: *index,
+                uid: r.uid(),
+                key: r.pack_key(),
+                year: r.season_year(),
+            })
+            .collect();
+        Ok((matrix, columns, rows))
+    }
+}
+
+/// Builds a feature matrix with the default `FeatureOptions`
+pub fn to_feature_matrix<T: WolfRecord>(
+    records: &[T],
+    names: &[&str],
+) -> Result<FeatureMatrix, WolfDataError> {
+    FeatureOptions::default().to_matrix(records, names)
+}
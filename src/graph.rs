@@ -4,58 +4,58 @@
 
 use petgraph::graph::{Graph, NodeIndex};
 use petgraph::Undirected;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
-use crate::data::DenningPhenology;
+use crate::data::PackKey;
+use crate::record::WolfRecord;
 
 /// Struct representing a wolf pack node in the graph
-/// Each node stores pack ID, study area, and location
+/// Each node stores the pack's identity (study + pack ID) and location
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct WolfNode {
-    pub pack_id: u32,
-    pub study: String,
+    pub key: PackKey,
     pub latitude: f64,
     pub longitude: f64,
 }
 
-/// Builds a graph from wolf denning data.
+/// Builds a graph from denning or reproductive records.
 ///
 /// Nodes = packs  
 /// Edges = packs in the same study area (co-location)
 ///
 /// # Arguments
-/// * `denning_data` - List of denning records
+/// * `records` - Denning or reproductive records
 ///
 /// # Returns
 /// * An undirected `Graph` of `WolfNode` with empty edge weights
-pub fn build_graph(denning_data: &[DenningPhenology]) -> Graph<WolfNode, (), Undirected> {
+pub fn build_graph<T: WolfRecord>(records: &[T]) -> Graph<WolfNode, (), Undirected> {
     let mut graph = Graph::<WolfNode, (), Undirected>::new_undirected();
-    let mut node_map = HashMap::new(); // Maps pack_id to graph node index
+    let mut node_map = HashMap::new(); // Maps pack key to graph node index
 
     // Add nodes for each unique pack
-    for record in denning_data {
-        if !node_map.contains_key(&record.pack_id) {
-            let node = WolfNode {
-                pack_id: record.pack_id,
-                study: record.study.clone(),
-                latitude: record.latitude_study,
-                longitude: record.longitude_study,
-            };
-            let idx = graph.add_node(node);
-            node_map.insert(record.pack_id, idx);
-        }
+    for record in records {
+        node_map.entry(record.pack_key()).or_insert_with(|| {
+            let (latitude, longitude) = record.location();
+            graph.add_node(WolfNode {
+                key: record.pack_key(),
+                latitude,
+                longitude,
+            })
+        });
     }
 
-    // Group node indices by shared study area
-    let mut study_map: HashMap<String, Vec<NodeIndex>> = HashMap::new();
-    for record in denning_data {
-        let idx = node_map[&record.pack_id];
-        study_map.entry(record.study.clone()).or_default().push(idx);
+    // Group node indices by shared study area, once per pack however many
+    // seasons it has
+    let mut study_map: HashMap<String, BTreeSet<NodeIndex>> = HashMap::new();
+    for record in records {
+        let idx = node_map[&record.pack_key()];
+        study_map.entry(record.study().to_string()).or_default().insert(idx);
     }
 
     // Fully connect nodes within each study area
     for nodes in study_map.values() {
+        let nodes: Vec<NodeIndex> = nodes.iter().copied().collect();
         for i in 0..nodes.len() {
             for j in i + 1..nodes.len() {
                 graph.add_edge(nodes[i], nodes[j], ());
@@ -72,14 +72,16 @@ pub fn build_graph(denning_data: &[DenningPhenology]) -> Graph<WolfNode, (), Und
 /// * `graph` - Reference to the undirected graph
 ///
 /// # Returns
-/// * `HashMap<pack_id, degree>`
-pub fn compute_degree_centrality(graph: &Graph<WolfNode, (), Undirected>) -> HashMap<u32, usize> {
+/// * `HashMap<pack key, degree>`
+pub fn compute_degree_centrality(
+    graph: &Graph<WolfNode, (), Undirected>,
+) -> HashMap<PackKey, usize> {
     graph
         .node_indices()
         .map(|node| {
-            let pack_id = graph[node].pack_id;
+            let key = graph[node].key.clone();
             let degree = graph.edges(node).count();
-            (pack_id, degree)
+            (key, degree)
         })
         .collect()
 }
@@ -89,12 +91,12 @@ pub fn compute_degree_centrality(graph: &Graph<WolfNode, (), Undirected>) -> Has
 /// # Arguments
 /// * `centrality` - Centrality scores
 /// * `top_n` - Number of top entries to print
-pub fn print_top_central_packs(centrality: &HashMap<u32, usize>, top_n: usize) {
+pub fn print_top_central_packs(centrality: &HashMap<PackKey, usize>, top_n: usize) {
     let mut ranked: Vec<_> = centrality.iter().collect();
-    ranked.sort_by(|a, b| b.1.cmp(a.1));
+    ranked.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
 
     println!("\nTop {} packs by degree centrality:", top_n);
-    for (pack_id, degree) in ranked.into_iter().take(top_n) {
-        println!("Pack {} → degree {}", pack_id, degree);
+    for (key, degree) in ranked.into_iter().take(top_n) {
+        println!("{} → degree {}", key, degree);
     }
 }
@@ -1,33 +1,151 @@
 // src/lib.rs
+pub mod columnar;
 pub mod data;
+pub mod dataset;
+pub mod diff;
+pub mod error;
+pub mod export;
+pub mod features;
 pub mod graph;
+pub mod missing;
+pub mod record;
+pub mod schema;
+pub mod snapshot;
+pub mod stats;
+#[cfg(feature = "sql")]
+pub mod sql;
+#[cfg(feature = "store")]
+pub mod store;
+pub mod study;
+pub mod summary;
+pub mod synthetic;
+pub mod units;
+pub mod validation;
 
+use std::collections::BTreeSet;
+use std::path::Path;
 
-pub fn analyze_temperature_impact(
-    denning_data: &[data::DenningPhenology],
-    reproductive_data: &[data::ReproductiveSuccess],
-) -> Vec<f64> {
-    reproductive_data
-        .iter()
+use plotters::prelude::*;
+
+use columnar::{Aggregate, ColumnStore, GroupKey, Predicate};
+use data::{PackKey, ReproductiveSuccess};
+use dataset::WolfDataset;
+use error::WolfDataError;
+use record::WolfRecord;
+use summary::{summarize, Summary};
+use units::Quantity;
+
+pub fn analyze_temperature_impact(dataset: &WolfDataset) -> Vec<f64> {
+    reproduction_with_denning(dataset)
+        .into_iter()
         .filter_map(|r| match (r.summer_tmax, r.winter_tmax) {
-            (Some(s), Some(w)) if denning_data.iter().any(|d| d.pack_id == 
-r.pack_id) => Some((s - w) as f64),
+            (Some(s), Some(w)) => Some(s.value() - w.value()),
             _ => None,
         })
         .collect()
 }
 
-pub fn analyze_snow_cover_impact(
-    denning_data: &[data::DenningPhenology],
-    reproductive_data: &[data::ReproductiveSuccess],
-) -> Vec<f64> {
-    reproductive_data
-        .iter()
+pub fn analyze_snow_cover_impact(dataset: &WolfDataset) -> Vec<f64> {
+    reproduction_with_denning(dataset)
+        .into_iter()
         .filter_map(|r| match (r.winter_swe, r.fall_prcp) {
-            (Some(w), Some(f)) if denning_data.iter().any(|d| d.pack_id == 
-r.pack_id) => Some(w as f64 - f as f64),
+            (Some(w), Some(f)) => Some(w.value() - f.value()),
             _ => None,
         })
         .collect()
 }
 
+/// Reproductive records whose pack has a denning record in the same
+/// season year, ordered by pack and season
+fn reproduction_with_denning(dataset: &WolfDataset) -> Vec<&ReproductiveSuccess> {
+    let mut seasons: Vec<(PackKey, i32)> =
+        dataset.denning().iter().map(WolfRecord::season_key).collect();
+    seasons.sort_unstable();
+    seasons.dedup();
+    seasons
+        .iter()
+        .flat_map(|(key, year)| dataset.reproduction_for_season(key, *year))
+        .collect()
+}
+
+/// Descriptive statistics of the differences returned by
+/// `analyze_temperature_impact` or `analyze_snow_cover_impact`
+pub fn summarize_impact(impact: &[f64]) -> Summary {
+    summarize(impact)
+}
+
+/// Packs with no reproductive success in a season they have a denning
+/// record for, each listed once however many seasons failed
+pub fn identify_vulnerable_regions(dataset: &WolfDataset) -> BTreeSet<PackKey> {
+    let columns = dataset.reproduction_columns();
+    let failed = columns.query().filter("success", Predicate::Eq(0.0));
+    failed
+        .rows()
+        .iter()
+        .map(|&row| (columns.pack_key(row), columns.year(row)))
+        .filter(|(key, year)| dataset.denning_for_season(key, *year).next().is_some())
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// Splits denning records at DOY 120, returning `(early, late)` counts
+pub fn cluster_denning_patterns(dataset: &WolfDataset) -> (usize, usize) {
+    let denning = dataset.denning_columns();
+    let early = denning.query().filter("denning_doy", Predicate::Lt(120.0)).count();
+    (early, denning.len() - early)
+}
+
+/// Draws the yearly mean denning DOY and reproductive success to a PNG
+/// file; the parent directory must already exist
+pub fn plot_denning_and_success(
+    dataset: &WolfDataset,
+    path: impl AsRef<Path>,
+) -> Result<(), WolfDataError> {
+    // Truncated to whole units, as the chart's y axis is integral
+    let yearly_mean = |store: &ColumnStore, column: &str| -> Vec<(i32, u32)> {
+        let mean = Aggregate::Mean(column.to_string());
+        let table = store.query().group_by(&[GroupKey::Year]).aggregate(std::slice::from_ref(&mean));
+        table
+            .series("year", &mean.name())
+            .into_iter()
+            .map(|(year, mean)| (year as i32, mean as u32))
+            .collect()
+    };
+    let avg_doy = yearly_mean(dataset.denning_columns(), "denning_doy");
+    let avg_success = yearly_mean(dataset.reproduction_columns(), "success");
+
+    let output_path = path.as_ref();
+    let root = BitMapBackend::new(output_path, (800, 500)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let min_year = *avg_doy.first().map(|(y, _)| y).unwrap_or(&2000);
+    let max_year = *avg_doy.last().map(|(y, _)| y).unwrap_or(&2025);
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Average Denning DOY and Reproductive Success per Year", ("sans-serif", 22))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(min_year..max_year, 0u32..200u32)?;
+
+    chart.configure_mesh().x_desc("Year").y_desc("Value").draw()?;
+
+    chart
+        .draw_series(LineSeries::new(avg_doy.clone(), &BLUE))?
+        .label("Avg Denning DOY")
+        .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], BLUE));
+
+    chart
+        .draw_series(LineSeries::new(avg_success.clone(), &RED))?
+        .label("Avg Reproductive Success")
+        .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], RED));
+
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    println!("\n📊 Saved visualization to `{}`", output_path.display());
+    Ok(())
+}
@@ -1,170 +1,323 @@
-mod data;
-mod graph;
+use std::fs::{self, File};
+use std::path::Path;
 
-use crate::data::{read_denning_csv, read_reproductive_csv, DenningPhenology, ReproductiveSuccess};
-use plotters::prelude::*;
-use std::collections::HashMap;
-use chrono::Datelike;
+use wolf_project_210::data::{find_reused_pack_ids, JoinKind, LoadOptions, LoadReport};
+use wolf_project_210::dataset::WolfDataset;
+use wolf_project_210::diff::{DiffKey, DiffOptions};
+use wolf_project_210::error::WolfDataError;
+use wolf_project_210::missing::MissingPolicy;
+use wolf_project_210::record::covariate_values;
+use wolf_project_210::snapshot::SnapshotCache;
+use wolf_project_210::stats::correlation::{CorrelationOptions, Method};
+use wolf_project_210::stats::linear::{CovarianceKind, LinearOptions};
+use wolf_project_210::stats::logistic::logistic_regression;
+use wolf_project_210::stats::mixed::linear_mixed;
+use wolf_project_210::summary::{Grouping, SummaryOptions};
+use wolf_project_210::validation::Validator;
+use wolf_project_210::{
+    analyze_snow_cover_impact, analyze_temperature_impact, cluster_denning_patterns, graph,
+    identify_vulnerable_regions, plot_denning_and_success, summarize_impact,
+};
 
+/// Abort the run if more than this fraction of either file is rejected
+const MAX_REJECTION_RATE: f64 = 0.10;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let denning = read_denning_csv("data/Wolf_DenningPhenology_AK_CA.csv")?;
-    let reproduction = read_reproductive_csv("data/Wolf_ReproductiveSuccess_AK_CA.csv")?;
+const DENNING_CSV: &str = "data/Wolf_DenningPhenology_AK_CA.csv";
+const REPRODUCTIVE_CSV: &str = "data/Wolf_ReproductiveSuccess_AK_CA.csv";
 
-    println!("📦 Loaded {} denning records", denning.len());
-    println!("📦 Loaded {} reproductive records", reproduction.len());
+/// Where parsed datasets are cached between runs
+const SNAPSHOT_DIR: &str = ".snapshots";
 
-    let temperature_impact = analyze_temperature_impact(&denning, &reproduction);
-    let snow_cover_impact = analyze_snow_cover_impact(&denning, &reproduction);
+const CORRELATION_HEATMAP: &str = "output/covariate_correlations.png";
+const DENNING_PLOT: &str = "output/denning_vs_success.png";
 
-    summarize_impact("🌡️ Temperature", &temperature_impact, "°C");
-    summarize_impact("❄️ Snow Cover", &snow_cover_impact, "mm");
+const USAGE: &str = "usage: wolf_project_210 [sql <query> [--output <file>] \
+     | ingest <store.db> <denning|reproductive> <file.csv> \
+     | diff <old dir> <new dir> [--key uid|season] [--output <file.md|file.json>]]";
 
-    identify_vulnerable_regions(&denning, &reproduction);
-    cluster_denning_patterns(&denning);
+fn main() -> Result<(), WolfDataError> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None => run_analysis(),
+        Some("sql") => run_sql(&args[1..]),
+        Some("ingest") => run_ingest(&args[1..]),
+        Some("diff") => run_diff(&args[1..]),
+        Some(_) => Err(usage_error(USAGE)),
+    }
+}
 
-    let network = graph::build_graph(&denning);
-    let centrality = graph::compute_degree_centrality(&network);
-    graph::print_top_central_packs(&centrality, 5);
+fn usage_error(message: &str) -> WolfDataError {
+    WolfDataError::Validation {
+        line: None,
+        column: None,
+        message: message.to_string(),
+    }
+}
+
+/// Runs one SQL query against the files in `data/`, printing the result or
+/// writing it to `--output` in the format given by the file extension
+#[cfg(feature = "sql")]
+fn run_sql(args: &[String]) -> Result<(), WolfDataError> {
+    use wolf_project_210::export::export_table;
+    use wolf_project_210::sql::SqlSession;
 
-    plot_denning_and_success(&denning, &reproduction)?;
+    let (query, output) = match args {
+        [query] => (query, None),
+        [query, flag, path] if flag == "--output" => (query, Some(path)),
+        _ => return Err(usage_error(USAGE)),
+    };
+    let (dataset, _) = load_dataset(&LoadOptions::default())?;
+    let session = SqlSession::from_dataset(&dataset)?;
+    let table = session.query(query)?;
+    match output {
+        Some(path) => {
+            export_table(&table, path)?;
+            println!("💾 Wrote {} rows to `{}`", table.len(), path);
+        }
+        None => println!("{}\n({} rows)", table, table.len()),
+    }
     Ok(())
 }
 
-fn analyze_temperature_impact(
-    denning_data: &[DenningPhenology],
-    reproductive_data: &[ReproductiveSuccess],
-) -> Vec<f64> {
-    reproductive_data
-        .iter()
-        .filter_map(|r| match (r.summer_tmax, r.winter_tmax) {
-            (Some(s), Some(w)) if denning_data.iter().any(|d| d.pack_id == r.pack_id) => Some((s - w) as f64),
-            _ => None,
-        })
-        .collect()
+#[cfg(not(feature = "sql"))]
+fn run_sql(_args: &[String]) -> Result<(), WolfDataError> {
+    Err(usage_error(
+        "the `sql` subcommand needs the `sql` feature; rebuild with `--features sql`",
+    ))
 }
 
-fn analyze_snow_cover_impact(
-    denning_data: &[DenningPhenology],
-    reproductive_data: &[ReproductiveSuccess],
-) -> Vec<f64> {
-    reproductive_data
-        .iter()
-        .filter_map(|r| match (r.winter_swe, r.fall_prcp) {
-            (Some(w), Some(f)) if denning_data.iter().any(|d| d.pack_id == r.pack_id) => Some(w as f64 - f as f64),
-            _ => None,
-        })
-        .collect()
+/// Adds a CSV file to a persistent record store and lists the store's sources
+#[cfg(feature = "store")]
+fn run_ingest(args: &[String]) -> Result<(), WolfDataError> {
+    use wolf_project_210::store::RecordStore;
+
+    let [database, kind, path] = args else {
+        return Err(usage_error(USAGE));
+    };
+    let mut store = RecordStore::open(database)?;
+    let report = match kind.as_str() {
+        "denning" => store.ingest_denning(path)?,
+        "reproductive" => store.ingest_reproductive(path)?,
+        _ => return Err(usage_error(USAGE)),
+    };
+    println!("📥 {}", report);
+    for finding in report.validation.findings.iter() {
+        eprintln!("{}", finding);
+    }
+    for conflict in &report.conflicts {
+        eprintln!("{}", conflict);
+    }
+    println!("🗄️ Sources in `{}`:", database);
+    for source in store.sources()? {
+        println!("  • {}", source);
+    }
+    Ok(())
 }
 
-fn summarize_impact(label: &str, values: &[f64], unit: &str) {
-    if values.is_empty() {
-        println!("\n{} Impact: No data available.", label);
-        return;
-    }
-    let avg = values.iter().sum::<f64>() / values.len() as f64;
-    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
-    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
-    println!("\n{} Impact Summary:", label);
-    println!("  • Records: {}", values.len());
-    println!("  • Avg Δ: {:.2}{}", avg, unit);
-    println!("  • Min Δ: {:.2}{}", min, unit);
-    println!("  • Max Δ: {:.2}{}", max, unit);
+#[cfg(not(feature = "store"))]
+fn run_ingest(_args: &[String]) -> Result<(), WolfDataError> {
+    Err(usage_error(
+        "the `ingest` subcommand needs the `store` feature; rebuild with `--features store`",
+    ))
 }
 
-fn identify_vulnerable_regions(
-    denning_data: &[DenningPhenology],
-    reproductive_data: &[ReproductiveSuccess],
-) {
-    let vulnerable: Vec<_> = reproductive_data
-        .iter()
-        .filter(|r| r.success == 0)
-        .filter_map(|r| {
-            denning_data
-                .iter()
-                .find(|d| d.pack_id == r.pack_id)
-                .map(|d| (r.pack_id, d.study.clone()))
-        })
-        .collect();
+/// Compares the CSV files of two data releases, each a directory laid out
+/// like `data/`, and writes the report as Markdown or JSON
+fn run_diff(args: &[String]) -> Result<(), WolfDataError> {
+    let [old_dir, new_dir, flags @ ..] = args else {
+        return Err(usage_error(USAGE));
+    };
+    let mut options = DiffOptions::default();
+    let mut output = None;
+    for pair in flags.chunks(2) {
+        match pair {
+            [flag, key] if flag == "--key" => {
+                options.key = match key.as_str() {
+                    "uid" => DiffKey::Uid,
+                    "season" => DiffKey::Season,
+                    _ => return Err(usage_error(USAGE)),
+                }
+            }
+            [flag, path] if flag == "--output" => output = Some(Path::new(path)),
+            _ => return Err(usage_error(USAGE)),
+        }
+    }
 
-    println!("\n⚠️ Vulnerability Summary:");
-    println!("  • Packs with 0 reproductive success: {}", vulnerable.len());
+    let diff = options.diff_datasets(&load_release(old_dir)?, &load_release(new_dir)?)?;
+    let json = output.is_some_and(|p| p.extension().is_some_and(|e| e == "json"));
+    let mut report = Vec::new();
+    if json {
+        diff.write_json(&mut report)?;
+    } else {
+        diff.write_markdown(&mut report)?;
+    }
+    match output {
+        Some(path) => {
+            fs::write(path, report).map_err(|e| WolfDataError::io(path, e))?;
+            println!("🔍 Denning: {}", diff.denning);
+            println!("🔍 Reproductive: {}", diff.reproduction);
+            println!("📝 Wrote diff report to `{}`", path.display());
+        }
+        None => print!("{}", String::from_utf8_lossy(&report)),
+    }
+    Ok(())
 }
 
-fn cluster_denning_patterns(data: &[DenningPhenology]) {
-    let (mut early, mut late) = (0, 0);
-    for d in data {
-        if d.denning_doy < 120 {
-            early += 1;
-        } else {
-            late += 1;
-        }
+/// Loads both files of a release, keeping rows with missing values so
+/// that values which went missing show up as changes
+fn load_release(dir: &str) -> Result<WolfDataset, WolfDataError> {
+    let options = LoadOptions::with_missing_policy(MissingPolicy::KeepNone);
+    let dir = Path::new(dir);
+    let file = |name: &str| {
+        let path = dir.join(Path::new(name).file_name().unwrap_or_default());
+        File::open(&path).map_err(|e| WolfDataError::io(path, e))
+    };
+    let (denning, _) = options.read_denning(file(DENNING_CSV)?)?;
+    let (reproduction, _) = options.read_reproductive(file(REPRODUCTIVE_CSV)?)?;
+    Ok(WolfDataset::new(denning, reproduction))
+}
+
+fn run_analysis() -> Result<(), WolfDataError> {
+    let cache = SnapshotCache::new(SNAPSHOT_DIR);
+    let (dataset, reports, status) = cache.load(
+        &[DENNING_CSV, REPRODUCTIVE_CSV],
+        &LoadOptions::default(),
+        load_dataset,
+    )?;
+    let loaded = [
+        ("denning", dataset.denning().len()),
+        ("reproductive", dataset.reproduction().len()),
+    ];
+    for ((kind, rows), report) in loaded.into_iter().zip(&reports) {
+        println!("📦 Loaded {} {} records ({})", rows, kind, report);
+    }
+    println!("📦 {}", status);
+    for report in &reports {
+        report.ensure_rejection_rate(MAX_REJECTION_RATE)?;
     }
+    check_dataset(&dataset);
+
+    let seasons = dataset.join_pack_seasons(JoinKind::Inner);
+    println!(
+        "🔗 Joined {} pack-seasons ({} denning and {} reproductive records unmatched)",
+        seasons.seasons.len(),
+        seasons.unmatched_denning.len(),
+        seasons.unmatched_reproduction.len()
+    );
+
+    println!("\n🗺️ Study areas:");
+    let areas = dataset.study_areas();
+    let denning_by_country = areas.group_by_country(dataset.denning());
+    let reproduction_by_country = areas.group_by_country(dataset.reproduction());
+    for (country, denning) in &denning_by_country {
+        let codes: Vec<&str> = areas
+            .areas()
+            .iter()
+            .filter(|a| a.country == *country)
+            .map(|a| a.code.as_str())
+            .collect();
+        let doy = covariate_values(denning.iter().copied(), "denning_doy");
+        let success = reproduction_by_country
+            .get(country)
+            .map_or_else(Vec::new, |r| covariate_values(r.iter().copied(), "success"));
+        println!(
+            "  • {}: {} areas ({}), mean denning DOY {:.1}, success rate {:.2}",
+            country,
+            codes.len(),
+            codes.join(", "),
+            doy.iter().sum::<f64>() / doy.len().max(1) as f64,
+            success.iter().sum::<f64>() / success.len().max(1) as f64
+        );
+    }
+
+    let correlations = CorrelationOptions {
+        method: Method::Spearman,
+        ..CorrelationOptions::default()
+    }
+    .compute(
+        &seasons.seasons,
+        &["winter_swe", "winter_tmax", "summer_tmax", "annual_pdo", "annual_ao", "sos_prev1"],
+    )?;
+    println!("\n🔗 Covariate {}", correlations);
+    std::fs::create_dir_all("output").map_err(|e| WolfDataError::io("output", e))?;
+    correlations.plot_heatmap(CORRELATION_HEATMAP)?;
+    println!("📊 Saved correlation heatmap to `{}`", CORRELATION_HEATMAP);
+
+    let model = logistic_regression(
+        &seasons.seasons,
+        &["denning_doy", "winter_swe", "summer_tmax", "annual_pdo"],
+    )?;
+    println!("\n📈 Reproductive success model:\n{}", model);
+
+    let doy_model = LinearOptions {
+        covariance: CovarianceKind::Hc3,
+        ..LinearOptions::default()
+    }
+    .fit(
+        dataset.denning(),
+        "denning_doy",
+        &["summer_tmax_prev1", "sos_prev1", "los_prev1", "tiNDVI_prev1", "latitude_individual"],
+    )?;
+    println!("\n📈 Denning day-of-year model:\n{}", doy_model);
+
+    let doy_mixed = linear_mixed(
+        dataset.denning(),
+        "denning_doy",
+        &["summer_tmax_prev1", "sos_prev1", "los_prev1", "tiNDVI_prev1"],
+    )?;
+    println!("\n📈 Denning day-of-year mixed model:\n{}", doy_mixed);
+
+    let temperature_impact = analyze_temperature_impact(&dataset);
+    let snow_cover_impact = analyze_snow_cover_impact(&dataset);
+
+    println!("\n🌡️ Temperature Impact Summary (Δ °C):\n{}", summarize_impact(&temperature_impact));
+    println!("\n❄️ Snow Cover Impact Summary (Δ mm):\n{}", summarize_impact(&snow_cover_impact));
+
+    let doy_by_study = SummaryOptions { percentiles: Vec::new(), ..SummaryOptions::default() }
+        .summarize_by(dataset.denning(), "denning_doy", Grouping::Study)?;
+    println!("\n📋 Denning {}", doy_by_study);
+
+    let vulnerable = identify_vulnerable_regions(&dataset);
+    println!("\n⚠️ Vulnerability Summary:");
+    println!("  • Packs with 0 reproductive success: {}", vulnerable.len());
+
+    let (early, late) = cluster_denning_patterns(&dataset);
     println!("\n🧩 Denning Clustering Summary:");
     println!("  • Early denning (< DOY 120): {}", early);
     println!("  • Late denning (≥ DOY 120): {}", late);
-}
 
-fn plot_denning_and_success(
-    denning_data: &[DenningPhenology],
-    reproductive_data: &[ReproductiveSuccess],
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut year_to_doy: HashMap<i32, Vec<u32>> = HashMap::new();
-    let mut year_to_success: HashMap<i32, Vec<u32>> = HashMap::new();
-
-    for d in denning_data {
-        year_to_doy.entry(d.denning_date.year()).or_default().push(d.denning_doy.into());
-    }
-    for r in reproductive_data {
-        year_to_success.entry(r.start_date.year()).or_default().push(r.success.into());
-    }
-
-    let mut avg_doy: Vec<_> = year_to_doy
-        .iter()
-        .map(|(y, v)| (*y, v.iter().sum::<u32>() / v.len() as u32))
-        .collect();
-    let mut avg_success: Vec<_> = year_to_success
-        .iter()
-        .map(|(y, v)| (*y, v.iter().sum::<u32>() / v.len() as u32))
-        .collect();
-
-    avg_doy.sort_by_key(|k| k.0);
-    avg_success.sort_by_key(|k| k.0);
-
-    let output_path = "output/denning_vs_success.png";
-    std::fs::create_dir_all("output")?;
-    let root = BitMapBackend::new(output_path, (800, 500)).into_drawing_area();
-    root.fill(&WHITE)?;
-
-    let min_year = *avg_doy.first().map(|(y, _)| y).unwrap_or(&2000);
-    let max_year = *avg_doy.last().map(|(y, _)| y).unwrap_or(&2025);
-
-    let mut chart = ChartBuilder::on(&root)
-        .caption("Average Denning DOY and Reproductive Success per Year", ("sans-serif", 22))
-        .margin(10)
-        .x_label_area_size(40)
-        .y_label_area_size(50)
-        .build_cartesian_2d(min_year..max_year, 0u32..200u32)?;
-
-    chart.configure_mesh().x_desc("Year").y_desc("Value").draw()?;
-
-    chart
-        .draw_series(LineSeries::new(avg_doy.clone(), &BLUE))?
-        .label("Avg Denning DOY")
-        .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], &BLUE));
-
-    chart
-        .draw_series(LineSeries::new(avg_success.clone(), &RED))?
-        .label("Avg Reproductive Success")
-        .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], &RED));
-
-    chart
-        .configure_series_labels()
-        .background_style(&WHITE.mix(0.8))
-        .border_style(&BLACK)
-        .draw()?;
-
-    println!("\n📊 Saved visualization to `{}`", output_path);
+    let network = graph::build_graph(dataset.denning());
+    let centrality = graph::compute_degree_centrality(&network);
+    graph::print_top_central_packs(&centrality, 5);
+
+    fs::create_dir_all("output").map_err(|e| WolfDataError::io("output", e))?;
+    plot_denning_and_success(&dataset, DENNING_PLOT)?;
     Ok(())
 }
 
+/// Parses and validates both CSVs, reporting what was rejected or flagged
+fn load_dataset(options: &LoadOptions) -> Result<(WolfDataset, Vec<LoadReport>), WolfDataError> {
+    let file = |path: &str| File::open(path).map_err(|e| WolfDataError::io(path, e));
+    let (denning, denning_report) = options.read_denning(file(DENNING_CSV)?)?;
+    let (reproduction, reproduction_report) = options.read_reproductive(file(REPRODUCTIVE_CSV)?)?;
+    let reports = vec![denning_report, reproduction_report];
+    Ok((WolfDataset::new(denning, reproduction), reports))
+}
+
+/// Reports validation findings and pack ids shared between studies; runs on
+/// every load, whether or not the dataset came from a snapshot
+fn check_dataset(dataset: &WolfDataset) {
+    let denning_validation = Validator::builtin().validate(dataset.denning());
+    let reproduction_validation = Validator::builtin().validate(dataset.reproduction());
+    println!("🧪 Denning validation: {}", denning_validation);
+    println!("🧪 Reproductive validation: {}", reproduction_validation);
+    for finding in denning_validation.findings.iter().chain(&reproduction_validation.findings) {
+        eprintln!("{}", finding);
+    }
+
+    let keys = dataset.denning().iter().map(|d| d.pack_key());
+    let keys = keys.chain(dataset.reproduction().iter().map(|r| r.pack_key()));
+    for (pack_id, studies) in &find_reused_pack_ids(keys) {
+        eprintln!("Pack id {} is used by {} studies: {:?}", pack_id, studies.len(), studies);
+    }
+}
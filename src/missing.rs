@@ -0,0 +1,336 @@
+//! Policies for records with missing covariates
+//!
+//! A `MissingPolicy` can be set on `LoadOptions` to decide which rows the
+//! loaders drop, or applied afterwards to records that were kept with
+//! missing values.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::data::{DenningPhenology, PackKey, ReproductiveSuccess};
+use crate::record::WolfRecord;
+use crate::units::{Celsius, Millimetres, Quantity, SweMm};
+
+/// Columns that every policy requires, since a row cannot be identified without them
+const IDENTITY_COLUMNS: &[&str] = &["uid", "study"];
+
+/// How a missing value is filled in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ImputeMethod {
+    /// Mean of the column within the same study
+    StudyMean,
+    /// Median of the column within the same study
+    StudyMedian,
+    /// Most recent earlier value for the same pack
+    LastObservation,
+}
+
+/// What to do with a record that has missing fields
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MissingPolicy {
+    /// Drop the record if any field is missing (the original behaviour)
+    #[default]
+    DropAny,
+    /// Drop the record only if one of these columns is missing
+    DropIfMissing(Vec<String>),
+    /// Keep every identifiable record and leave missing covariates as `None`
+    KeepNone,
+    /// Keep every identifiable record and fill in missing covariates
+    Impute(ImputeMethod),
+}
+
+impl MissingPolicy {
+    /// Convenience constructor for `DropIfMissing`
+    pub fn require(columns: &[&str]) -> Self {
+        MissingPolicy::DropIfMissing(columns.iter().map(|c| c.to_string()).collect())
+    }
+
+    /// Whether a missing value in `column` causes the record to be dropped
+    pub fn drops_on(&self, column: &str) -> bool {
+        if IDENTITY_COLUMNS.contains(&column) {
+            return true;
+        }
+        match self {
+            MissingPolicy::DropAny => true,
+            MissingPolicy::DropIfMissing(columns) => columns.iter().any(|c| c == column),
+            MissingPolicy::KeepNone | MissingPolicy::Impute(_) => false,
+        }
+    }
+
+    /// Applies the policy to denning records that were loaded with missing values
+    pub fn apply_denning(
+        &self,
+        records: Vec<DenningPhenology>,
+    ) -> (Vec<DenningPhenology>, MissingReport) {
+        self.apply(records)
+    }
+
+    /// Applies the policy to reproductive records that were loaded with missing values
+    pub fn apply_reproductive(
+        &self,
+        records: Vec<ReproductiveSuccess>,
+    ) -> (Vec<ReproductiveSuccess>, MissingReport) {
+        self.apply(records)
+    }
+
+    fn apply<T: Imputable>(&self, records: Vec<T>) -> (Vec<T>, MissingReport) {
+        let mut report = MissingReport::new(self.clone());
+        let before = records.len();
+        let mut records: Vec<T> = records
+            .into_iter()
+            .filter(|r| !r.missing_fields().iter().any(|c| self.drops_on(c)))
+            .collect();
+        report.dropped = before - records.len();
+
+        if let MissingPolicy::Impute(method) = self {
+            report.imputed = impute(&mut records, *method);
+        }
+        (records, report)
+    }
+}
+
+impl fmt::Display for MissingPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MissingPolicy::DropAny => write!(f, "drop rows with any missing field"),
+            MissingPolicy::DropIfMissing(columns) => {
+                write!(f, "drop rows missing any of {}", columns.join(", "))
+            }
+            MissingPolicy::KeepNone => write!(f, "keep missing values as None"),
+            MissingPolicy::Impute(ImputeMethod::StudyMean) => write!(f, "impute with study mean"),
+            MissingPolicy::Impute(ImputeMethod::StudyMedian) => {
+                write!(f, "impute with study median")
+            }
+            MissingPolicy::Impute(ImputeMethod::LastObservation) => {
+                write!(f, "impute with the pack's last observation")
+            }
+        }
+    }
+}
+
+/// What a `MissingPolicy` did to a set of records
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MissingReport {
+    pub policy: MissingPolicy,
+    /// Records dropped by the policy
+    pub dropped: usize,
+    /// Number of values filled in per column
+    pub imputed: BTreeMap<String, usize>,
+}
+
+impl MissingReport {
+    pub fn new(policy: MissingPolicy) -> Self {
+        MissingReport {
+            policy,
+            ..MissingReport::default()
+        }
+    }
+}
+
+impl fmt::Display for MissingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.policy)?;
+        let imputed: usize = self.imputed.values().sum();
+        if imputed > 0 {
+            write!(f, ", {} values imputed", imputed)?;
+        }
+        Ok(())
+    }
+}
+
+/// A covariate field, either a plain `f64` or a unit type
+pub(crate) enum Slot<'a> {
+    Celsius(&'a mut Option<Celsius>),
+    Millimetres(&'a mut Option<Millimetres>),
+    Swe(&'a mut Option<SweMm>),
+    F64(&'a mut Option<f64>),
+}
+
+impl Slot<'_> {
+    fn get(&self) -> Option<f64> {
+        match self {
+            Slot::Celsius(v) => v.map(Quantity::value),
+            Slot::Millimetres(v) => v.map(Quantity::value),
+            Slot::Swe(v) => v.map(Quantity::value),
+            Slot::F64(v) => **v,
+        }
+    }
+
+    /// Fills the slot, returning whether the value was in the unit's
+    /// range. Fills are means, medians or earlier values of the same
+    /// column, but a mean of very large values can still overflow.
+    fn set(&mut self, value: f64) -> bool {
+        match self {
+            Slot::Celsius(v) => **v = Celsius::new(value).ok(),
+            Slot::Millimetres(v) => **v = Millimetres::new(value).ok(),
+            Slot::Swe(v) => **v = SweMm::new(value).ok(),
+            Slot::F64(v) => **v = Some(value),
+        }
+        self.get().is_some()
+    }
+}
+
+/// Records whose optional covariates can be imputed
+pub(crate) trait Imputable: WolfRecord {
+    fn missing_fields(&self) -> Vec<&'static str>;
+    fn slots(&mut self) -> Vec<(&'static str, Slot<'_>)>;
+}
+
+impl Imputable for DenningPhenology {
+    fn missing_fields(&self) -> Vec<&'static str> {
+        DenningPhenology::missing_fields(self)
+    }
+
+    fn slots(&mut self) -> Vec<(&'static str, Slot<'_>)> {
+        vec![
+            ("fall_tmax", Slot::Celsius(&mut self.fall_tmax)),
+            ("summer_tmax_prev1", Slot::Celsius(&mut self.summer_tmax_prev1)),
+            ("winter_tmax", Slot::Celsius(&mut self.winter_tmax)),
+            ("fall_prcp", Slot::Millimetres(&mut self.fall_prcp)),
+            ("summer_prcp_prev1", Slot::Millimetres(&mut self.summer_prcp_prev1)),
+            ("winter_swe", Slot::Swe(&mut self.winter_swe)),
+            ("tiNDVI_prev1", Slot::F64(&mut self.ti_ndvi_prev1)),
+            ("annual_pdo", Slot::F64(&mut self.annual_pdo)),
+            ("annual_ao", Slot::F64(&mut self.annual_ao)),
+            ("sos_prev1", Slot::F64(&mut self.sos_prev1)),
+            ("los_prev1", Slot::F64(&mut self.los_prev1)),
+        ]
+    }
+}
+
+impl Imputable for ReproductiveSuccess {
+    fn missing_fields(&self) -> Vec<&'static str> {
+        ReproductiveSuccess::missing_fields(self)
+    }
+
+    fn slots(&mut self) -> Vec<(&'static str, Slot<'_>)> {
+        vec![
+            ("summer_prcp", Slot::Millimetres(&mut self.summer_prcp)),
+            ("fall_prcp", Slot::Millimetres(&mut self.fall_prcp)),
+            ("winter_swe", Slot::Swe(&mut self.winter_swe)),
+            ("fall_tmax", Slot::Celsius(&mut self.fall_tmax)),
+            ("summer_tmax", Slot::Celsius(&mut self.summer_tmax)),
+            ("winter_tmax", Slot::Celsius(&mut self.winter_tmax)),
+            ("tiNDVI_prev1", Slot::F64(&mut self.ti_ndvi_prev1)),
+            ("tiNDVI", Slot::F64(&mut self.ti_ndvi)),
+            ("annual_pdo", Slot::F64(&mut self.annual_pdo)),
+            ("annual_ao", Slot::F64(&mut self.annual_ao)),
+            ("home_range_area", Slot::F64(&mut self.home_range_area)),
+            (
+                "denning_match_growing_season",
+                Slot::F64(&mut self.denning_match_growing_season),
+            ),
+        ]
+    }
+}
+
+/// Fills in missing covariates in place, returning how many were filled per column
+pub(crate) fn impute<T: Imputable>(
+    records: &mut [T],
+    method: ImputeMethod,
+) -> BTreeMap<String, usize> {
+    let keys: Vec<SeasonKey> = records
+        .iter()
+        .map(|r| (r.pack_key(), r.season_date()))
+        .collect();
+    let columns: Vec<&'static str> = records.first_mut().map_or_else(Vec::new, |r| {
+        r.slots().into_iter().map(|(column, _)| column).collect()
+    });
+
+    let mut imputed = BTreeMap::new();
+    for (j, column) in columns.into_iter().enumerate() {
+        let mut values: Vec<Option<f64>> =
+            records.iter_mut().map(|r| r.slots()[j].1.get()).collect();
+        impute_column(&keys, &mut values, method);
+        // Counted as they are stored, since a fill outside the unit's range
+        // leaves the slot empty
+        let mut filled = 0;
+        for (record, value) in records.iter_mut().zip(values) {
+            let mut slots = record.slots();
+            let slot = &mut slots[j].1;
+            if let (None, Some(value)) = (slot.get(), value) {
+                filled += usize::from(slot.set(value));
+            }
+        }
+        if filled > 0 {
+            imputed.insert(column.to_string(), filled);
+        }
+    }
+    imputed
+}
+
+/// Pack and season date of the record that owns a value
+pub(crate) type SeasonKey = (PackKey, NaiveDate);
+
+/// Fills the gaps of one column in place, `values[i]` belonging to the
+/// record with `keys[i]`. Study means and medians are taken over the
+/// observed values of the same study; the last observation is carried
+/// forward within each pack in season order. Returns how many values were
+/// filled.
+pub(crate) fn impute_column(
+    keys: &[SeasonKey],
+    values: &mut [Option<f64>],
+    method: ImputeMethod,
+) -> usize {
+    let mut filled = 0;
+    match method {
+        ImputeMethod::StudyMean | ImputeMethod::StudyMedian => {
+            let mut observed: HashMap<&str, Vec<f64>> = HashMap::new();
+            for ((key, _), value) in keys.iter().zip(values.iter()) {
+                if let Some(value) = value {
+                    observed.entry(&key.study).or_default().push(*value);
+                }
+            }
+            let fills: HashMap<&str, f64> = observed
+                .into_iter()
+                .filter_map(|(study, mut v)| {
+                    let fill = match method {
+                        ImputeMethod::StudyMean => Some(v.iter().sum::<f64>() / v.len() as f64),
+                        _ => median(&mut v),
+                    };
+                    Some((study, fill?))
+                })
+                .collect();
+            for ((key, _), value) in keys.iter().zip(values.iter_mut()) {
+                if value.is_none() {
+                    *value = fills.get(key.study.as_str()).copied();
+                    filled += usize::from(value.is_some());
+                }
+            }
+        }
+        ImputeMethod::LastObservation => {
+            let mut order: Vec<usize> = (0..keys.len()).collect();
+            order.sort_by(|&a, &b| keys[a].cmp(&keys[b]));
+            let mut last: Option<(&PackKey, f64)> = None;
+            for i in order {
+                let pack = &keys[i].0;
+                let carried = last.filter(|(p, _)| *p == pack).map(|(_, v)| v);
+                match values[i] {
+                    Some(value) => last = Some((pack, value)),
+                    None => {
+                        values[i] = carried;
+                        filled += usize::from(carried.is_some());
+                    }
+                }
+            }
+        }
+    }
+    filled
+}
+
+/// Median of `values`, sorting them in place; `None` when empty
+pub(crate) fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    })
+}
@@ -0,0 +1,286 @@
+//! Common interface over the two ABoVE record types
+//!
+//! `DenningPhenology` and `ReproductiveSuccess` share their identity
+//! columns, study coordinates, a season date and most climate covariates.
+//! `WolfRecord` exposes those by name so grouping, filtering and
+//! summarising code can be written once for either dataset.
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate};
+
+use crate::data::{DenningPhenology, PackKey, PackSeason, ReproductiveSuccess};
+use crate::units::Quantity;
+
+/// Covariates present in both the denning and the reproductive files
+pub const SHARED_COVARIATES: &[&str] = &[
+    "fall_tmax",
+    "winter_tmax",
+    "fall_prcp",
+    "winter_swe",
+    "tiNDVI_prev1",
+    "annual_pdo",
+    "annual_ao",
+];
+
+/// A denning or reproductive record
+pub trait WolfRecord {
+    /// Numeric columns `covariate` accepts, named as in the source file
+    const NUMERIC_COLUMNS: &'static [&'static str];
+
+    fn uid(&self) -> u32;
+    fn study(&self) -> &str;
+    fn pack_id(&self) -> u32;
+    /// `(latitude, longitude)` of the study area
+    fn location(&self) -> (f64, f64);
+    /// The date that places the record in a season
+    fn season_date(&self) -> NaiveDate;
+
+    /// A numeric column by name, `None` if the value is missing or the
+    /// record type has no such column.
+    ///
+    /// Names are the source headers (`tiNDVI_prev1`); the Rust field
+    /// names (`ti_ndvi_prev1`) are accepted too.
+    fn covariate(&self, name: &str) -> Option<f64>;
+
+    fn pack_key(&self) -> PackKey {
+        PackKey::new(self.study(), self.pack_id())
+    }
+
+    fn season_year(&self) -> i32 {
+        self.season_date().year()
+    }
+
+    fn season_key(&self) -> (PackKey, i32) {
+        (self.pack_key(), self.season_year())
+    }
+}
+
+impl WolfRecord for DenningPhenology {
+    const NUMERIC_COLUMNS: &'static [&'static str] = &[
+        "denning_doy",
+        "denned",
+        "fall_tmax",
+        "summer_tmax_prev1",
+        "winter_tmax",
+        "fall_prcp",
+        "summer_prcp_prev1",
+        "winter_swe",
+        "tiNDVI_prev1",
+        "annual_pdo",
+        "annual_ao",
+        "sos_prev1",
+        "los_prev1",
+        "latitude_individual",
+    ];
+
+    fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    fn study(&self) -> &str {
+        &self.study
+    }
+
+    fn pack_id(&self) -> u32 {
+        self.pack_id
+    }
+
+    fn location(&self) -> (f64, f64) {
+        (self.latitude_study, self.longitude_study)
+    }
+
+    fn season_date(&self) -> NaiveDate {
+        self.denning_date
+    }
+
+    fn covariate(&self, name: &str) -> Option<f64> {
+        match name {
+            "denning_doy" => Some(f64::from(self.denning_doy)),
+            "denned" => Some(f64::from(self.denned)),
+            "fall_tmax" => self.fall_tmax.map(Quantity::value),
+            "summer_tmax_prev1" => self.summer_tmax_prev1.map(Quantity::value),
+            "winter_tmax" => self.winter_tmax.map(Quantity::value),
+            "fall_prcp" => self.fall_prcp.map(Quantity::value),
+            "summer_prcp_prev1" => self.summer_prcp_prev1.map(Quantity::value),
+            "winter_swe" => self.winter_swe.map(Quantity::value),
+            "tiNDVI_prev1" | "ti_ndvi_prev1" => self.ti_ndvi_prev1,
+            "annual_pdo" => self.annual_pdo,
+            "annual_ao" => self.annual_ao,
+            "sos_prev1" => self.sos_prev1,
+            "los_prev1" => self.los_prev1,
+            // 0 marks a missing individual location in the source file
+            "latitude_individual" => Some(self.latitude_individual).filter(|v| *v != 0.0),
+            _ => None,
+        }
+    }
+}
+
+impl WolfRecord for ReproductiveSuccess {
+    const NUMERIC_COLUMNS: &'static [&'static str] = &[
+        "success",
+        "summer_prcp",
+        "fall_prcp",
+        "winter_swe",
+        "fall_tmax",
+        "summer_tmax",
+        "winter_tmax",
+        "tiNDVI_prev1",
+        "tiNDVI",
+        "annual_pdo",
+        "annual_ao",
+        "home_range_area",
+        "denning_match_growing_season",
+    ];
+
+    fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    fn study(&self) -> &str {
+        &self.study
+    }
+
+    fn pack_id(&self) -> u32 {
+        self.pack_id
+    }
+
+    fn location(&self) -> (f64, f64) {
+        (self.latitude_study, self.longitude_study)
+    }
+
+    fn season_date(&self) -> NaiveDate {
+        self.start_date
+    }
+
+    fn covariate(&self, name: &str) -> Option<f64> {
+        match name {
+            "success" => Some(f64::from(self.success)),
+            "summer_prcp" => self.summer_prcp.map(Quantity::value),
+            "fall_prcp" => self.fall_prcp.map(Quantity::value),
+            "winter_swe" => self.winter_swe.map(Quantity::value),
+            "fall_tmax" => self.fall_tmax.map(Quantity::value),
+            "summer_tmax" => self.summer_tmax.map(Quantity::value),
+            "winter_tmax" => self.winter_tmax.map(Quantity::value),
+            "tiNDVI_prev1" | "ti_ndvi_prev1" => self.ti_ndvi_prev1,
+            "tiNDVI" | "ti_ndvi" => self.ti_ndvi,
+            "annual_pdo" => self.annual_pdo,
+            "annual_ao" => self.annual_ao,
+            "home_range_area" => self.home_range_area,
+            "denning_match_growing_season" => self.denning_match_growing_season,
+            _ => None,
+        }
+    }
+}
+
+/// A joined pack-season takes its identity and season from the denning
+/// record. Reproductive columns keep their names, except those the denning
+/// file also has, which take a `repro_` prefix as in exported tables.
+impl WolfRecord for PackSeason {
+    const NUMERIC_COLUMNS: &'static [&'static str] = &[
+        "denning_doy",
+        "denned",
+        "fall_tmax",
+        "summer_tmax_prev1",
+        "winter_tmax",
+        "fall_prcp",
+        "summer_prcp_prev1",
+        "winter_swe",
+        "tiNDVI_prev1",
+        "annual_pdo",
+        "annual_ao",
+        "sos_prev1",
+        "los_prev1",
+        "latitude_individual",
+        "success",
+        "summer_prcp",
+        "repro_fall_prcp",
+        "repro_winter_swe",
+        "repro_fall_tmax",
+        "summer_tmax",
+        "repro_winter_tmax",
+        "repro_tiNDVI_prev1",
+        "tiNDVI",
+        "repro_annual_pdo",
+        "repro_annual_ao",
+        "home_range_area",
+        "denning_match_growing_season",
+    ];
+
+    fn uid(&self) -> u32 {
+        self.denning.uid
+    }
+
+    fn study(&self) -> &str {
+        &self.key.study
+    }
+
+    fn pack_id(&self) -> u32 {
+        self.key.pack_id
+    }
+
+    fn location(&self) -> (f64, f64) {
+        self.denning.location()
+    }
+
+    fn season_date(&self) -> NaiveDate {
+        self.denning.denning_date
+    }
+
+    fn season_year(&self) -> i32 {
+        self.year
+    }
+
+    fn covariate(&self, name: &str) -> Option<f64> {
+        let reproduction = self.reproduction.as_ref();
+        let denning_column =
+            DenningPhenology::NUMERIC_COLUMNS.contains(&name) || name == "ti_ndvi_prev1";
+        match name.strip_prefix("repro_") {
+            Some(shared) => reproduction.and_then(|r| r.covariate(shared)),
+            None if denning_column => self.denning.covariate(name),
+            None => reproduction.and_then(|r| r.covariate(name)),
+        }
+    }
+}
+
+/// Groups records by a key, keeping the input order within each group
+pub fn group_by<'a, T, K, I>(records: I, key: impl Fn(&T) -> K) -> BTreeMap<K, Vec<&'a T>>
+where
+    T: 'a,
+    K: Ord,
+    I: IntoIterator<Item = &'a T>,
+{
+    let mut groups: BTreeMap<K, Vec<&T>> = BTreeMap::new();
+    for record in records {
+        groups.entry(key(record)).or_default().push(record);
+    }
+    groups
+}
+
+/// Non-missing values of a covariate
+pub fn covariate_values<'a, T, I>(records: I, name: &str) -> Vec<f64>
+where
+    T: WolfRecord + 'a,
+    I: IntoIterator<Item = &'a T>,
+{
+    records
+        .into_iter()
+        .filter_map(|r| r.covariate(name))
+        .collect()
+}
+
+/// Mean of a covariate per season year, skipping missing values and years
+/// without any value
+pub fn yearly_means<'a, T, I>(records: I, name: &str) -> Vec<(i32, f64)>
+where
+    T: WolfRecord + 'a,
+    I: IntoIterator<Item = &'a T>,
+{
+    group_by(records, |r: &T| r.season_year())
+        .into_iter()
+        .filter_map(|(year, group)| {
+            let values = covariate_values(group, name);
+            (!values.is_empty()).then(|| (year, values.iter().sum::<f64>() / values.len() as f64))
+        })
+        .collect()
+}
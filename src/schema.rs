@@ -0,0 +1,176 @@
+//! Header checks and column renaming for alternate CSV exports
+//!
+//! The loaders expect the column names of the original ABoVE files. Other
+//! releases or partner exports can be loaded by supplying a `ColumnMapping`
+//! from their column names to the expected ones.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::WolfDataError;
+
+/// Renames source columns to the names the loaders expect.
+///
+/// A mapping file is TOML with a single `[columns]` table:
+///
+/// ```toml
+/// [columns]
+/// ti_ndvi_prev1 = "tiNDVI_prev1"
+/// pack = "pack_id"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ColumnMapping {
+    /// Source column name → expected column name
+    #[serde(default)]
+    columns: BTreeMap<String, String>,
+}
+
+impl ColumnMapping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a rename from `source` to the expected column `target`
+    pub fn rename(mut self, source: impl Into<String>, target: impl Into<String>) -> Self {
+        self.columns.insert(source.into(), target.into());
+        self
+    }
+
+    pub fn from_toml_str(text: &str) -> Result<Self, WolfDataError> {
+        toml::from_str(text).map_err(|e| WolfDataError::Validation {
+            line: None,
+            column: None,
+            message: format!("invalid column mapping: {}", e),
+        })
+    }
+
+    pub fn from_toml_file(path: impl AsRef<Path>) -> Result<Self, WolfDataError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| WolfDataError::io(path, e))?;
+        Self::from_toml_str(&text)
+    }
+
+    /// The expected name for a source column
+    pub fn target<'a>(&'a self, source: &'a str) -> &'a str {
+        self.columns.get(source).map_or(source, String::as_str)
+    }
+}
+
+/// How a file's header compares with the columns a record type expects
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaReport {
+    /// Expected columns that no source column provides
+    pub missing: Vec<String>,
+    /// Source columns that are not used
+    pub extra: Vec<String>,
+    /// `(source, expected)` pairs renamed by the mapping
+    pub renamed: Vec<(String, String)>,
+    /// `(source, expected)` pairs that look like unmapped renames, e.g. a
+    /// change of case or underscores
+    pub likely_renamed: Vec<(String, String)>,
+    /// Expected columns provided by more than one source column
+    pub duplicates: Vec<String>,
+}
+
+impl SchemaReport {
+    /// Whether every expected column can be read unambiguously
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.duplicates.is_empty()
+    }
+}
+
+impl fmt::Display for SchemaReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if !self.missing.is_empty() {
+            parts.push(format!("missing columns: {}", self.missing.join(", ")));
+        }
+        if !self.duplicates.is_empty() {
+            parts.push(format!("duplicate columns: {}", self.duplicates.join(", ")));
+        }
+        if !self.likely_renamed.is_empty() {
+            let pairs: Vec<_> = self
+                .likely_renamed
+                .iter()
+                .map(|(source, target)| format!("`{}` → `{}`?", source, target))
+                .collect();
+            parts.push(format!("possible renames: {}", pairs.join(", ")));
+        }
+        if !self.renamed.is_empty() {
+            let pairs: Vec<_> = self
+                .renamed
+                .iter()
+                .map(|(source, target)| format!("`{}` → `{}`", source, target))
+                .collect();
+            parts.push(format!("renamed: {}", pairs.join(", ")));
+        }
+        if !self.extra.is_empty() {
+            parts.push(format!("extra columns: {}", self.extra.join(", ")));
+        }
+        if parts.is_empty() {
+            write!(f, "header matches")
+        } else {
+            write!(f, "{}", parts.join("; "))
+        }
+    }
+}
+
+/// Compares a header against the expected columns after applying `mapping`.
+///
+/// # Arguments
+/// * `header` - Column names as they appear in the file
+/// * `expected` - Column names the record type needs
+/// * `mapping` - Renames from source to expected names
+pub fn check_header<'a, I>(header: I, expected: &[&str], mapping: &ColumnMapping) -> SchemaReport
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let expected_set: BTreeSet<&str> = expected.iter().copied().collect();
+    let mut report = SchemaReport::default();
+    let mut provided: BTreeMap<&str, usize> = BTreeMap::new();
+
+    for source in header.into_iter().map(str::trim) {
+        let target = mapping.target(source);
+        if target != source {
+            report
+                .renamed
+                .push((source.to_string(), target.to_string()));
+        }
+        match expected_set.get(target) {
+            Some(column) => *provided.entry(column).or_default() += 1,
+            None => report.extra.push(source.to_string()),
+        }
+    }
+
+    for column in expected {
+        match provided.get(column) {
+            None => report.missing.push(column.to_string()),
+            Some(n) if *n > 1 => report.duplicates.push(column.to_string()),
+            Some(_) => {}
+        }
+    }
+
+    for column in &report.missing {
+        if let Some(source) = report
+            .extra
+            .iter()
+            .find(|source| normalize(source) == normalize(column))
+        {
+            report.likely_renamed.push((source.clone(), column.clone()));
+        }
+    }
+
+    report
+}
+
+/// Lowercases and drops everything but letters and digits
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
@@ -0,0 +1,396 @@
+//! Binary snapshots of parsed datasets
+//!
+//! Parsing and validating the CSVs is the slowest part of every run. A
+//! `SnapshotCache` stores the records of a loaded `WolfDataset`, together
+//! with the `LoadReport` of each source, in a compact bincode file and hands
+//! them back on the next run, as long as the source files and the loader
+//! settings are unchanged.
+//!
+//! Each set of source paths has one snapshot file. The file starts with a
+//! key hashed from the snapshot format, the loader settings and the bytes
+//! of every source; a snapshot whose key no longer matches is stale and is
+//! rebuilt and overwritten.
+//!
+//! ```no_run
+//! # use std::fs::File;
+//! # use wolf_project_210::data::LoadOptions;
+//! # use wolf_project_210::dataset::WolfDataset;
+//! # use wolf_project_210::error::WolfDataError;
+//! # use wolf_project_210::snapshot::SnapshotCache;
+//! let sources = ["data/denning.csv", "data/reproduction.csv"];
+//! let open = |path| File::open(path).map_err(|e| WolfDataError::io(path, e));
+//! let cache = SnapshotCache::new(".snapshots");
+//! let (dataset, reports, status) = cache.load(&sources, &LoadOptions::default(), |options| {
+//!     let (denning, denning_report) = options.read_denning(open(sources[0])?)?;
+//!     let (reproduction, reproduction_report) = options.read_reproductive(open(sources[1])?)?;
+//!     let reports = vec![denning_report, reproduction_report];
+//!     Ok((WolfDataset::new(denning, reproduction), reports))
+//! })?;
+//! println!("{}", status);
+//! for report in &reports {
+//!     report.ensure_rejection_rate(0.1)?;
+//! }
+//! # Ok::<(), wolf_project_210::error::WolfDataError>(())
+//! ```
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use bincode::{DefaultOptions, Options};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::data::{DenningPhenology, LoadOptions, LoadReport, ReproductiveSuccess};
+use crate::dataset::WolfDataset;
+use crate::error::WolfDataError;
+use crate::units::Quantity;
+
+/// Bumped whenever the snapshot layout or the meaning of a stored record
+/// changes, so older snapshots are rebuilt
+const SNAPSHOT_FORMAT: u32 = 3;
+
+/// Whether `SnapshotCache::load` reused a snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotStatus {
+    /// The snapshot matched the sources and was read back
+    Reused(PathBuf),
+    /// There was no usable snapshot; the dataset was built and written here
+    Rebuilt(PathBuf),
+}
+
+impl fmt::Display for SnapshotStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotStatus::Reused(path) => write!(f, "reused snapshot {}", path.display()),
+            SnapshotStatus::Rebuilt(path) => write!(f, "wrote snapshot {}", path.display()),
+        }
+    }
+}
+
+/// A directory of dataset snapshots
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotCache {
+    dir: PathBuf,
+}
+
+impl SnapshotCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        SnapshotCache { dir: dir.into() }
+    }
+
+    /// Returns the snapshot of `sources` if it is current, otherwise calls
+    /// `build` with `options` and stores the dataset and load reports it
+    /// returns, one report per source. A reused snapshot hands back the
+    /// reports of the load that wrote it.
+    pub fn load<P, F>(
+        &self,
+        sources: &[P],
+        options: &LoadOptions,
+        build: F,
+    ) -> Result<(WolfDataset, Vec<LoadReport>, SnapshotStatus), WolfDataError>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&LoadOptions) -> Result<(WolfDataset, Vec<LoadReport>), WolfDataError>,
+    {
+        let path = self.path_for(sources);
+        let key = snapshot_key(sources, options)?;
+        if let Some((dataset, reports)) = self.read(&path, &key) {
+            return Ok((dataset, reports, SnapshotStatus::Reused(path)));
+        }
+        let (dataset, reports) = build(options)?;
+        self.write(&path, &key, &dataset, &reports)?;
+        Ok((dataset, reports, SnapshotStatus::Rebuilt(path)))
+    }
+
+    /// The snapshot file used for a set of sources
+    pub fn path_for<P: AsRef<Path>>(&self, sources: &[P]) -> PathBuf {
+        let mut hasher = Sha256::new();
+        for source in sources {
+            hasher.update(source.as_ref().to_string_lossy().as_bytes());
+            hasher.update([0]);
+        }
+        let name = format!("{:x}", hasher.finalize());
+        self.dir.join(format!("{}.bin", &name[..16]))
+    }
+
+    /// Removes every snapshot in the cache directory
+    pub fn clear(&self) -> Result<(), WolfDataError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(WolfDataError::io(&self.dir, e)),
+        };
+        for entry in entries {
+            let path = entry.map_err(|e| WolfDataError::io(&self.dir, e))?.path();
+            if path.extension().is_some_and(|ext| ext == "bin") {
+                fs::remove_file(&path).map_err(|e| WolfDataError::io(&path, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a snapshot; a missing, stale or unreadable file is a miss
+    fn read(&self, path: &Path, key: &str) -> Option<(WolfDataset, Vec<LoadReport>)> {
+        let file = File::open(path).ok()?;
+        // A damaged length prefix must not make bincode allocate more than
+        // the file could hold
+        let codec = DefaultOptions::new().with_limit(file.metadata().ok()?.len());
+        let mut reader = BufReader::new(file);
+        let header: Header = codec.deserialize_from(&mut reader).ok()?;
+        if header.format != SNAPSHOT_FORMAT || header.key != key {
+            return None;
+        }
+        let body: Body = codec.deserialize_from(&mut reader).ok()?;
+        let denning = body.denning.into_iter().map(DenningPhenology::try_from);
+        let reproduction = body
+            .reproduction
+            .into_iter()
+            .map(ReproductiveSuccess::try_from);
+        let dataset = WolfDataset::new(
+            denning.collect::<Result<_, _>>().ok()?,
+            reproduction.collect::<Result<_, _>>().ok()?,
+        );
+        Some((dataset, body.reports))
+    }
+
+    /// Writes a snapshot next to its final path and moves it into place, so
+    /// an interrupted run never leaves a truncated snapshot behind
+    fn write(
+        &self,
+        path: &Path,
+        key: &str,
+        dataset: &WolfDataset,
+        reports: &[LoadReport],
+    ) -> Result<(), WolfDataError> {
+        fs::create_dir_all(&self.dir).map_err(|e| WolfDataError::io(&self.dir, e))?;
+        let partial = path.with_extension("partial");
+        let file = File::create(&partial).map_err(|e| WolfDataError::io(&partial, e))?;
+        let mut writer = BufWriter::new(file);
+        let header = Header {
+            format: SNAPSHOT_FORMAT,
+            key: key.to_string(),
+        };
+        let body = Body {
+            denning: dataset.denning().iter().map(DenningRow::from).collect(),
+            reproduction: dataset
+                .reproduction()
+                .iter()
+                .map(ReproductionRow::from)
+                .collect(),
+            reports: reports.to_vec(),
+        };
+        let codec = DefaultOptions::new();
+        codec
+            .serialize_into(&mut writer, &header)
+            .and_then(|()| codec.serialize_into(&mut writer, &body))
+            .map_err(|e| WolfDataError::Export(format!("cannot encode snapshot: {}", e)))?;
+        writer.flush().map_err(|e| WolfDataError::io(&partial, e))?;
+        fs::rename(&partial, path).map_err(|e| WolfDataError::io(path, e))
+    }
+}
+
+/// Hash of the snapshot format, the loader settings and every source file
+fn snapshot_key<P: AsRef<Path>>(
+    sources: &[P],
+    options: &LoadOptions,
+) -> Result<String, WolfDataError> {
+    let mut hasher = Sha256::new();
+    hasher.update(SNAPSHOT_FORMAT.to_le_bytes());
+    hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+    let settings = DefaultOptions::new()
+        .serialize(options)
+        .map_err(|e| WolfDataError::Export(format!("cannot encode loader settings: {}", e)))?;
+    hasher.update(&settings);
+    for source in sources {
+        let path = source.as_ref();
+        let bytes = fs::read(path).map_err(|e| WolfDataError::io(path, e))?;
+        hasher.update((bytes.len() as u64).to_le_bytes());
+        hasher.update(&bytes);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format: u32,
+    key: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Body {
+    denning: Vec<DenningRow>,
+    reproduction: Vec<ReproductionRow>,
+    reports: Vec<LoadReport>,
+}
+
+/// A denning record as stored in a snapshot. The records' own `Deserialize`
+/// impls parse CSV text, so snapshots use plain mirrors of them.
+#[derive(Serialize, Deserialize)]
+struct DenningRow {
+    uid: u32,
+    study: String,
+    longitude_study: f64,
+    latitude_study: f64,
+    pack_id: u32,
+    denning_date: NaiveDate,
+    denning_doy: u16,
+    denned: i8,
+    fall_tmax: Option<f64>,
+    summer_tmax_prev1: Option<f64>,
+    winter_tmax: Option<f64>,
+    fall_prcp: Option<f64>,
+    summer_prcp_prev1: Option<f64>,
+    winter_swe: Option<f64>,
+    ti_ndvi_prev1: Option<f64>,
+    annual_pdo: Option<f64>,
+    annual_ao: Option<f64>,
+    sos_prev1: Option<f64>,
+    los_prev1: Option<f64>,
+    latitude_individual: f64,
+}
+
+impl From<&DenningPhenology> for DenningRow {
+    fn from(d: &DenningPhenology) -> Self {
+        DenningRow {
+            uid: d.uid,
+            study: d.study.clone(),
+            longitude_study: d.longitude_study,
+            latitude_study: d.latitude_study,
+            pack_id: d.pack_id,
+            denning_date: d.denning_date,
+            denning_doy: d.denning_doy,
+            denned: d.denned,
+            fall_tmax: d.fall_tmax.map(Quantity::value),
+            summer_tmax_prev1: d.summer_tmax_prev1.map(Quantity::value),
+            winter_tmax: d.winter_tmax.map(Quantity::value),
+            fall_prcp: d.fall_prcp.map(Quantity::value),
+            summer_prcp_prev1: d.summer_prcp_prev1.map(Quantity::value),
+            winter_swe: d.winter_swe.map(Quantity::value),
+            ti_ndvi_prev1: d.ti_ndvi_prev1,
+            annual_pdo: d.annual_pdo,
+            annual_ao: d.annual_ao,
+            sos_prev1: d.sos_prev1,
+            los_prev1: d.los_prev1,
+            latitude_individual: d.latitude_individual,
+        }
+    }
+}
+
+impl TryFrom<DenningRow> for DenningPhenology {
+    type Error = WolfDataError;
+
+    fn try_from(d: DenningRow) -> Result<Self, WolfDataError> {
+        Ok(DenningPhenology {
+            uid: d.uid,
+            study: d.study,
+            longitude_study: d.longitude_study,
+            latitude_study: d.latitude_study,
+            pack_id: d.pack_id,
+            denning_date: d.denning_date,
+            denning_doy: d.denning_doy,
+            denned: d.denned,
+            fall_tmax: quantity(d.fall_tmax)?,
+            summer_tmax_prev1: quantity(d.summer_tmax_prev1)?,
+            winter_tmax: quantity(d.winter_tmax)?,
+            fall_prcp: quantity(d.fall_prcp)?,
+            summer_prcp_prev1: quantity(d.summer_prcp_prev1)?,
+            winter_swe: quantity(d.winter_swe)?,
+            ti_ndvi_prev1: d.ti_ndvi_prev1,
+            annual_pdo: d.annual_pdo,
+            annual_ao: d.annual_ao,
+            sos_prev1: d.sos_prev1,
+            los_prev1: d.los_prev1,
+            latitude_individual: d.latitude_individual,
+        })
+    }
+}
+
+/// A reproductive record as stored in a snapshot
+#[derive(Serialize, Deserialize)]
+struct ReproductionRow {
+    uid: u32,
+    study: String,
+    longitude_study: f64,
+    latitude_study: f64,
+    pack_id: u32,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    success: u8,
+    summer_prcp: Option<f64>,
+    fall_prcp: Option<f64>,
+    winter_swe: Option<f64>,
+    fall_tmax: Option<f64>,
+    summer_tmax: Option<f64>,
+    winter_tmax: Option<f64>,
+    ti_ndvi_prev1: Option<f64>,
+    ti_ndvi: Option<f64>,
+    annual_pdo: Option<f64>,
+    annual_ao: Option<f64>,
+    home_range_area: Option<f64>,
+    denning_match_growing_season: Option<f64>,
+}
+
+impl From<&ReproductiveSuccess> for ReproductionRow {
+    fn from(r: &ReproductiveSuccess) -> Self {
+        ReproductionRow {
+            uid: r.uid,
+            study: r.study.clone(),
+            longitude_study: r.longitude_study,
+            latitude_study: r.latitude_study,
+            pack_id: r.pack_id,
+            start_date: r.start_date,
+            end_date: r.end_date,
+            success: r.success,
+            summer_prcp: r.summer_prcp.map(Quantity::value),
+            fall_prcp: r.fall_prcp.map(Quantity::value),
+            winter_swe: r.winter_swe.map(Quantity::value),
+            fall_tmax: r.fall_tmax.map(Quantity::value),
+            summer_tmax: r.summer_tmax.map(Quantity::value),
+            winter_tmax: r.winter_tmax.map(Quantity::value),
+            ti_ndvi_prev1: r.ti_ndvi_prev1,
+            ti_ndvi: r.ti_ndvi,
+            annual_pdo: r.annual_pdo,
+            annual_ao: r.annual_ao,
+            home_range_area: r.home_range_area,
+            denning_match_growing_season: r.denning_match_growing_season,
+        }
+    }
+}
+
+impl TryFrom<ReproductionRow> for ReproductiveSuccess {
+    type Error = WolfDataError;
+
+    fn try_from(r: ReproductionRow) -> Result<Self, WolfDataError> {
+        Ok(ReproductiveSuccess {
+            uid: r.uid,
+            study: r.study,
+            longitude_study: r.longitude_study,
+            latitude_study: r.latitude_study,
+            pack_id: r.pack_id,
+            start_date: r.start_date,
+            end_date: r.end_date,
+            success: r.success,
+            summer_prcp: quantity(r.summer_prcp)?,
+            fall_prcp: quantity(r.fall_prcp)?,
+            winter_swe: quantity(r.winter_swe)?,
+            fall_tmax: quantity(r.fall_tmax)?,
+            summer_tmax: quantity(r.summer_tmax)?,
+            winter_tmax: quantity(r.winter_tmax)?,
+            ti_ndvi_prev1: r.ti_ndvi_prev1,
+            ti_ndvi: r.ti_ndvi,
+            annual_pdo: r.annual_pdo,
+            annual_ao: r.annual_ao,
+            home_range_area: r.home_range_area,
+            denning_match_growing_season: r.denning_match_growing_season,
+        })
+    }
+}
+
+/// Re-checks a stored value, so a damaged snapshot cannot produce an
+/// out-of-range quantity
+fn quantity<Q: Quantity>(value: Option<f64>) -> Result<Option<Q>, WolfDataError> {
+    value.map(Q::new).transpose()
+}
@@ -0,0 +1,225 @@
+//! Ad-hoc SQL over the wolf datasets (requires the `sql` feature)
+//!
+//! `SqlSession::from_dataset` copies the records into an in-memory SQLite
+//! database with three tables:
+//!
+//! * `denning` — one row per denning record, columns as in the source file
+//! * `reproduction` — one row per reproductive record
+//! * `pack_seasons` — every denning record left-joined to its season's
+//!   reproductive record, with the columns of `Table::from_pack_seasons`
+//!
+//! Dates are stored as ISO-8601 text and come back as `Value::Date` when a
+//! result column is a plain date column. Query results are `Table`s, so they
+//! print and export like any other table.
+
+use chrono::NaiveDate;
+use rusqlite::types::{ToSqlOutput, ValueRef};
+use rusqlite::{Connection, ToSql};
+
+use crate::data::JoinKind;
+use crate::dataset::WolfDataset;
+use crate::error::WolfDataError;
+use crate::export::{Column, ColumnType, Table, Value};
+
+/// An in-memory SQLite database holding copies of the record tables
+pub struct SqlSession {
+    connection: Connection,
+}
+
+impl SqlSession {
+    /// An empty session; add tables with `load_table`
+    pub fn new() -> Result<Self, WolfDataError> {
+        Ok(SqlSession {
+            connection: Connection::open_in_memory()?,
+        })
+    }
+
+    /// A session with the `denning`, `reproduction` and `pack_seasons` tables
+    pub fn from_dataset(dataset: &WolfDataset) -> Result<Self, WolfDataError> {
+        let mut session = SqlSession::new()?;
+        session.load_table("denning", &Table::from_records(dataset.denning())?)?;
+        session.load_table(
+            "reproduction",
+            &Table::from_records(dataset.reproduction())?,
+        )?;
+        let join = dataset.join_pack_seasons(JoinKind::Left);
+        session.load_table("pack_seasons", &Table::from_pack_seasons(&join.seasons)?)?;
+        Ok(session)
+    }
+
+    /// Creates table `name` with the table's columns and copies its rows in,
+    /// replacing any existing table of that name
+    pub fn load_table(&mut self, name: &str, table: &Table) -> Result<(), WolfDataError> {
+        let columns: Vec<String> = table
+            .columns()
+            .iter()
+            .map(|c| format!("{} {}", quote(&c.name), sql_type(c.kind)))
+            .collect();
+        let placeholders = vec!["?"; table.columns().len()].join(", ");
+
+        let transaction = self.connection.transaction()?;
+        transaction.execute_batch(&format!(
+            "DROP TABLE IF EXISTS {name}; CREATE TABLE {name} ({});",
+            columns.join(", "),
+            name = quote(name)
+        ))?;
+        {
+            let mut insert = transaction.prepare(&format!(
+                "INSERT INTO {} VALUES ({})",
+                quote(name),
+                placeholders
+            ))?;
+            for row in table.rows() {
+                insert.execute(rusqlite::params_from_iter(row))?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Names of the tables in the session, sorted
+    pub fn table_names(&self) -> Result<Vec<String>, WolfDataError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")?;
+        let names = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(names)
+    }
+
+    /// Runs one SQL statement and collects its result rows.
+    ///
+    /// Column types follow the declared type of plain column references
+    /// when every value fits it; other columns are `Integer` if every value
+    /// is an integer, `Float` if every value is numeric and `Text` otherwise.
+    pub fn query(&self, sql: &str) -> Result<Table, WolfDataError> {
+        let mut statement = self.connection.prepare(sql)?;
+        let declared: Vec<Option<ColumnType>> = statement
+            .columns()
+            .iter()
+            .map(|c| c.decl_type().and_then(declared_type))
+            .collect();
+        let names: Vec<String> = statement
+            .column_names()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+
+        let mut cells: Vec<Vec<Value>> = Vec::new();
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let values = (0..names.len())
+                .map(|i| row.get_ref(i).map(from_sql))
+                .collect::<Result<Vec<_>, _>>()?;
+            cells.push(values);
+        }
+
+        let kinds: Vec<ColumnType> = declared
+            .iter()
+            .enumerate()
+            .map(|(i, kind)| {
+                kind.filter(|k| cells.iter().all(|r| fits(&r[i], *k)))
+                    .unwrap_or_else(|| inferred_type(cells.iter().map(|r| &r[i])))
+            })
+            .collect();
+        let columns = names
+            .into_iter()
+            .zip(&kinds)
+            .map(|(name, kind)| Column::new(name, *kind))
+            .collect();
+        let mut table = Table::new(columns);
+        for row in cells {
+            let row = row
+                .into_iter()
+                .zip(&kinds)
+                .map(|(value, kind)| coerce(value, *kind))
+                .collect();
+            table.push_row(row)?;
+        }
+        Ok(table)
+    }
+}
+
+impl ToSql for Value {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(match self {
+            Value::Null => ToSqlOutput::Borrowed(ValueRef::Null),
+            Value::Integer(v) => ToSqlOutput::Borrowed(ValueRef::Integer(*v)),
+            Value::Float(v) => ToSqlOutput::Borrowed(ValueRef::Real(*v)),
+            Value::Text(v) => ToSqlOutput::Borrowed(ValueRef::Text(v.as_bytes())),
+            Value::Date(v) => ToSqlOutput::from(v.format("%Y-%m-%d").to_string()),
+        })
+    }
+}
+
+/// Double-quotes an identifier so mixed-case names like `tiNDVI_prev1` survive
+pub(crate) fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+pub(crate) fn sql_type(kind: ColumnType) -> &'static str {
+    match kind {
+        ColumnType::Integer => "INTEGER",
+        ColumnType::Float => "REAL",
+        ColumnType::Text => "TEXT",
+        ColumnType::Date => "DATE",
+    }
+}
+
+fn declared_type(declared: &str) -> Option<ColumnType> {
+    match declared.to_ascii_uppercase().as_str() {
+        "INTEGER" => Some(ColumnType::Integer),
+        "REAL" => Some(ColumnType::Float),
+        "TEXT" => Some(ColumnType::Text),
+        "DATE" => Some(ColumnType::Date),
+        _ => None,
+    }
+}
+
+pub(crate) fn from_sql(value: ValueRef<'_>) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(v) => Value::Integer(v),
+        ValueRef::Real(v) => Value::Float(v),
+        ValueRef::Text(v) | ValueRef::Blob(v) => {
+            Value::Text(String::from_utf8_lossy(v).into_owned())
+        }
+    }
+}
+
+fn inferred_type<'a>(values: impl Iterator<Item = &'a Value>) -> ColumnType {
+    let mut kind = ColumnType::Integer;
+    for value in values {
+        match value {
+            Value::Null | Value::Integer(_) => {}
+            Value::Float(_) => kind = ColumnType::Float,
+            Value::Text(_) | Value::Date(_) => return ColumnType::Text,
+        }
+    }
+    kind
+}
+
+/// Whether a cell can be stored in a column of the given type
+fn fits(value: &Value, kind: ColumnType) -> bool {
+    match (value, kind) {
+        (Value::Null, _) => true,
+        (Value::Integer(_), ColumnType::Integer | ColumnType::Float) => true,
+        (Value::Float(_), ColumnType::Float) => true,
+        (Value::Text(_), ColumnType::Text) => true,
+        (Value::Text(v), ColumnType::Date) => NaiveDate::parse_from_str(v, "%Y-%m-%d").is_ok(),
+        _ => false,
+    }
+}
+
+/// Converts a cell to its column's type; SQLite hands back integers from
+/// REAL columns when the value is whole, and dates as text
+pub(crate) fn coerce(value: Value, kind: ColumnType) -> Value {
+    match (value, kind) {
+        (Value::Integer(v), ColumnType::Float) => Value::Float(v as f64),
+        (Value::Text(v), ColumnType::Date) => {
+            NaiveDate::parse_from_str(&v, "%Y-%m-%d").map_or(Value::Null, Value::Date)
+        }
+        (value, _) => value,
+    }
+}
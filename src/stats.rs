@@ -0,0 +1,354 @@
+//! Statistical models of denning and reproductive success, and the
+//! covariate correlations to check before fitting them
+//!
+//! Models are fit to any `WolfRecord` set by naming a response and the
+//! covariates to use. `Design` collects the complete rows into ndarray
+//! matrices; records missing the response or any chosen covariate are
+//! left out and counted.
+//!
+//! ```no_run
+//! # use wolf_project_210::data::read_reproductive_csv;
+//! # use wolf_project_210::stats::logistic::logistic_regression;
+//! let (reproduction, _) = read_reproductive_csv("data/Wolf_ReproductiveSuccess_AK_CA.csv")?;
+//! let fit = logistic_regression(&reproduction, &["winter_swe", "summer_tmax"])?;
+//! println!("{}", fit);
+//! # Ok::<(), wolf_project_210::error::WolfDataError>(())
+//! ```
+
+pub mod correlation;
+pub mod linear;
+pub mod logistic;
+pub mod mixed;
+
+use ndarray::{Array1, Array2, Axis};
+use serde::Serialize;
+use statrs::distribution::{ContinuousCDF, Normal, StudentsT};
+
+use crate::data::PackKey;
+use crate::error::WolfDataError;
+use crate::export::{Column, ColumnType, Table, Value};
+use crate::record::WolfRecord;
+
+/// Name of the constant column every design starts with
+pub const INTERCEPT: &str = "(Intercept)";
+
+/// Response vector and model matrix of the complete rows of a record set
+#[derive(Debug, Clone, PartialEq)]
+pub struct Design {
+    pub response: String,
+    /// Column names of `x`, starting with the intercept
+    pub terms: Vec<String>,
+    pub x: Array2<f64>,
+    pub y: Array1<f64>,
+    /// Position in the input records of each row of `x`
+    pub rows: Vec<usize>,
+    /// Study and pack of each row of `x`
+    pub groups: Vec<PackKey>,
+    /// Records left out because the response or a covariate was missing
+    pub dropped: usize,
+}
+
+impl Design {
+    /// Builds the design for `response ~ covariates`. Names are the source
+    /// column names accepted by `WolfRecord::covariate`.
+    pub fn from_records<T: WolfRecord>(
+        records: &[T],
+        response: &str,
+        covariates: &[&str],
+    ) -> Result<Self, WolfDataError> {
+        check_numeric::<T>(std::iter::once(&response).chain(covariates))?;
+
+        let p = covariates.len() + 1;
+        let mut x = Vec::with_capacity(records.len() * p);
+        let mut y = Vec::with_capacity(records.len());
+        let mut rows = Vec::with_capacity(records.len());
+        let mut groups = Vec::with_capacity(records.len());
+        for (i, record) in records.iter().enumerate() {
+            let values: Option<Vec<f64>> = covariates.iter().map(|c| record.covariate(c)).collect();
+            if let (Some(response), Some(values)) = (record.covariate(response), values) {
+                x.push(1.0);
+                x.extend(values);
+                y.push(response);
+                rows.push(i);
+                groups.push(record.pack_key());
+            }
+        }
+
+        let n = rows.len();
+        if n <= p {
+            return Err(WolfDataError::Validation {
+                line: None,
+                column: None,
+                message: format!(
+                    "{} complete rows are too few to estimate {} coefficients",
+                    n, p
+                ),
+            });
+        }
+        let terms = std::iter::once(INTERCEPT)
+            .chain(covariates.iter().copied())
+            .map(str::to_string)
+            .collect();
+        Ok(Design {
+            response: response.to_string(),
+            terms,
+            x: Array2::from_shape_vec((n, p), x).expect("one value per row and term"),
+            y: Array1::from(y),
+            dropped: records.len() - n,
+            rows,
+            groups,
+        })
+    }
+
+    /// Adds a 0/1 column per study except the first, which becomes the
+    /// reference level absorbed by the intercept
+    pub fn with_study_effects(mut self) -> Self {
+        let mut studies: Vec<String> = self.groups.iter().map(|g| g.study.clone()).collect();
+        studies.sort_unstable();
+        studies.dedup();
+        for study in studies.iter().skip(1) {
+            let column: Array1<f64> = self
+                .groups
+                .iter()
+                .map(|g| f64::from(u8::from(g.study == *study)))
+                .collect();
+            self.x
+                .push_column(column.view())
+                .expect("one value per row");
+            self.terms.push(study_term(study));
+        }
+        self
+    }
+
+    /// Number of rows used
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The model row of a record, `None` if it misses a covariate
+    fn row_of<T: WolfRecord>(terms: &[String], record: &T) -> Option<Array1<f64>> {
+        terms
+            .iter()
+            .map(|term| match term.as_str() {
+                INTERCEPT => Some(1.0),
+                term if term == study_term(record.study()) => Some(1.0),
+                term if term.starts_with("study[") => Some(0.0),
+                name => record.covariate(name),
+            })
+            .collect::<Option<Vec<f64>>>()
+            .map(Array1::from)
+    }
+}
+
+/// One estimated coefficient with its Wald test and confidence interval
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Coefficient {
+    pub term: String,
+    pub estimate: f64,
+    pub std_error: f64,
+    /// Estimate divided by its standard error
+    pub statistic: f64,
+    /// Two-sided p-value of `statistic`
+    pub p_value: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl Coefficient {
+    /// t test and interval with `df` residual degrees of freedom
+    fn student(term: &str, estimate: f64, std_error: f64, df: usize, confidence: f64) -> Self {
+        let t = StudentsT::new(0.0, 1.0, df as f64).expect("positive degrees of freedom");
+        let statistic = estimate / std_error;
+        let critical = t.inverse_cdf(0.5 + confidence / 2.0);
+        Coefficient {
+            term: term.to_string(),
+            estimate,
+            std_error,
+            statistic,
+            p_value: 2.0 * t.sf(statistic.abs()),
+            lower: estimate - critical * std_error,
+            upper: estimate + critical * std_error,
+        }
+    }
+
+    /// Wald test and interval against the standard normal
+    fn normal(term: &str, estimate: f64, std_error: f64, confidence: f64) -> Self {
+        let normal = Normal::standard();
+        let statistic = estimate / std_error;
+        let critical = normal.inverse_cdf(0.5 + confidence / 2.0);
+        Coefficient {
+            term: term.to_string(),
+            estimate,
+            std_error,
+            statistic,
+            p_value: 2.0 * normal.sf(statistic.abs()),
+            lower: estimate - critical * std_error,
+            upper: estimate + critical * std_error,
+        }
+    }
+}
+
+/// Fails on the first name that is not a numeric column of `T`
+pub(crate) fn check_numeric<'a, T: WolfRecord>(
+    names: impl IntoIterator<Item = &'a &'a str>,
+) -> Result<(), WolfDataError> {
+    match names
+        .into_iter()
+        .find(|name| !T::NUMERIC_COLUMNS.contains(name))
+    {
+        Some(name) => Err(WolfDataError::Validation {
+            line: None,
+            column: Some(name.to_string()),
+            message: "not a numeric column of these records".to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Name of the fixed-effect term of a study
+fn study_term(study: &str) -> String {
+    format!("study[{}]", study)
+}
+
+/// One row per coefficient: its estimate, standard error, test statistic
+/// (named `statistic`), p-value and interval, then any `extra` columns
+fn coefficient_table(
+    coefficients: &[Coefficient],
+    statistic: &str,
+    extra: &[(&str, Vec<f64>)],
+) -> Table {
+    let names = [
+        "estimate",
+        "std_error",
+        statistic,
+        "p_value",
+        "lower",
+        "upper",
+    ];
+    let columns = std::iter::once(Column::new("term", ColumnType::Text))
+        .chain(
+            names
+                .into_iter()
+                .chain(extra.iter().map(|(name, _)| *name))
+                .map(|name| Column::new(name, ColumnType::Float)),
+        )
+        .collect();
+    let mut table = Table::new(columns);
+    for (i, c) in coefficients.iter().enumerate() {
+        let mut row = vec![Value::Text(c.term.clone())];
+        row.extend(
+            [
+                c.estimate,
+                c.std_error,
+                c.statistic,
+                c.p_value,
+                c.lower,
+                c.upper,
+            ]
+            .into_iter()
+            .chain(extra.iter().map(|(_, values)| values[i]))
+            .map(Value::Float),
+        );
+        table.push_row(row).expect("one value per column");
+    }
+    table
+}
+
+/// `Xᵀ W X` for diagonal weights `W`
+fn weighted_cross_product(x: &Array2<f64>, weights: &Array1<f64>) -> Array2<f64> {
+    let weighted = x * &weights.view().insert_axis(Axis(1));
+    x.t().dot(&weighted)
+}
+
+/// Lower-triangular Cholesky factor of a symmetric positive-definite
+/// matrix, `None` if the matrix is singular (collinear covariates)
+fn cholesky(a: &Array2<f64>) -> Option<Array2<f64>> {
+    let n = a.nrows();
+    let scale = (0..n).map(|i| a[[i, i]].abs()).fold(0.0, f64::max);
+    let mut l = Array2::<f64>::zeros((n, n));
+    for j in 0..n {
+        let diagonal = a[[j, j]] - (0..j).map(|k| l[[j, k]].powi(2)).sum::<f64>();
+        if diagonal <= scale * 1e-12 {
+            return None;
+        }
+        l[[j, j]] = diagonal.sqrt();
+        for i in j + 1..n {
+            let dot: f64 = (0..j).map(|k| l[[i, k]] * l[[j, k]]).sum();
+            l[[i, j]] = (a[[i, j]] - dot) / l[[j, j]];
+        }
+    }
+    Some(l)
+}
+
+/// Inverse of a symmetric positive-definite matrix, `None` if singular
+fn invert_spd(a: &Array2<f64>) -> Option<Array2<f64>> {
+    let l = cholesky(a)?;
+    let n = l.nrows();
+
+    // Invert L by forward substitution, then A⁻¹ = L⁻ᵀ L⁻¹
+    let mut l_inv = Array2::<f64>::zeros((n, n));
+    for j in 0..n {
+        l_inv[[j, j]] = 1.0 / l[[j, j]];
+        for i in j + 1..n {
+            let dot: f64 = (j..i).map(|k| l[[i, k]] * l_inv[[k, j]]).sum();
+            l_inv[[i, j]] = -dot / l[[i, i]];
+        }
+    }
+    Some(l_inv.t().dot(&l_inv))
+}
+
+/// Log-determinant of a symmetric positive-definite matrix
+fn log_det_spd(a: &Array2<f64>) -> Option<f64> {
+    let l = cholesky(a)?;
+    Some(2.0 * l.diag().iter().map(|d| d.ln()).sum::<f64>())
+}
+
+/// Fitted probabilities are kept this far from 0 and 1 so the weights and
+/// the log-likelihood stay finite when a covariate separates the classes
+const PROBABILITY_FLOOR: f64 = 1e-10;
+
+fn inverse_logit(eta: f64) -> f64 {
+    (1.0 / (1.0 + (-eta).exp())).clamp(PROBABILITY_FLOOR, 1.0 - PROBABILITY_FLOOR)
+}
+
+fn collinear(terms: &[String]) -> WolfDataError {
+    WolfDataError::Validation {
+        line: None,
+        column: None,
+        message: format!(
+            "covariates {} are collinear or constant",
+            terms[1..].join(", ")
+        ),
+    }
+}
+
+/// A binomial response must be 0/1 and take both values; with one outcome
+/// the coefficients diverge and the null deviance is undefined
+fn check_binary_response(design: &Design) -> Result<(), WolfDataError> {
+    let invalid = |message: String| WolfDataError::Validation {
+        line: None,
+        column: Some(design.response.clone()),
+        message,
+    };
+    if let Some(value) = design.y.iter().find(|y| **y != 0.0 && **y != 1.0) {
+        return Err(invalid(format!(
+            "logistic regression needs a 0/1 response, found {}",
+            value
+        )));
+    }
+    match design.y.first() {
+        None => Err(invalid(
+            "logistic regression needs at least one row".to_string(),
+        )),
+        Some(first) if design.y.iter().all(|y| y == first) => Err(invalid(format!(
+            "logistic regression needs both outcomes, but all {} rows are {}",
+            design.len(),
+            first
+        ))),
+        Some(_) => Ok(()),
+    }
+}
@@ -0,0 +1,431 @@
+//! Pairwise correlations between covariates
+//!
+//! Each pair of covariates is correlated over the records that have both
+//! values, so a column with gaps only loses rows for its own pairs. The
+//! p-values test zero correlation with `t = r √((n − 2) / (1 − r²))` on
+//! `n − 2` degrees of freedom; for Spearman's rho this is the usual
+//! large-sample approximation. They are adjusted for the number of pairs
+//! by Holm's step-down method or the Benjamini–Hochberg false discovery
+//! rate.
+
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use ndarray::Array2;
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use serde::Serialize;
+use statrs::distribution::{ContinuousCDF, StudentsT};
+
+use super::check_numeric;
+use crate::error::WolfDataError;
+use crate::export::{Column, ColumnType, Table, Value};
+use crate::record::WolfRecord;
+
+/// Correlation coefficient to compute
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Method {
+    #[default]
+    Pearson,
+    /// Pearson correlation of the ranks, ties sharing their mean rank
+    Spearman,
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Method::Pearson => write!(f, "Pearson"),
+            Method::Spearman => write!(f, "Spearman"),
+        }
+    }
+}
+
+/// Multiple-comparison adjustment of the pairwise p-values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Adjustment {
+    None,
+    /// Holm's step-down method; controls the family-wise error rate
+    #[default]
+    Holm,
+    /// Benjamini–Hochberg; controls the false discovery rate
+    BenjaminiHochberg,
+}
+
+impl fmt::Display for Adjustment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Adjustment::None => write!(f, "unadjusted"),
+            Adjustment::Holm => write!(f, "Holm"),
+            Adjustment::BenjaminiHochberg => write!(f, "Benjamini–Hochberg"),
+        }
+    }
+}
+
+/// Settings for a correlation matrix.
+///
+/// `correlation_matrix` uses `CorrelationOptions::default()`: Pearson,
+/// Holm-adjusted, significant below 0.05.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorrelationOptions {
+    pub method: Method,
+    pub adjustment: Adjustment,
+    /// Adjusted p-values below this are flagged significant
+    pub alpha: f64,
+}
+
+impl Default for CorrelationOptions {
+    fn default() -> Self {
+        CorrelationOptions {
+            method: Method::default(),
+            adjustment: Adjustment::default(),
+            alpha: 0.05,
+        }
+    }
+}
+
+impl CorrelationOptions {
+    /// Correlates every pair of `covariates`, named as in
+    /// `WolfRecord::covariate`
+    pub fn compute<T: WolfRecord>(
+        &self,
+        records: &[T],
+        covariates: &[&str],
+    ) -> Result<CorrelationMatrix, WolfDataError> {
+        check_numeric::<T>(covariates)?;
+        if covariates.len() < 2 {
+            return Err(WolfDataError::Validation {
+                line: None,
+                column: None,
+                message: "a correlation matrix needs at least two covariates".to_string(),
+            });
+        }
+        let columns: Vec<Vec<Option<f64>>> = covariates
+            .iter()
+            .map(|name| records.iter().map(|r| r.covariate(name)).collect())
+            .collect();
+
+        let mut pairs = Vec::new();
+        for i in 0..covariates.len() {
+            for j in i + 1..covariates.len() {
+                let (x, y): (Vec<f64>, Vec<f64>) = columns[i]
+                    .iter()
+                    .zip(&columns[j])
+                    .filter_map(|(x, y)| Some(((*x)?, (*y)?)))
+                    .unzip();
+                let r = match self.method {
+                    Method::Pearson => pearson(&x, &y),
+                    Method::Spearman => pearson(&ranks(&x), &ranks(&y)),
+                };
+                pairs.push(Correlation {
+                    x: covariates[i].to_string(),
+                    y: covariates[j].to_string(),
+                    n: x.len(),
+                    r,
+                    p_value: r.map(|r| p_value(r, x.len())),
+                    adjusted_p: None,
+                    significant: false,
+                });
+            }
+        }
+
+        let p_values: Vec<Option<f64>> = pairs.iter().map(|p| p.p_value).collect();
+        for (pair, adjusted) in pairs.iter_mut().zip(adjust(&p_values, self.adjustment)) {
+            pair.adjusted_p = adjusted;
+            pair.significant = adjusted.is_some_and(|p| p < self.alpha);
+        }
+        Ok(CorrelationMatrix {
+            method: self.method,
+            adjustment: self.adjustment,
+            alpha: self.alpha,
+            columns: covariates.iter().map(|c| c.to_string()).collect(),
+            pairs,
+        })
+    }
+}
+
+/// Pearson correlations with Holm-adjusted significance
+pub fn correlation_matrix<T: WolfRecord>(
+    records: &[T],
+    covariates: &[&str],
+) -> Result<CorrelationMatrix, WolfDataError> {
+    CorrelationOptions::default().compute(records, covariates)
+}
+
+/// Correlation of one pair of covariates
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Correlation {
+    pub x: String,
+    pub y: String,
+    /// Records with both values
+    pub n: usize,
+    /// `None` when fewer than three records have both values or either
+    /// covariate is constant over them
+    pub r: Option<f64>,
+    pub p_value: Option<f64>,
+    pub adjusted_p: Option<f64>,
+    /// Whether `adjusted_p` is below the chosen alpha
+    pub significant: bool,
+}
+
+/// Pairwise correlations of a covariate selection
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CorrelationMatrix {
+    pub method: Method,
+    pub adjustment: Adjustment,
+    pub alpha: f64,
+    pub columns: Vec<String>,
+    /// Each unordered pair once, in the order of `columns`
+    pub pairs: Vec<Correlation>,
+}
+
+impl CorrelationMatrix {
+    /// The correlation of two covariates, in either order
+    pub fn get(&self, x: &str, y: &str) -> Option<&Correlation> {
+        self.pairs
+            .iter()
+            .find(|p| (p.x == x && p.y == y) || (p.x == y && p.y == x))
+    }
+
+    /// The symmetric matrix of coefficients in the order of `columns`, with
+    /// 1 on the diagonal and NaN where a pair has no coefficient
+    pub fn matrix(&self) -> Array2<f64> {
+        let k = self.columns.len();
+        let mut matrix = Array2::from_diag_elem(k, 1.0);
+        let mut pairs = self.pairs.iter();
+        for i in 0..k {
+            for j in i + 1..k {
+                let r = pairs.next().and_then(|p| p.r).unwrap_or(f64::NAN);
+                matrix[[i, j]] = r;
+                matrix[[j, i]] = r;
+            }
+        }
+        matrix
+    }
+
+    /// One row per pair with its coefficient and tests
+    pub fn table(&self) -> Table {
+        let mut table = Table::new(vec![
+            Column::new("x", ColumnType::Text),
+            Column::new("y", ColumnType::Text),
+            Column::new("n", ColumnType::Integer),
+            Column::new("r", ColumnType::Float),
+            Column::new("p_value", ColumnType::Float),
+            Column::new("adjusted_p", ColumnType::Float),
+            Column::new("significant", ColumnType::Integer),
+        ]);
+        let float = |v: Option<f64>| v.map_or(Value::Null, Value::Float);
+        for p in &self.pairs {
+            let row = vec![
+                Value::Text(p.x.clone()),
+                Value::Text(p.y.clone()),
+                Value::Integer(p.n as i64),
+                float(p.r),
+                float(p.p_value),
+                float(p.adjusted_p),
+                Value::Integer(i64::from(p.significant)),
+            ];
+            table.push_row(row).expect("one value per column");
+        }
+        table
+    }
+
+    /// The square matrix as a table: a `covariate` column, then one column
+    /// per covariate
+    pub fn matrix_table(&self) -> Table {
+        let columns = std::iter::once(Column::new("covariate", ColumnType::Text))
+            .chain(
+                self.columns
+                    .iter()
+                    .map(|c| Column::new(c.as_str(), ColumnType::Float)),
+            )
+            .collect();
+        let mut table = Table::new(columns);
+        for (name, row) in self.columns.iter().zip(self.matrix().rows()) {
+            let values = row.iter().map(|r| {
+                if r.is_nan() {
+                    Value::Null
+                } else {
+                    Value::Float(*r)
+                }
+            });
+            let row = std::iter::once(Value::Text(name.clone()))
+                .chain(values)
+                .collect();
+            table.push_row(row).expect("one value per column");
+        }
+        table
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<(), WolfDataError> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Renders the matrix as a blue (−1) to red (+1) heatmap PNG with the
+    /// coefficient in each cell
+    pub fn plot_heatmap(&self, path: impl AsRef<Path>) -> Result<(), WolfDataError> {
+        let k = self.columns.len();
+        // Cells wide enough for a column name under each
+        let side = 180 + 110 * k as u32;
+        let root = BitMapBackend::new(path.as_ref(), (side, side)).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let x_label = |v: &SegmentValue<usize>| match v {
+            SegmentValue::CenterOf(j) if *j < k => self.columns[*j].clone(),
+            _ => String::new(),
+        };
+        // Row 0 at the top, as the matrix is printed
+        let y_label = |v: &SegmentValue<usize>| match v {
+            SegmentValue::CenterOf(y) if *y < k => self.columns[k - 1 - y].clone(),
+            _ => String::new(),
+        };
+        let mut chart = ChartBuilder::on(&root)
+            .caption(format!("{} correlations", self.method), ("sans-serif", 22))
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(130)
+            .build_cartesian_2d((0..k - 1).into_segmented(), (0..k - 1).into_segmented())?;
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .x_labels(k + 1)
+            .y_labels(k + 1)
+            .x_label_formatter(&x_label)
+            .y_label_formatter(&y_label)
+            .draw()?;
+
+        let matrix = self.matrix();
+        let cell =
+            |i: usize, j: usize| (SegmentValue::CenterOf(j), SegmentValue::CenterOf(k - 1 - i));
+        chart.draw_series(matrix.indexed_iter().map(|((i, j), r)| {
+            let (x, y) = (j, k - 1 - i);
+            Rectangle::new(
+                [
+                    (SegmentValue::Exact(x), SegmentValue::Exact(y)),
+                    (SegmentValue::Exact(x + 1), SegmentValue::Exact(y + 1)),
+                ],
+                heat_color(*r).filled(),
+            )
+        }))?;
+        let centered = ("sans-serif", 14)
+            .into_font()
+            .color(&BLACK)
+            .pos(Pos::new(HPos::Center, VPos::Center));
+        chart.draw_series(
+            matrix
+                .indexed_iter()
+                .filter(|(_, r)| !r.is_nan())
+                .map(|((i, j), r)| Text::new(format!("{:.2}", r), cell(i, j), centered.clone())),
+        )?;
+        root.present()?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for CorrelationMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} correlations ({} p-values, significant below {})",
+            self.method, self.adjustment, self.alpha
+        )?;
+        write!(f, "{}", self.matrix_table())
+    }
+}
+
+/// White at 0, shading to blue at −1 and red at +1; grey when undefined
+fn heat_color(r: f64) -> RGBColor {
+    if r.is_nan() {
+        return RGBColor(200, 200, 200);
+    }
+    let fade = (255.0 * (1.0 - r.abs().min(1.0))) as u8;
+    if r < 0.0 {
+        RGBColor(fade, fade, 255)
+    } else {
+        RGBColor(255, fade, fade)
+    }
+}
+
+/// Pearson's r, `None` for fewer than three pairs or a constant variable
+fn pearson(x: &[f64], y: &[f64]) -> Option<f64> {
+    let n = x.len();
+    if n < 3 {
+        return None;
+    }
+    let mean = |v: &[f64]| v.iter().sum::<f64>() / n as f64;
+    let (mx, my) = (mean(x), mean(y));
+    let (mut sxy, mut sxx, mut syy) = (0.0, 0.0, 0.0);
+    for (x, y) in x.iter().zip(y) {
+        sxy += (x - mx) * (y - my);
+        sxx += (x - mx).powi(2);
+        syy += (y - my).powi(2);
+    }
+    if sxx == 0.0 || syy == 0.0 {
+        return None;
+    }
+    Some((sxy / (sxx * syy).sqrt()).clamp(-1.0, 1.0))
+}
+
+/// 1-based ranks, ties sharing the mean of their ranks
+fn ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        for &i in &order[start..end] {
+            ranks[i] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+/// Two-sided p-value of `r` from `n ≥ 3` pairs against zero correlation
+fn p_value(r: f64, n: usize) -> f64 {
+    if r.abs() == 1.0 {
+        return 0.0;
+    }
+    let df = (n - 2) as f64;
+    let t = r * (df / (1.0 - r * r)).sqrt();
+    let student = StudentsT::new(0.0, 1.0, df).expect("positive degrees of freedom");
+    2.0 * student.sf(t.abs())
+}
+
+/// Adjusts the defined p-values for their number; `None` stays `None`
+fn adjust(p_values: &[Option<f64>], adjustment: Adjustment) -> Vec<Option<f64>> {
+    let mut order: Vec<usize> = (0..p_values.len())
+        .filter(|i| p_values[*i].is_some())
+        .collect();
+    order.sort_by(|a, b| p_values[*a].unwrap().total_cmp(&p_values[*b].unwrap()));
+    let m = order.len() as f64;
+    let mut adjusted = p_values.to_vec();
+    match adjustment {
+        Adjustment::None => {}
+        Adjustment::Holm => {
+            // Running maximum of (m − k) p₍ₖ₎ upwards from the smallest
+            let mut running = 0.0_f64;
+            for (k, &i) in order.iter().enumerate() {
+                running = running.max(((m - k as f64) * p_values[i].unwrap()).min(1.0));
+                adjusted[i] = Some(running);
+            }
+        }
+        Adjustment::BenjaminiHochberg => {
+            // Running minimum of m p₍ₖ₎ / k downwards from the largest
+            let mut running = 1.0_f64;
+            for (k, &i) in order.iter().enumerate().rev() {
+                running = running.min(m * p_values[i].unwrap() / (k + 1) as f64);
+                adjusted[i] = Some(running);
+            }
+        }
+    }
+    adjusted
+}
@@ -0,0 +1,559 @@
+//! Linear regression of denning day-of-year
+//!
+//! Ordinary least squares, or feasible GLS that weights each study by the
+//! inverse of its residual variance, with optional study fixed effects and
+//! heteroskedasticity-robust (HC0–HC3) standard errors. A `LinearFit`
+//! carries residual diagnostics, prints as a coefficient table, exports its
+//! coefficients and residuals as `Table`s and plots residuals against
+//! fitted values.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use ndarray::{Array1, Array2, Axis};
+use plotters::prelude::*;
+use serde::Serialize;
+use statrs::distribution::{ChiSquared, ContinuousCDF, FisherSnedecor};
+
+use super::{
+    coefficient_table, collinear, invert_spd, weighted_cross_product, Coefficient, Design,
+};
+use crate::error::WolfDataError;
+use crate::export::{Column, ColumnType, Table, Value};
+use crate::record::WolfRecord;
+
+/// HC2 and HC3 divide by `1 − leverage`, so rows this close to leverage 1
+/// make them undefined
+const LEVERAGE_TOLERANCE: f64 = 1e-8;
+
+/// How the coefficients are estimated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Estimator {
+    #[default]
+    Ols,
+    /// Feasible GLS: an OLS fit estimates one residual variance per study,
+    /// then each row is weighted by the inverse of its study's variance
+    StudyGls,
+}
+
+impl fmt::Display for Estimator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Estimator::Ols => write!(f, "OLS"),
+            Estimator::StudyGls => write!(f, "GLS by study"),
+        }
+    }
+}
+
+/// How the coefficient standard errors are computed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CovarianceKind {
+    /// Assumes a constant residual variance
+    #[default]
+    Classical,
+    /// White's sandwich estimator
+    Hc0,
+    /// HC0 scaled by `n / (n - p)`
+    Hc1,
+    /// Squared residuals divided by `1 - leverage`
+    Hc2,
+    /// Squared residuals divided by `(1 - leverage)²`; the safest choice
+    /// for small samples
+    Hc3,
+}
+
+impl fmt::Display for CovarianceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CovarianceKind::Classical => write!(f, "classical"),
+            CovarianceKind::Hc0 => write!(f, "HC0"),
+            CovarianceKind::Hc1 => write!(f, "HC1"),
+            CovarianceKind::Hc2 => write!(f, "HC2"),
+            CovarianceKind::Hc3 => write!(f, "HC3"),
+        }
+    }
+}
+
+/// Settings for a linear fit.
+///
+/// `denning_regression` uses `LinearOptions::default()`: OLS with classical
+/// standard errors and no study effects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearOptions {
+    pub estimator: Estimator,
+    pub covariance: CovarianceKind,
+    /// Adds an intercept shift for every study but the first
+    pub study_effects: bool,
+    /// Coverage of the coefficient intervals
+    pub confidence: f64,
+}
+
+impl Default for LinearOptions {
+    fn default() -> Self {
+        LinearOptions {
+            estimator: Estimator::default(),
+            covariance: CovarianceKind::default(),
+            study_effects: false,
+            confidence: 0.95,
+        }
+    }
+}
+
+impl LinearOptions {
+    /// Fits `response ~ covariates` over the records' complete rows
+    pub fn fit<T: WolfRecord>(
+        &self,
+        records: &[T],
+        response: &str,
+        covariates: &[&str],
+    ) -> Result<LinearFit, WolfDataError> {
+        self.fit_design(Design::from_records(records, response, covariates)?)
+    }
+
+    pub fn fit_design(&self, design: Design) -> Result<LinearFit, WolfDataError> {
+        let design = match self.study_effects {
+            true => design.with_study_effects(),
+            false => design,
+        };
+        let (n, p) = design.x.dim();
+        if n <= p {
+            return Err(WolfDataError::Validation {
+                line: None,
+                column: None,
+                message: format!(
+                    "{} complete rows are too few to estimate {} coefficients",
+                    n, p
+                ),
+            });
+        }
+        let (x, y) = (&design.x, &design.y);
+        let solve = |weights: &Array1<f64>| {
+            least_squares(x, y, weights).ok_or_else(|| collinear(&design.terms))
+        };
+
+        let mut weights = Array1::<f64>::ones(n);
+        let (mut beta, mut inverse) = solve(&weights)?;
+        // One residual variance per study under GLS, one overall under OLS
+        let mut variances = 1;
+        if self.estimator == Estimator::StudyGls {
+            let (gls_weights, studies) = study_weights(&design, &(y - &x.dot(&beta)))?;
+            (weights, variances) = (gls_weights, studies);
+            (beta, inverse) = solve(&weights)?;
+        }
+
+        let fitted = x.dot(&beta);
+        let residuals = y - &fitted;
+        let root_weights = weights.mapv(f64::sqrt);
+        let weighted = &residuals * &root_weights;
+        let df = n - p;
+        let sse = weighted.dot(&weighted);
+        let sigma2 = sse / df as f64;
+        let leverage: Array1<f64> = x
+            .outer_iter()
+            .zip(&weights)
+            .map(|(row, w)| w * row.dot(&inverse.dot(&row)))
+            .collect();
+
+        if matches!(self.covariance, CovarianceKind::Hc2 | CovarianceKind::Hc3) {
+            if let Some(i) = leverage.iter().position(|h| *h > 1.0 - LEVERAGE_TOLERANCE) {
+                return Err(WolfDataError::Validation {
+                    line: None,
+                    column: None,
+                    message: format!(
+                        "row {} has leverage 1, so {} standard errors are undefined",
+                        design.rows[i], self.covariance
+                    ),
+                });
+            }
+        }
+        let covariance = match self.covariance {
+            CovarianceKind::Classical => &inverse * sigma2,
+            kind => {
+                let scale: Array1<f64> = leverage
+                    .iter()
+                    .map(|h| match kind {
+                        CovarianceKind::Hc1 => n as f64 / df as f64,
+                        CovarianceKind::Hc2 => 1.0 / (1.0 - h),
+                        CovarianceKind::Hc3 => 1.0 / (1.0 - h).powi(2),
+                        _ => 1.0,
+                    })
+                    .collect();
+                let factor = &weights * &residuals * &scale.mapv(f64::sqrt);
+                let scores = x * &factor.view().insert_axis(Axis(1));
+                inverse.dot(&scores.t().dot(&scores)).dot(&inverse)
+            }
+        };
+        let coefficients = design
+            .terms
+            .iter()
+            .enumerate()
+            .map(|(j, term)| {
+                let se = covariance[[j, j]].sqrt();
+                Coefficient::student(term, beta[j], se, df, self.confidence)
+            })
+            .collect();
+
+        let mean = weights.dot(y) / weights.sum();
+        let sst: f64 = y
+            .iter()
+            .zip(&weights)
+            .map(|(y, w)| w * (y - mean).powi(2))
+            .sum();
+        let r_squared = 1.0 - sse / sst;
+        let (f_statistic, f_p_value) = match p {
+            1 => (None, None),
+            _ => {
+                let f = ((sst - sse) / (p - 1) as f64) / sigma2;
+                let distribution = FisherSnedecor::new((p - 1) as f64, df as f64)
+                    .expect("positive degrees of freedom");
+                (Some(f), Some(distribution.sf(f)))
+            }
+        };
+        let log_likelihood = -(n as f64) / 2.0
+            * ((2.0 * std::f64::consts::PI).ln() + (sse / n as f64).ln() + 1.0)
+            + weights.mapv(f64::ln).sum() / 2.0;
+
+        let cooks_distance: Vec<f64> = weighted
+            .iter()
+            .zip(&leverage)
+            .map(|(e, h)| e * e * h / (p as f64 * sigma2 * (1.0 - h).powi(2)))
+            .collect();
+        let diagnostics = ResidualDiagnostics::new(x, &weighted, &leverage, &cooks_distance);
+
+        Ok(LinearFit {
+            response: design.response,
+            estimator: self.estimator,
+            covariance_kind: self.covariance,
+            coefficients,
+            covariance,
+            n,
+            dropped: design.dropped,
+            df_residual: df,
+            sigma: sigma2.sqrt(),
+            r_squared,
+            adj_r_squared: 1.0 - (1.0 - r_squared) * (n - 1) as f64 / df as f64,
+            f_statistic,
+            f_p_value,
+            log_likelihood,
+            aic: -2.0 * log_likelihood + 2.0 * (p + variances) as f64,
+            confidence: self.confidence,
+            fitted: fitted.to_vec(),
+            residuals: residuals.to_vec(),
+            leverage: leverage.to_vec(),
+            cooks_distance,
+            weights: weights.to_vec(),
+            rows: design.rows,
+            diagnostics,
+        })
+    }
+}
+
+/// Fits `denning_doy ~ covariates` by OLS with classical standard errors
+pub fn denning_regression<T: WolfRecord>(
+    records: &[T],
+    covariates: &[&str],
+) -> Result<LinearFit, WolfDataError> {
+    LinearOptions::default().fit(records, "denning_doy", covariates)
+}
+
+/// Checks of the model's assumptions on the (weighted) residuals
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResidualDiagnostics {
+    pub mean: f64,
+    pub sd: f64,
+    pub skewness: f64,
+    pub excess_kurtosis: f64,
+    /// Jarque–Bera test of normal residuals
+    pub jarque_bera: f64,
+    pub jarque_bera_p: f64,
+    /// Koenker's studentized Breusch–Pagan test of constant variance;
+    /// `None` for an intercept-only model
+    pub breusch_pagan: Option<f64>,
+    pub breusch_pagan_p: Option<f64>,
+    /// Durbin–Watson statistic in row order; near 2 without
+    /// autocorrelation when rows are sorted by pack and year
+    pub durbin_watson: f64,
+    pub max_leverage: f64,
+    pub max_cooks_distance: f64,
+    /// Positions in `LinearFit::rows` with Cook's distance above `4 / n`
+    pub influential: Vec<usize>,
+}
+
+impl ResidualDiagnostics {
+    fn new(
+        x: &Array2<f64>,
+        residuals: &Array1<f64>,
+        leverage: &Array1<f64>,
+        cooks: &[f64],
+    ) -> Self {
+        let n = residuals.len() as f64;
+        let mean = residuals.sum() / n;
+        let moment = |k: i32| residuals.iter().map(|e| (e - mean).powi(k)).sum::<f64>() / n;
+        let variance = moment(2);
+        let skewness = moment(3) / variance.powf(1.5);
+        let excess_kurtosis = moment(4) / variance.powi(2) - 3.0;
+        let jarque_bera = n / 6.0 * (skewness.powi(2) + excess_kurtosis.powi(2) / 4.0);
+
+        let p = x.ncols();
+        let squared = residuals.mapv(|e| e * e);
+        let breusch_pagan = (p > 1)
+            .then(|| least_squares(x, &squared, &Array1::ones(squared.len())))
+            .flatten()
+            .map(|(beta, _)| {
+                let explained = x.dot(&beta);
+                let centre = squared.sum() / n;
+                let ssr: f64 = explained.iter().map(|v| (v - centre).powi(2)).sum();
+                let sst: f64 = squared.iter().map(|v| (v - centre).powi(2)).sum();
+                n * ssr / sst
+            });
+        let breusch_pagan_p = breusch_pagan.map(|lm| chi_squared_sf(lm, (p - 1) as f64));
+
+        let durbin_watson = residuals
+            .windows(2)
+            .into_iter()
+            .map(|pair| (pair[1] - pair[0]).powi(2))
+            .sum::<f64>()
+            / residuals.dot(residuals);
+        ResidualDiagnostics {
+            mean,
+            sd: (variance * n / (n - 1.0)).sqrt(),
+            skewness,
+            excess_kurtosis,
+            jarque_bera,
+            jarque_bera_p: chi_squared_sf(jarque_bera, 2.0),
+            breusch_pagan,
+            breusch_pagan_p,
+            durbin_watson,
+            max_leverage: leverage.iter().copied().fold(0.0, f64::max),
+            max_cooks_distance: cooks.iter().copied().fold(0.0, f64::max),
+            influential: (0..cooks.len()).filter(|&i| cooks[i] > 4.0 / n).collect(),
+        }
+    }
+}
+
+/// A fitted linear model
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LinearFit {
+    pub response: String,
+    pub estimator: Estimator,
+    pub covariance_kind: CovarianceKind,
+    /// Intercept first, then covariates, then any study effects
+    pub coefficients: Vec<Coefficient>,
+    #[serde(skip)]
+    pub covariance: Array2<f64>,
+    /// Rows used in the fit
+    pub n: usize,
+    /// Records left out because a value was missing
+    pub dropped: usize,
+    pub df_residual: usize,
+    /// Residual standard error
+    pub sigma: f64,
+    pub r_squared: f64,
+    pub adj_r_squared: f64,
+    /// Overall F test against the intercept-only model
+    pub f_statistic: Option<f64>,
+    pub f_p_value: Option<f64>,
+    pub log_likelihood: f64,
+    pub aic: f64,
+    pub confidence: f64,
+    /// Per row used, in `rows` order
+    pub fitted: Vec<f64>,
+    pub residuals: Vec<f64>,
+    pub leverage: Vec<f64>,
+    pub cooks_distance: Vec<f64>,
+    /// 1 for OLS, the inverse study variance for GLS
+    pub weights: Vec<f64>,
+    /// Position in the input records of each row used
+    pub rows: Vec<usize>,
+    pub diagnostics: ResidualDiagnostics,
+}
+
+impl LinearFit {
+    pub fn coefficient(&self, term: &str) -> Option<&Coefficient> {
+        self.coefficients.iter().find(|c| c.term == term)
+    }
+
+    /// Predicted response for each record, `None` where a covariate is
+    /// missing. A study the model has no effect for gets the reference
+    /// study's intercept.
+    pub fn predict<T: WolfRecord>(&self, records: &[T]) -> Vec<Option<f64>> {
+        let terms: Vec<String> = self.coefficients.iter().map(|c| c.term.clone()).collect();
+        let beta: Array1<f64> = self.coefficients.iter().map(|c| c.estimate).collect();
+        records
+            .iter()
+            .map(|record| Design::row_of(&terms, record).map(|row| row.dot(&beta)))
+            .collect()
+    }
+
+    pub fn coefficient_table(&self) -> Table {
+        coefficient_table(&self.coefficients, "t", &[])
+    }
+
+    /// One row per observation: its position in the input records, fitted
+    /// value, residual, leverage and Cook's distance
+    pub fn residual_table(&self) -> Table {
+        let mut table = Table::new(vec![
+            Column::new("row", ColumnType::Integer),
+            Column::new("fitted", ColumnType::Float),
+            Column::new("residual", ColumnType::Float),
+            Column::new("leverage", ColumnType::Float),
+            Column::new("cooks_distance", ColumnType::Float),
+        ]);
+        for i in 0..self.n {
+            let row = vec![
+                Value::Integer(self.rows[i] as i64),
+                Value::Float(self.fitted[i]),
+                Value::Float(self.residuals[i]),
+                Value::Float(self.leverage[i]),
+                Value::Float(self.cooks_distance[i]),
+            ];
+            table.push_row(row).expect("one value per column");
+        }
+        table
+    }
+
+    /// Draws residuals against fitted values to a PNG file
+    pub fn plot_residuals(&self, path: impl AsRef<Path>) -> Result<(), WolfDataError> {
+        let path = path.as_ref();
+        let range = |values: &[f64]| {
+            let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let pad = ((max - min) * 0.05).max(1.0);
+            (min - pad)..(max + pad)
+        };
+        let root = BitMapBackend::new(path, (800, 500)).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let mut chart = ChartBuilder::on(&root)
+            .caption(
+                format!("Residuals vs fitted {}", self.response),
+                ("sans-serif", 22),
+            )
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(50)
+            .build_cartesian_2d(range(&self.fitted), range(&self.residuals))?;
+        chart
+            .configure_mesh()
+            .x_desc(format!("Fitted {}", self.response))
+            .y_desc("Residual")
+            .draw()?;
+
+        let x_range = chart.x_range();
+        chart.draw_series(LineSeries::new(
+            [(x_range.start, 0.0), (x_range.end, 0.0)],
+            &BLACK,
+        ))?;
+        chart.draw_series(
+            self.fitted
+                .iter()
+                .zip(&self.residuals)
+                .map(|(x, y)| Circle::new((*x, *y), 3, BLUE.filled())),
+        )?;
+        root.present()?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for LinearFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Linear regression of {} ({}, {} standard errors; n = {}, {} dropped)",
+            self.response, self.estimator, self.covariance_kind, self.n, self.dropped
+        )?;
+        writeln!(f, "{}", self.coefficient_table())?;
+        write!(
+            f,
+            "Residual SE {:.3} on {} df; R² {:.3} (adjusted {:.3})",
+            self.sigma, self.df_residual, self.r_squared, self.adj_r_squared
+        )?;
+        if let (Some(statistic), Some(p)) = (self.f_statistic, self.f_p_value) {
+            write!(
+                f,
+                "; F {:.2} on {} and {} df, p = {:.4}",
+                statistic,
+                self.coefficients.len() - 1,
+                self.df_residual,
+                p
+            )?;
+        }
+        let d = &self.diagnostics;
+        write!(
+            f,
+            "\nResiduals: skewness {:.2}, excess kurtosis {:.2}, Jarque–Bera p = {:.4}",
+            d.skewness, d.excess_kurtosis, d.jarque_bera_p
+        )?;
+        if let Some(p) = d.breusch_pagan_p {
+            write!(f, ", Breusch–Pagan p = {:.4}", p)?;
+        }
+        write!(
+            f,
+            ", Durbin–Watson {:.2}, {} influential rows",
+            d.durbin_watson,
+            d.influential.len()
+        )
+    }
+}
+
+/// Coefficients and `(XᵀWX)⁻¹` of a weighted least-squares fit, `None` if
+/// the columns of `x` are collinear
+fn least_squares(
+    x: &Array2<f64>,
+    y: &Array1<f64>,
+    weights: &Array1<f64>,
+) -> Option<(Array1<f64>, Array2<f64>)> {
+    let inverse = invert_spd(&weighted_cross_product(x, weights))?;
+    let beta = inverse.dot(&x.t().dot(&(weights * y)));
+    Some((beta, inverse))
+}
+
+/// Inverse residual variance of each row's study, and the number of
+/// studies. A study whose residuals are all (close to) zero keeps the pooled
+/// variance.
+fn study_weights(
+    design: &Design,
+    residuals: &Array1<f64>,
+) -> Result<(Array1<f64>, usize), WolfDataError> {
+    let mut sums: HashMap<&str, (f64, usize)> = HashMap::new();
+    for (group, e) in design.groups.iter().zip(residuals) {
+        let entry = sums.entry(group.study.as_str()).or_default();
+        entry.0 += e * e;
+        entry.1 += 1;
+    }
+    let n = residuals.len() as f64;
+    let pooled = residuals.dot(residuals) / n;
+    // Variances this small relative to the response are rounding error
+    let floor = f64::EPSILON * design.y.dot(&design.y) / n;
+    if pooled <= floor {
+        let study = design.groups.first().map_or("", |g| g.study.as_str());
+        return Err(WolfDataError::Validation {
+            line: None,
+            column: None,
+            message: format!(
+                "the OLS fit is exact, so study {} and every other study have no \
+                 residual variance to weight by",
+                study
+            ),
+        });
+    }
+    let weights = design
+        .groups
+        .iter()
+        .map(|group| {
+            let (sum, count) = sums[group.study.as_str()];
+            let variance = sum / count as f64;
+            1.0 / if variance > floor { variance } else { pooled }
+        })
+        .collect();
+    Ok((weights, sums.len()))
+}
+
+fn chi_squared_sf(statistic: f64, df: f64) -> f64 {
+    ChiSquared::new(df)
+        .expect("positive degrees of freedom")
+        .sf(statistic)
+}
@@ -0,0 +1,241 @@
+//! Logistic regression fit by iteratively reweighted least squares
+//!
+//! Models the probability that a 0/1 response (reproductive `success`) is
+//! 1 as `1 / (1 + exp(-Xβ))`. Coefficients are log odds ratios per unit of
+//! their covariate; `LogisticFit::odds_ratios` puts them on the odds scale.
+
+use std::fmt;
+
+use ndarray::{Array1, Array2};
+use serde::Serialize;
+
+use super::{
+    check_binary_response, coefficient_table, collinear, inverse_logit, invert_spd,
+    weighted_cross_product, Coefficient, Design,
+};
+use crate::error::WolfDataError;
+use crate::export::Table;
+use crate::record::WolfRecord;
+
+/// Settings for the IRLS fit.
+///
+/// `logistic_regression` uses `LogisticOptions::default()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogisticOptions {
+    pub max_iterations: usize,
+    /// Stop once the relative change in deviance falls below this
+    pub tolerance: f64,
+    /// Coverage of the coefficient and odds-ratio intervals
+    pub confidence: f64,
+}
+
+impl Default for LogisticOptions {
+    fn default() -> Self {
+        LogisticOptions {
+            max_iterations: 25,
+            tolerance: 1e-8,
+            confidence: 0.95,
+        }
+    }
+}
+
+impl LogisticOptions {
+    /// Fits `response ~ covariates` over the records' complete rows
+    pub fn fit<T: WolfRecord>(
+        &self,
+        records: &[T],
+        response: &str,
+        covariates: &[&str],
+    ) -> Result<LogisticFit, WolfDataError> {
+        self.fit_design(Design::from_records(records, response, covariates)?)
+    }
+
+    pub fn fit_design(&self, design: Design) -> Result<LogisticFit, WolfDataError> {
+        check_binary_response(&design)?;
+
+        let (x, y) = (&design.x, &design.y);
+        let mut beta = Array1::<f64>::zeros(x.ncols());
+        let mut deviance = f64::INFINITY;
+        let mut iterations = 0;
+        let mut converged = false;
+        while iterations < self.max_iterations {
+            iterations += 1;
+            let eta = x.dot(&beta);
+            let mu = eta.mapv(inverse_logit);
+            let weights = &mu * &(1.0 - &mu);
+            let working = &eta + &((y - &mu) / &weights);
+            let information = weighted_cross_product(x, &weights);
+            let covariance = invert_spd(&information).ok_or_else(|| collinear(&design.terms))?;
+            beta = covariance.dot(&x.t().dot(&(&weights * &working)));
+
+            let next = binomial_deviance(y, &x.dot(&beta).mapv(inverse_logit));
+            let change = (next - deviance).abs() / (next.abs() + 0.1);
+            deviance = next;
+            if change < self.tolerance {
+                converged = true;
+                break;
+            }
+        }
+
+        let fitted = x.dot(&beta).mapv(inverse_logit);
+        let weights = &fitted * &(1.0 - &fitted);
+        let covariance = invert_spd(&weighted_cross_product(x, &weights))
+            .ok_or_else(|| collinear(&design.terms))?;
+        let coefficients = design
+            .terms
+            .iter()
+            .enumerate()
+            .map(|(j, term)| {
+                Coefficient::normal(term, beta[j], covariance[[j, j]].sqrt(), self.confidence)
+            })
+            .collect();
+
+        let n = design.len();
+        let mean = y.sum() / n as f64;
+        let null_deviance = binomial_deviance(y, &Array1::from_elem(n, mean));
+        Ok(LogisticFit {
+            response: design.response,
+            coefficients,
+            covariance,
+            n,
+            dropped: design.dropped,
+            iterations,
+            converged,
+            log_likelihood: -deviance / 2.0,
+            deviance,
+            null_deviance,
+            aic: deviance + 2.0 * design.terms.len() as f64,
+            confidence: self.confidence,
+            fitted: fitted.to_vec(),
+            rows: design.rows,
+        })
+    }
+}
+
+/// Fits `success ~ covariates` with the default settings
+pub fn logistic_regression<T: WolfRecord>(
+    records: &[T],
+    covariates: &[&str],
+) -> Result<LogisticFit, WolfDataError> {
+    LogisticOptions::default().fit(records, "success", covariates)
+}
+
+/// Odds ratio of one coefficient with its confidence interval
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OddsRatio {
+    pub term: String,
+    pub odds_ratio: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/// A fitted logistic regression
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogisticFit {
+    pub response: String,
+    /// On the log-odds scale, intercept first
+    pub coefficients: Vec<Coefficient>,
+    #[serde(skip)]
+    pub covariance: Array2<f64>,
+    /// Rows used in the fit
+    pub n: usize,
+    /// Records left out because a value was missing
+    pub dropped: usize,
+    pub iterations: usize,
+    /// False if the deviance was still changing after `max_iterations`,
+    /// usually because a covariate separates successes from failures
+    pub converged: bool,
+    pub log_likelihood: f64,
+    pub deviance: f64,
+    /// Deviance of the intercept-only model
+    pub null_deviance: f64,
+    pub aic: f64,
+    pub confidence: f64,
+    /// Fitted probability of each row used, in `rows` order
+    pub fitted: Vec<f64>,
+    /// Position in the input records of each row used
+    pub rows: Vec<usize>,
+}
+
+impl LogisticFit {
+    pub fn coefficient(&self, term: &str) -> Option<&Coefficient> {
+        self.coefficients.iter().find(|c| c.term == term)
+    }
+
+    pub fn odds_ratios(&self) -> Vec<OddsRatio> {
+        self.coefficients
+            .iter()
+            .map(|c| OddsRatio {
+                term: c.term.clone(),
+                odds_ratio: c.estimate.exp(),
+                lower: c.lower.exp(),
+                upper: c.upper.exp(),
+            })
+            .collect()
+    }
+
+    /// Residual degrees of freedom
+    pub fn df_residual(&self) -> usize {
+        self.n - self.coefficients.len()
+    }
+
+    /// Predicted probability for each record, `None` where a covariate is
+    /// missing
+    pub fn predict<T: WolfRecord>(&self, records: &[T]) -> Vec<Option<f64>> {
+        let terms: Vec<String> = self.coefficients.iter().map(|c| c.term.clone()).collect();
+        let beta: Array1<f64> = self.coefficients.iter().map(|c| c.estimate).collect();
+        records
+            .iter()
+            .map(|record| Design::row_of(&terms, record).map(|row| inverse_logit(row.dot(&beta))))
+            .collect()
+    }
+
+    /// Coefficients with their odds ratios, one row per term
+    pub fn coefficient_table(&self) -> Table {
+        let odds_ratios = self.odds_ratios();
+        let column = |f: fn(&OddsRatio) -> f64| odds_ratios.iter().map(f).collect();
+        coefficient_table(
+            &self.coefficients,
+            "z",
+            &[
+                ("odds_ratio", column(|or| or.odds_ratio)),
+                ("or_lower", column(|or| or.lower)),
+                ("or_upper", column(|or| or.upper)),
+            ],
+        )
+    }
+}
+
+impl fmt::Display for LogisticFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Logistic regression of {} (n = {}, {} dropped{})",
+            self.response,
+            self.n,
+            self.dropped,
+            if self.converged {
+                ""
+            } else {
+                ", not converged"
+            }
+        )?;
+        writeln!(f, "{}", self.coefficient_table())?;
+        write!(
+            f,
+            "Deviance {:.2} on {} df (null {:.2}), AIC {:.2}",
+            self.deviance,
+            self.df_residual(),
+            self.null_deviance,
+            self.aic
+        )
+    }
+}
+
+fn binomial_deviance(y: &Array1<f64>, mu: &Array1<f64>) -> f64 {
+    -2.0 * y
+        .iter()
+        .zip(mu)
+        .map(|(y, mu)| y * mu.ln() + (1.0 - y) * (1.0 - mu).ln())
+        .sum::<f64>()
+}
@@ -0,0 +1,665 @@
+//! Mixed-effects models with random intercepts for study and pack
+//!
+//! Rows of the same study, and of the same pack within a study, share a
+//! random intercept, so repeated seasons of one pack are not treated as
+//! independent. Linear models are fit by REML (or ML) with the residual
+//! variance profiled out; logistic models by penalized quasi-likelihood,
+//! which refits a weighted linear mixed model to the IRLS working response
+//! until the linear predictor settles.
+//!
+//! The covariance of a study's rows is
+//! `σ²_e W⁻¹ + σ²_pack Σ 1ₖ1ₖᵀ + σ²_study 11ᵀ`, inverted in closed form one
+//! pack and then one study at a time, so a fit never builds an `n × n`
+//! matrix.
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::fmt;
+
+use ndarray::{Array1, Array2, ArrayView1};
+use serde::Serialize;
+
+use super::{
+    check_binary_response, coefficient_table, collinear, inverse_logit, invert_spd, log_det_spd,
+    Coefficient, Design,
+};
+use crate::data::PackKey;
+use crate::error::WolfDataError;
+use crate::export::{Column, ColumnType, Table, Value};
+use crate::record::WolfRecord;
+
+/// Log variance ratios are searched within these bounds; the lower one is
+/// effectively a zero variance
+const LOG_RATIO_BOUNDS: (f64, f64) = (-15.0, 10.0);
+
+/// Distribution of the response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Family {
+    /// Linear mixed model
+    Gaussian,
+    /// Logistic mixed model of a 0/1 response
+    Binomial,
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Family::Gaussian => write!(f, "Linear"),
+            Family::Binomial => write!(f, "Logistic"),
+        }
+    }
+}
+
+/// Settings for a mixed-model fit.
+///
+/// `linear_mixed` and `logistic_mixed` use `MixedOptions::default()`:
+/// REML with random intercepts for study and for pack within study.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixedOptions {
+    /// Adds a random intercept per pack within each study
+    pub pack_effects: bool,
+    /// Restricted maximum likelihood; plain ML when false
+    pub reml: bool,
+    /// Outer iterations of the logistic (PQL) fit
+    pub max_iterations: usize,
+    /// Convergence threshold on the variance search and on the largest
+    /// change of the PQL linear predictor
+    pub tolerance: f64,
+    /// Coverage of the fixed-effect intervals
+    pub confidence: f64,
+}
+
+impl Default for MixedOptions {
+    fn default() -> Self {
+        MixedOptions {
+            pack_effects: true,
+            reml: true,
+            max_iterations: 50,
+            tolerance: 1e-6,
+            confidence: 0.95,
+        }
+    }
+}
+
+impl MixedOptions {
+    /// Fits a linear mixed model of `response ~ covariates`
+    pub fn fit_linear<T: WolfRecord>(
+        &self,
+        records: &[T],
+        response: &str,
+        covariates: &[&str],
+    ) -> Result<MixedFit, WolfDataError> {
+        let design = Design::from_records(records, response, covariates)?;
+        let groups = Groups::new(&design.groups);
+        let weights = Array1::ones(design.len());
+        let (solution, ratios, converged) =
+            self.estimate(Family::Gaussian, &design, &design.y, &weights, &groups)?;
+
+        let (n, p) = design.x.dim();
+        let df = if self.reml { n - p } else { n } as f64;
+        let scale = solution.rhr / df;
+        let criterion = self.criterion(&solution, n, p, Family::Gaussian);
+        let log_likelihood = -0.5 * (criterion + df * (1.0 + (2.0 * PI).ln()));
+        let parameters = p + 2 + usize::from(self.pack_effects);
+
+        let variance = VarianceComponents {
+            study: scale * ratios.0,
+            pack: scale * ratios.1,
+            residual: scale,
+        };
+        Ok(self.finish(
+            Family::Gaussian,
+            design,
+            &groups,
+            &solution,
+            ratios,
+            scale,
+            variance,
+            Some(log_likelihood),
+            Some(-2.0 * log_likelihood + 2.0 * parameters as f64),
+            1,
+            converged,
+        ))
+    }
+
+    /// Fits a logistic mixed model of a 0/1 `response ~ covariates` by
+    /// penalized quasi-likelihood
+    pub fn fit_logistic<T: WolfRecord>(
+        &self,
+        records: &[T],
+        response: &str,
+        covariates: &[&str],
+    ) -> Result<MixedFit, WolfDataError> {
+        let design = Design::from_records(records, response, covariates)?;
+        check_binary_response(&design)?;
+        let groups = Groups::new(&design.groups);
+
+        // Start from μ = (y + ½) / 2, as glm does for binomial responses
+        let mut eta = design.y.mapv(|y| ((y + 0.5) / (1.5 - y)).ln());
+        let mut iterations = 0;
+        let mut converged = false;
+        let mut fit = None;
+        while iterations < self.max_iterations {
+            iterations += 1;
+            let mu = eta.mapv(inverse_logit);
+            let weights = &mu * &(1.0 - &mu);
+            let working = &eta + &((&design.y - &mu) / &weights);
+            let (solution, ratios, search_converged) =
+                self.estimate(Family::Binomial, &design, &working, &weights, &groups)?;
+
+            let next = design.x.dot(&solution.beta)
+                + groups.random_effects(&solution.h_inv_r, ratios).row_values;
+            let change = (&next - &eta)
+                .iter()
+                .fold(0.0_f64, |max, d| max.max(d.abs()));
+            eta = next;
+            fit = Some((solution, ratios));
+            if change < self.tolerance.sqrt() {
+                converged = search_converged;
+                break;
+            }
+        }
+
+        let (solution, ratios) = fit.expect("at least one iteration");
+        // On the latent logistic scale the residual variance is π²/3
+        let variance = VarianceComponents {
+            study: ratios.0,
+            pack: ratios.1,
+            residual: PI * PI / 3.0,
+        };
+        Ok(self.finish(
+            Family::Binomial,
+            design,
+            &groups,
+            &solution,
+            ratios,
+            1.0,
+            variance,
+            None,
+            None,
+            iterations,
+            converged,
+        ))
+    }
+
+    /// Searches the variance ratios for the best REML/ML criterion of the
+    /// weighted model `y ~ X` with unit residual scale
+    fn estimate(
+        &self,
+        family: Family,
+        design: &Design,
+        y: &Array1<f64>,
+        weights: &Array1<f64>,
+        groups: &Groups,
+    ) -> Result<(Solution, (f64, f64), bool), WolfDataError> {
+        let (n, p) = design.x.dim();
+        let ratios = |theta: &[f64]| {
+            let ratio = |t: f64| t.clamp(LOG_RATIO_BOUNDS.0, LOG_RATIO_BOUNDS.1).exp();
+            let pack = match self.pack_effects {
+                true => ratio(theta[1]),
+                false => 0.0,
+            };
+            (ratio(theta[0]), pack)
+        };
+        let objective = |theta: &[f64]| {
+            solve(&design.x, y, weights, groups, ratios(theta))
+                .map_or(f64::INFINITY, |s| self.criterion(&s, n, p, family))
+        };
+
+        let start = vec![0.0; 1 + usize::from(self.pack_effects)];
+        let (theta, converged) = nelder_mead(objective, &start, self.tolerance, 400);
+        let ratios = ratios(&theta);
+        let solution =
+            solve(&design.x, y, weights, groups, ratios).ok_or_else(|| collinear(&design.terms))?;
+        Ok((solution, ratios, converged))
+    }
+
+    /// −2 × log-likelihood up to a constant; the Gaussian residual scale is
+    /// profiled out, the binomial one is fixed at 1
+    fn criterion(&self, s: &Solution, n: usize, p: usize, family: Family) -> f64 {
+        let reml = if self.reml { s.log_det_xhx } else { 0.0 };
+        let df = if self.reml { n - p } else { n } as f64;
+        match family {
+            Family::Gaussian => df * (s.rhr / df).ln() + s.log_det_h + reml,
+            Family::Binomial => s.rhr + s.log_det_h + reml,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn finish(
+        &self,
+        family: Family,
+        design: Design,
+        groups: &Groups,
+        solution: &Solution,
+        ratios: (f64, f64),
+        scale: f64,
+        variance: VarianceComponents,
+        log_likelihood: Option<f64>,
+        aic: Option<f64>,
+        iterations: usize,
+        converged: bool,
+    ) -> MixedFit {
+        let covariance = &solution.xhx_inv * scale;
+        let coefficients = design
+            .terms
+            .iter()
+            .enumerate()
+            .map(|(j, term)| {
+                let se = covariance[[j, j]].sqrt();
+                Coefficient::normal(term, solution.beta[j], se, self.confidence)
+            })
+            .collect();
+
+        let effects = groups.random_effects(&solution.h_inv_r, ratios);
+        let linear = design.x.dot(&solution.beta) + &effects.row_values;
+        let fitted = match family {
+            Family::Gaussian => linear.to_vec(),
+            Family::Binomial => linear.iter().map(|eta| inverse_logit(*eta)).collect(),
+        };
+        let total = variance.study + variance.pack + variance.residual;
+        MixedFit {
+            family,
+            response: design.response,
+            reml: self.reml,
+            coefficients,
+            covariance,
+            icc_study: variance.study / total,
+            icc_pack: (variance.study + variance.pack) / total,
+            variance,
+            n: design.rows.len(),
+            dropped: design.dropped,
+            studies: groups.studies.len(),
+            packs: groups.studies.iter().map(|(_, packs)| packs.len()).sum(),
+            log_likelihood,
+            aic,
+            iterations,
+            converged,
+            study_effects: effects.studies,
+            pack_effects: effects.packs,
+            fitted,
+            rows: design.rows,
+        }
+    }
+}
+
+/// Fits a linear mixed model with the default settings
+pub fn linear_mixed<T: WolfRecord>(
+    records: &[T],
+    response: &str,
+    covariates: &[&str],
+) -> Result<MixedFit, WolfDataError> {
+    MixedOptions::default().fit_linear(records, response, covariates)
+}
+
+/// Fits a logistic mixed model of `success ~ covariates` with the default
+/// settings
+pub fn logistic_mixed<T: WolfRecord>(
+    records: &[T],
+    covariates: &[&str],
+) -> Result<MixedFit, WolfDataError> {
+    MixedOptions::default().fit_logistic(records, "success", covariates)
+}
+
+/// Variances of the random intercepts and of the residual. For a logistic
+/// model they are on the latent log-odds scale.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VarianceComponents {
+    pub study: f64,
+    pub pack: f64,
+    pub residual: f64,
+}
+
+/// A fitted mixed-effects model
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MixedFit {
+    pub family: Family,
+    pub response: String,
+    pub reml: bool,
+    /// Fixed effects with Wald tests, intercept first; log odds for a
+    /// logistic model
+    pub coefficients: Vec<Coefficient>,
+    #[serde(skip)]
+    pub covariance: Array2<f64>,
+    pub variance: VarianceComponents,
+    /// Share of the total variance between studies
+    pub icc_study: f64,
+    /// Share of the total variance shared by seasons of the same pack,
+    /// which includes the study variance
+    pub icc_pack: f64,
+    /// Rows used in the fit
+    pub n: usize,
+    /// Records left out because a value was missing
+    pub dropped: usize,
+    pub studies: usize,
+    pub packs: usize,
+    /// REML or ML log-likelihood of a linear model; PQL has none
+    pub log_likelihood: Option<f64>,
+    pub aic: Option<f64>,
+    /// PQL iterations; 1 for a linear model
+    pub iterations: usize,
+    pub converged: bool,
+    /// Predicted random intercept of each study
+    pub study_effects: BTreeMap<String, f64>,
+    /// Predicted random intercept of each pack, on top of its study's
+    #[serde(skip)]
+    pub pack_effects: BTreeMap<PackKey, f64>,
+    /// Conditional fitted value of each row used, on the response scale
+    pub fitted: Vec<f64>,
+    /// Position in the input records of each row used
+    pub rows: Vec<usize>,
+}
+
+impl MixedFit {
+    pub fn coefficient(&self, term: &str) -> Option<&Coefficient> {
+        self.coefficients.iter().find(|c| c.term == term)
+    }
+
+    /// Population-level predictions from the fixed effects alone, on the
+    /// response scale; `None` where a covariate is missing
+    pub fn predict<T: WolfRecord>(&self, records: &[T]) -> Vec<Option<f64>> {
+        self.predictions(records, false)
+    }
+
+    /// Predictions including the record's study and pack intercepts. A
+    /// study or pack the model has not seen contributes 0.
+    pub fn predict_conditional<T: WolfRecord>(&self, records: &[T]) -> Vec<Option<f64>> {
+        self.predictions(records, true)
+    }
+
+    fn predictions<T: WolfRecord>(&self, records: &[T], conditional: bool) -> Vec<Option<f64>> {
+        let terms: Vec<String> = self.coefficients.iter().map(|c| c.term.clone()).collect();
+        let beta: Array1<f64> = self.coefficients.iter().map(|c| c.estimate).collect();
+        records
+            .iter()
+            .map(|record| {
+                let row = Design::row_of(&terms, record)?;
+                let mut eta = row.dot(&beta);
+                if conditional {
+                    eta += self.study_effects.get(record.study()).unwrap_or(&0.0);
+                    eta += self.pack_effects.get(&record.pack_key()).unwrap_or(&0.0);
+                }
+                Some(match self.family {
+                    Family::Gaussian => eta,
+                    Family::Binomial => inverse_logit(eta),
+                })
+            })
+            .collect()
+    }
+
+    pub fn coefficient_table(&self) -> Table {
+        coefficient_table(&self.coefficients, "z", &[])
+    }
+
+    /// One row per variance component with its standard deviation
+    pub fn variance_table(&self) -> Table {
+        let mut table = Table::new(vec![
+            Column::new("group", ColumnType::Text),
+            Column::new("variance", ColumnType::Float),
+            Column::new("sd", ColumnType::Float),
+        ]);
+        let v = &self.variance;
+        for (group, variance) in [
+            ("study", v.study),
+            ("pack:study", v.pack),
+            ("residual", v.residual),
+        ] {
+            let row = vec![
+                Value::Text(group.to_string()),
+                Value::Float(variance),
+                Value::Float(variance.sqrt()),
+            ];
+            table.push_row(row).expect("one value per column");
+        }
+        table
+    }
+}
+
+impl fmt::Display for MixedFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let method = match (self.family, self.reml) {
+            (Family::Binomial, true) => "PQL, REML",
+            (Family::Binomial, false) => "PQL, ML",
+            (Family::Gaussian, true) => "REML",
+            (Family::Gaussian, false) => "ML",
+        };
+        writeln!(
+            f,
+            "{} mixed model of {} ({}; n = {}, {} dropped; {} studies, {} packs{})",
+            self.family,
+            self.response,
+            method,
+            self.n,
+            self.dropped,
+            self.studies,
+            self.packs,
+            if self.converged {
+                ""
+            } else {
+                ", not converged"
+            }
+        )?;
+        writeln!(f, "{}", self.coefficient_table())?;
+        writeln!(f, "Random effects:\n{}", self.variance_table())?;
+        write!(
+            f,
+            "ICC study {:.3}, same pack (incl. study) {:.3}",
+            self.icc_study, self.icc_pack
+        )?;
+        if let (Some(log_likelihood), Some(aic)) = (self.log_likelihood, self.aic) {
+            write!(f, "; log-likelihood {:.2}, AIC {:.2}", log_likelihood, aic)?;
+        }
+        Ok(())
+    }
+}
+
+/// Row positions of each pack of one study
+type Packs = Vec<(PackKey, Vec<usize>)>;
+
+/// Row positions of each pack, grouped by study
+struct Groups {
+    studies: Vec<(String, Packs)>,
+}
+
+/// Predicted random intercepts
+struct RandomEffects {
+    studies: BTreeMap<String, f64>,
+    packs: BTreeMap<PackKey, f64>,
+    /// Study plus pack intercept of each row
+    row_values: Array1<f64>,
+}
+
+impl Groups {
+    fn new(keys: &[PackKey]) -> Self {
+        let mut studies: BTreeMap<&str, BTreeMap<&PackKey, Vec<usize>>> = BTreeMap::new();
+        for (i, key) in keys.iter().enumerate() {
+            studies
+                .entry(key.study.as_str())
+                .or_default()
+                .entry(key)
+                .or_default()
+                .push(i);
+        }
+        Groups {
+            studies: studies
+                .into_iter()
+                .map(|(study, packs)| {
+                    let packs = packs.into_iter().map(|(k, rows)| (k.clone(), rows));
+                    (study.to_string(), packs.collect())
+                })
+                .collect(),
+        }
+    }
+
+    /// `H⁻¹ v` for `H = W⁻¹ + γ_pack Σ 1ₖ1ₖᵀ + γ_study 11ᵀ`, block by block
+    /// with the Sherman–Morrison formula. Also returns `log |H|`.
+    fn apply_inverse(
+        &self,
+        v: ArrayView1<f64>,
+        weights: &Array1<f64>,
+        (study, pack): (f64, f64),
+    ) -> (Array1<f64>, f64) {
+        let mut out = Array1::<f64>::zeros(v.len());
+        let mut log_det = -weights.mapv(f64::ln).sum();
+        for (_, packs) in &self.studies {
+            // A = W⁻¹ + γ_pack Σ 1ₖ1ₖᵀ; A⁻¹v and A⁻¹1 pack by pack
+            let mut a_inv_v = Vec::new();
+            let mut a_inv_1 = Vec::new();
+            for (_, rows) in packs {
+                let w_sum: f64 = rows.iter().map(|&i| weights[i]).sum();
+                let wv_sum: f64 = rows.iter().map(|&i| weights[i] * v[i]).sum();
+                let shrink = pack / (1.0 + pack * w_sum);
+                log_det += (1.0 + pack * w_sum).ln();
+                for &i in rows {
+                    a_inv_v.push((i, weights[i] * v[i] - weights[i] * shrink * wv_sum));
+                    a_inv_1.push(weights[i] - weights[i] * shrink * w_sum);
+                }
+            }
+            let one_a_one: f64 = a_inv_1.iter().sum();
+            let one_a_v: f64 = a_inv_v.iter().map(|(_, x)| x).sum();
+            let shrink = study / (1.0 + study * one_a_one);
+            log_det += (1.0 + study * one_a_one).ln();
+            for ((i, x), u) in a_inv_v.into_iter().zip(a_inv_1) {
+                out[i] = x - shrink * u * one_a_v;
+            }
+        }
+        (out, log_det)
+    }
+
+    /// Best linear unbiased predictions of the study and pack intercepts
+    /// (in units of the residual scale) from `H⁻¹ r`
+    fn random_effects(&self, h_inv_r: &Array1<f64>, (study, pack): (f64, f64)) -> RandomEffects {
+        let mut effects = RandomEffects {
+            studies: BTreeMap::new(),
+            packs: BTreeMap::new(),
+            row_values: Array1::zeros(h_inv_r.len()),
+        };
+        for (name, packs) in &self.studies {
+            let rows = packs.iter().flat_map(|(_, rows)| rows);
+            let b_study = study * rows.clone().map(|&i| h_inv_r[i]).sum::<f64>();
+            effects.studies.insert(name.clone(), b_study);
+            for (key, rows) in packs {
+                let b_pack = pack * rows.iter().map(|&i| h_inv_r[i]).sum::<f64>();
+                effects.packs.insert(key.clone(), b_pack);
+                for &i in rows {
+                    effects.row_values[i] = b_study + b_pack;
+                }
+            }
+        }
+        effects
+    }
+}
+
+/// Generalized least squares under `H` for fixed variance ratios
+struct Solution {
+    beta: Array1<f64>,
+    xhx_inv: Array2<f64>,
+    h_inv_r: Array1<f64>,
+    /// `rᵀ H⁻¹ r`
+    rhr: f64,
+    log_det_h: f64,
+    log_det_xhx: f64,
+}
+
+fn solve(
+    x: &Array2<f64>,
+    y: &Array1<f64>,
+    weights: &Array1<f64>,
+    groups: &Groups,
+    ratios: (f64, f64),
+) -> Option<Solution> {
+    let (n, p) = x.dim();
+    let mut h_inv_x = Array2::<f64>::zeros((n, p));
+    for j in 0..p {
+        let (column, _) = groups.apply_inverse(x.column(j), weights, ratios);
+        h_inv_x.column_mut(j).assign(&column);
+    }
+    let (h_inv_y, log_det_h) = groups.apply_inverse(y.view(), weights, ratios);
+    let xhx = x.t().dot(&h_inv_x);
+    let xhx_inv = invert_spd(&xhx)?;
+    let beta = xhx_inv.dot(&x.t().dot(&h_inv_y));
+    let h_inv_r = &h_inv_y - &h_inv_x.dot(&beta);
+    let rhr = (y - &x.dot(&beta)).dot(&h_inv_r);
+    Some(Solution {
+        log_det_xhx: log_det_spd(&xhx)?,
+        beta,
+        xhx_inv,
+        h_inv_r,
+        rhr,
+        log_det_h,
+    })
+}
+
+/// Minimizes `f` by the Nelder–Mead simplex method, returning the best
+/// point and whether the simplex shrank below `tolerance`
+fn nelder_mead<F: Fn(&[f64]) -> f64>(
+    f: F,
+    start: &[f64],
+    tolerance: f64,
+    max_evaluations: usize,
+) -> (Vec<f64>, bool) {
+    let d = start.len();
+    let mut simplex: Vec<(Vec<f64>, f64)> = (0..=d)
+        .map(|k| {
+            let mut point = start.to_vec();
+            if k > 0 {
+                point[k - 1] += 1.0;
+            }
+            let value = f(&point);
+            (point, value)
+        })
+        .collect();
+    let mut evaluations = d + 1;
+    let blend = |a: &[f64], b: &[f64], t: f64| -> Vec<f64> {
+        a.iter().zip(b).map(|(a, b)| a + t * (b - a)).collect()
+    };
+
+    while evaluations < max_evaluations {
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let spread = simplex[d].1 - simplex[0].1;
+        let size = simplex[1..]
+            .iter()
+            .flat_map(|(p, _)| p.iter().zip(&simplex[0].0).map(|(a, b)| (a - b).abs()))
+            .fold(0.0, f64::max);
+        if spread.abs() < tolerance && size < tolerance.sqrt() {
+            return (simplex.swap_remove(0).0, true);
+        }
+
+        let centroid: Vec<f64> = (0..d)
+            .map(|j| simplex[..d].iter().map(|(p, _)| p[j]).sum::<f64>() / d as f64)
+            .collect();
+        let worst = simplex[d].clone();
+        let reflected = blend(&centroid, &worst.0, -1.0);
+        let reflected_value = f(&reflected);
+        evaluations += 1;
+
+        if reflected_value < simplex[0].1 {
+            let expanded = blend(&centroid, &worst.0, -2.0);
+            let expanded_value = f(&expanded);
+            evaluations += 1;
+            simplex[d] = match expanded_value < reflected_value {
+                true => (expanded, expanded_value),
+                false => (reflected, reflected_value),
+            };
+        } else if reflected_value < simplex[d - 1].1 {
+            simplex[d] = (reflected, reflected_value);
+        } else {
+            let contracted = blend(&centroid, &worst.0, 0.5);
+            let contracted_value = f(&contracted);
+            evaluations += 1;
+            if contracted_value < worst.1 {
+                simplex[d] = (contracted, contracted_value);
+            } else {
+                let best = simplex[0].0.clone();
+                for (point, value) in simplex.iter_mut().skip(1) {
+                    *point = blend(&best, point, 0.5);
+                    *value = f(point);
+                }
+                evaluations += d;
+            }
+        }
+    }
+    simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+    (simplex.swap_remove(0).0, false)
+}
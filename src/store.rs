@@ -0,0 +1,592 @@
+//! Persistent SQLite record store (the default `store` feature)
+//!
+//! A `RecordStore` keeps validated denning and reproductive records in a
+//! local database file, together with where each one came from: the source
+//! file's path and SHA-256 hash and the record's line in that file. New
+//! field seasons are added by ingesting another CSV; a file whose hash was
+//! already ingested is skipped, and a record whose `uid` is already stored
+//! with different values is a conflict, handled by `ConflictPolicy`.
+//!
+//! ```no_run
+//! # use wolf_project_210::store::RecordStore;
+//! let mut store = RecordStore::open("wolves.db")?;
+//! let report = store.ingest_denning("data/Wolf_DenningPhenology_AK_CA.csv")?;
+//! println!("{}", report);
+//! let dataset = store.snapshot()?;
+//! # Ok::<(), wolf_project_210::error::WolfDataError>(())
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use crate::data::{DenningPhenology, LoadOptions, LoadReport, ReproductiveSuccess};
+use crate::dataset::WolfDataset;
+use crate::error::WolfDataError;
+use crate::export::{write_csv, Table, Tabular, Value};
+use crate::missing::MissingPolicy;
+use crate::sql::{coerce, from_sql, quote, sql_type};
+use crate::validation::{Validated, ValidationReport, Validator};
+
+/// Which record table a source file feeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecordKind {
+    Denning,
+    Reproductive,
+}
+
+impl RecordKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            RecordKind::Denning => "denning",
+            RecordKind::Reproductive => "reproductive",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "denning" => Some(RecordKind::Denning),
+            "reproductive" => Some(RecordKind::Reproductive),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RecordKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// What to do with an incoming record whose `uid` is already stored with
+/// different values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Abort the ingest without writing anything
+    #[default]
+    Reject,
+    /// Keep the stored record and skip the incoming one
+    Skip,
+    /// Overwrite the stored record and its provenance
+    Replace,
+}
+
+/// Settings for ingesting a CSV file.
+///
+/// The `ingest_*` methods on `RecordStore` use `IngestOptions::default()`:
+/// the default loader settings and `ConflictPolicy::Reject`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IngestOptions {
+    pub load: LoadOptions,
+    pub on_conflict: ConflictPolicy,
+}
+
+impl IngestOptions {
+    pub fn ingest_denning(
+        &self,
+        store: &mut RecordStore,
+        path: impl AsRef<Path>,
+    ) -> Result<IngestReport, WolfDataError> {
+        store.ingest::<DenningPhenology>(path.as_ref(), self)
+    }
+
+    pub fn ingest_reproductive(
+        &self,
+        store: &mut RecordStore,
+        path: impl AsRef<Path>,
+    ) -> Result<IngestReport, WolfDataError> {
+        store.ingest::<ReproductiveSuccess>(path.as_ref(), self)
+    }
+}
+
+/// An ingested source file
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    pub id: i64,
+    pub path: String,
+    /// Hex SHA-256 of the file's bytes
+    pub sha256: String,
+    pub kind: RecordKind,
+    /// RFC 3339 UTC timestamp
+    pub ingested_at: String,
+    /// Records written from this file, including ones later replaced
+    pub stored_rows: usize,
+}
+
+impl fmt::Display for SourceFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} records, sha256 {}, ingested {})",
+            self.path,
+            self.kind,
+            &self.sha256[..12.min(self.sha256.len())],
+            self.ingested_at
+        )
+    }
+}
+
+/// Where a stored record came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub source: SourceFile,
+    /// 1-based line in the source file
+    pub line: u64,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` line {}", self.source.path, self.line)
+    }
+}
+
+/// An incoming record whose `uid` is stored with different values
+#[derive(Debug, Clone, PartialEq)]
+pub struct UidConflict {
+    pub uid: u32,
+    /// Line of the incoming record
+    pub line: u64,
+    pub existing: Provenance,
+}
+
+impl fmt::Display for UidConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: uid {} is already stored from {} with different values",
+            self.line, self.uid, self.existing
+        )
+    }
+}
+
+/// What an ingest did
+#[derive(Debug, Clone)]
+pub struct IngestReport {
+    pub source: SourceFile,
+    /// The file's hash was already ingested, so nothing was read or written
+    pub already_ingested: bool,
+    pub load: LoadReport,
+    /// Records with error-level findings are not stored
+    pub validation: ValidationReport,
+    pub inserted: usize,
+    /// Records identical to one already stored
+    pub unchanged: usize,
+    pub replaced: usize,
+    /// Conflicting records that were skipped or replaced
+    pub conflicts: Vec<UidConflict>,
+}
+
+impl fmt::Display for IngestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.already_ingested {
+            return write!(f, "{} already ingested", self.source);
+        }
+        write!(
+            f,
+            "{}: {} inserted, {} unchanged, {} replaced, {} conflicts, {} failed validation",
+            self.source.path,
+            self.inserted,
+            self.unchanged,
+            self.replaced,
+            self.conflicts.len(),
+            self.validation.failing_rows().len()
+        )
+    }
+}
+
+/// Record types the store has a table for
+trait Stored: Tabular + Validated {
+    const KIND: RecordKind;
+    const TABLE: &'static str;
+
+    #[allow(clippy::type_complexity)]
+    fn read(
+        options: &LoadOptions,
+        bytes: &[u8],
+    ) -> Result<(Vec<(u64, Self)>, LoadReport), WolfDataError>;
+    fn read_back(bytes: &[u8]) -> Result<Vec<Self>, WolfDataError>;
+}
+
+impl Stored for DenningPhenology {
+    const KIND: RecordKind = RecordKind::Denning;
+    const TABLE: &'static str = "denning";
+
+    fn read(
+        options: &LoadOptions,
+        bytes: &[u8],
+    ) -> Result<(Vec<(u64, Self)>, LoadReport), WolfDataError> {
+        options.read_denning_with_lines(bytes)
+    }
+
+    fn read_back(bytes: &[u8]) -> Result<Vec<Self>, WolfDataError> {
+        let (records, _) = stored_load_options().read_denning(bytes)?;
+        Ok(records)
+    }
+}
+
+impl Stored for ReproductiveSuccess {
+    const KIND: RecordKind = RecordKind::Reproductive;
+    const TABLE: &'static str = "reproduction";
+
+    fn read(
+        options: &LoadOptions,
+        bytes: &[u8],
+    ) -> Result<(Vec<(u64, Self)>, LoadReport), WolfDataError> {
+        options.read_reproductive_with_lines(bytes)
+    }
+
+    fn read_back(bytes: &[u8]) -> Result<Vec<Self>, WolfDataError> {
+        let (records, _) = stored_load_options().read_reproductive(bytes)?;
+        Ok(records)
+    }
+}
+
+/// Stored records already passed the loader, so missing values read back
+/// as `None` rather than dropping the row
+fn stored_load_options() -> LoadOptions {
+    LoadOptions::with_missing_policy(MissingPolicy::KeepNone)
+}
+
+/// Hex SHA-256 of a byte string
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// A SQLite database of ingested records and their sources
+pub struct RecordStore {
+    connection: Connection,
+}
+
+impl RecordStore {
+    /// Opens or creates a store at `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, WolfDataError> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// A store that lives only as long as the value
+    pub fn open_in_memory() -> Result<Self, WolfDataError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(connection: Connection) -> Result<Self, WolfDataError> {
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sources (
+                id INTEGER PRIMARY KEY,
+                path TEXT NOT NULL,
+                sha256 TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                ingested_at TEXT NOT NULL,
+                stored_rows INTEGER NOT NULL,
+                UNIQUE (sha256, kind)
+            );",
+        )?;
+        let store = RecordStore { connection };
+        store.create_table::<DenningPhenology>()?;
+        store.create_table::<ReproductiveSuccess>()?;
+        Ok(store)
+    }
+
+    fn create_table<T: Stored>(&self) -> Result<(), WolfDataError> {
+        let columns: Vec<String> = T::columns()
+            .iter()
+            .map(|c| format!("{} {}", quote(&c.name), sql_type(c.kind)))
+            .collect();
+        self.connection.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} ({}, \
+             source_id INTEGER NOT NULL REFERENCES sources (id), \
+             source_line INTEGER NOT NULL, \
+             PRIMARY KEY (\"uid\"));",
+            quote(T::TABLE),
+            columns.join(", ")
+        ))?;
+        Ok(())
+    }
+
+    /// Ingests a denning CSV with the default `IngestOptions`
+    pub fn ingest_denning(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<IngestReport, WolfDataError> {
+        IngestOptions::default().ingest_denning(self, path)
+    }
+
+    /// Ingests a reproductive CSV with the default `IngestOptions`
+    pub fn ingest_reproductive(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<IngestReport, WolfDataError> {
+        IngestOptions::default().ingest_reproductive(self, path)
+    }
+
+    fn ingest<T: Stored>(
+        &mut self,
+        path: &Path,
+        options: &IngestOptions,
+    ) -> Result<IngestReport, WolfDataError> {
+        let bytes = fs::read(path).map_err(|e| WolfDataError::io(path, e))?;
+        let sha256 = sha256_hex(&bytes);
+        if let Some(source) = self.source_by_hash(&sha256, T::KIND)? {
+            return Ok(IngestReport {
+                source,
+                already_ingested: true,
+                load: LoadReport::default(),
+                validation: ValidationReport::default(),
+                inserted: 0,
+                unchanged: 0,
+                replaced: 0,
+                conflicts: Vec::new(),
+            });
+        }
+
+        let (located, load) = T::read(&options.load, &bytes)?;
+        let (lines, records): (Vec<u64>, Vec<T>) = located.into_iter().unzip();
+        let validation = Validator::builtin().validate(&records);
+        let failing = validation.failing_rows();
+        let table = Table::from_records(&records)?;
+
+        // Sort every valid row into insert, unchanged or conflict before writing
+        let mut inserts = Vec::new();
+        let mut unchanged = 0;
+        let mut conflicts = Vec::new();
+        let mut conflicting_rows = Vec::new();
+        for (row, record) in records.iter().enumerate() {
+            if failing.contains(&row) {
+                continue;
+            }
+            match self.stored_row::<T>(record.uid())? {
+                None => inserts.push(row),
+                Some((values, _)) if values == table.rows()[row] => unchanged += 1,
+                Some((_, existing)) => {
+                    conflicts.push(UidConflict {
+                        uid: record.uid(),
+                        line: lines[row],
+                        existing,
+                    });
+                    conflicting_rows.push(row);
+                }
+            }
+        }
+        if options.on_conflict == ConflictPolicy::Reject && !conflicts.is_empty() {
+            let more = match conflicts.len() {
+                1 => String::new(),
+                n => format!(" (and {} more conflicts)", n - 1),
+            };
+            return Err(WolfDataError::Validation {
+                line: Some(conflicts[0].line),
+                column: Some("uid".to_string()),
+                message: format!(
+                    "uid {} is already stored from {} with different values{}",
+                    conflicts[0].uid, conflicts[0].existing, more
+                ),
+            });
+        }
+        let replaced = match options.on_conflict {
+            ConflictPolicy::Replace => conflicting_rows.len(),
+            _ => 0,
+        };
+
+        let path_text = path.display().to_string();
+        let ingested_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        let stored_rows = inserts.len() + replaced;
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO sources (path, sha256, kind, ingested_at, stored_rows) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                path_text,
+                sha256,
+                T::KIND.as_str(),
+                ingested_at,
+                stored_rows as i64
+            ],
+        )?;
+        let source_id = transaction.last_insert_rowid();
+        {
+            let placeholders = vec!["?"; table.columns().len() + 2].join(", ");
+            let mut insert = transaction.prepare(&format!(
+                "INSERT OR REPLACE INTO {} VALUES ({})",
+                quote(T::TABLE),
+                placeholders
+            ))?;
+            let replacing: &[usize] = match options.on_conflict {
+                ConflictPolicy::Replace => &conflicting_rows,
+                _ => &[],
+            };
+            for &row in inserts.iter().chain(replacing) {
+                let provenance = [Value::Integer(source_id), Value::Integer(lines[row] as i64)];
+                let values = table.rows()[row].iter().chain(&provenance);
+                insert.execute(rusqlite::params_from_iter(values))?;
+            }
+        }
+        transaction.commit()?;
+
+        Ok(IngestReport {
+            source: SourceFile {
+                id: source_id,
+                path: path_text,
+                sha256,
+                kind: T::KIND,
+                ingested_at,
+                stored_rows,
+            },
+            already_ingested: false,
+            load,
+            validation,
+            inserted: inserts.len(),
+            unchanged,
+            replaced,
+            conflicts,
+        })
+    }
+
+    /// The stored row for `uid` in export column order, and its provenance
+    fn stored_row<T: Stored>(
+        &self,
+        uid: u32,
+    ) -> Result<Option<(Vec<Value>, Provenance)>, WolfDataError> {
+        let columns = T::columns();
+        let names: Vec<String> = columns.iter().map(|c| quote(&c.name)).collect();
+        let mut statement = self.connection.prepare_cached(&format!(
+            "SELECT {}, source_id, source_line FROM {} WHERE \"uid\" = ?1",
+            names.join(", "),
+            quote(T::TABLE)
+        ))?;
+        let found = statement
+            .query_row([uid], |row| {
+                let values = columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| row.get_ref(i).map(|v| coerce(from_sql(v), c.kind)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let source_id: i64 = row.get(columns.len())?;
+                let line: i64 = row.get(columns.len() + 1)?;
+                Ok((values, source_id, line as u64))
+            })
+            .optional()?;
+        match found {
+            None => Ok(None),
+            Some((values, source_id, line)) => {
+                let source = self.source(source_id)?;
+                Ok(Some((values, Provenance { source, line })))
+            }
+        }
+    }
+
+    fn source(&self, id: i64) -> Result<SourceFile, WolfDataError> {
+        let sources = self.query_sources("WHERE id = ?1", params![id])?;
+        sources
+            .into_iter()
+            .next()
+            .ok_or_else(|| WolfDataError::Validation {
+                line: None,
+                column: None,
+                message: format!("store has no source with id {}", id),
+            })
+    }
+
+    fn source_by_hash(
+        &self,
+        sha256: &str,
+        kind: RecordKind,
+    ) -> Result<Option<SourceFile>, WolfDataError> {
+        let sources = self.query_sources(
+            "WHERE sha256 = ?1 AND kind = ?2",
+            params![sha256, kind.as_str()],
+        )?;
+        Ok(sources.into_iter().next())
+    }
+
+    fn query_sources(
+        &self,
+        filter: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<SourceFile>, WolfDataError> {
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT id, path, sha256, kind, ingested_at, stored_rows FROM sources {} ORDER BY id",
+            filter
+        ))?;
+        let mut rows = statement.query(params)?;
+        let mut sources = Vec::new();
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            let kind: String = row.get(3)?;
+            let stored_rows: i64 = row.get(5)?;
+            sources.push(SourceFile {
+                id,
+                path: row.get(1)?,
+                sha256: row.get(2)?,
+                kind: RecordKind::parse(&kind).ok_or_else(|| WolfDataError::Validation {
+                    line: None,
+                    column: Some("kind".to_string()),
+                    message: format!("source {} has unknown record kind {:?}", id, kind),
+                })?,
+                ingested_at: row.get(4)?,
+                stored_rows: stored_rows as usize,
+            });
+        }
+        Ok(sources)
+    }
+
+    /// Every ingested file, oldest first
+    pub fn sources(&self) -> Result<Vec<SourceFile>, WolfDataError> {
+        self.query_sources("", [])
+    }
+
+    /// Where the stored record with this `uid` came from
+    pub fn provenance(
+        &self,
+        kind: RecordKind,
+        uid: u32,
+    ) -> Result<Option<Provenance>, WolfDataError> {
+        let found = match kind {
+            RecordKind::Denning => self.stored_row::<DenningPhenology>(uid)?,
+            RecordKind::Reproductive => self.stored_row::<ReproductiveSuccess>(uid)?,
+        };
+        Ok(found.map(|(_, provenance)| provenance))
+    }
+
+    /// Stored denning records, in ingest order
+    pub fn denning(&self) -> Result<Vec<DenningPhenology>, WolfDataError> {
+        self.records()
+    }
+
+    /// Stored reproductive records, in ingest order
+    pub fn reproduction(&self) -> Result<Vec<ReproductiveSuccess>, WolfDataError> {
+        self.records()
+    }
+
+    /// The current contents of the store as an in-memory dataset
+    pub fn snapshot(&self) -> Result<WolfDataset, WolfDataError> {
+        Ok(WolfDataset::new(self.denning()?, self.reproduction()?))
+    }
+
+    /// Reads a record table back through the CSV loader, so stored records
+    /// are parsed and unit-checked exactly like freshly loaded ones
+    fn records<T: Stored>(&self) -> Result<Vec<T>, WolfDataError> {
+        let columns = T::columns();
+        let names: Vec<String> = columns.iter().map(|c| quote(&c.name)).collect();
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT {} FROM {} ORDER BY source_id, source_line",
+            names.join(", "),
+            quote(T::TABLE)
+        ))?;
+        let mut table = Table::new(columns.clone());
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let values = columns
+                .iter()
+                .enumerate()
+                .map(|(i, c)| row.get_ref(i).map(|v| coerce(from_sql(v), c.kind)))
+                .collect::<Result<Vec<_>, _>>()?;
+            table.push_row(values)?;
+        }
+
+        let mut csv = Vec::new();
+        write_csv(&table, &mut csv)?;
+        T::read_back(&csv)
+    }
+}
@@ -0,0 +1,305 @@
+//! Registry of study areas
+//!
+//! The `study` column is free text such as "Denali National Park, Alaska,
+//! USA". `StudyRegistry` parses each distinct string once into a
+//! `StudyArea` with an interned `StudyId`, a short code, its jurisdiction
+//! and country, and the centroid of its study coordinates. Extra metadata
+//! can be merged in from a TOML sidecar file:
+//!
+//! ```toml
+//! [studies."DNP-AK"]
+//! protected_status = "National Park and Preserve"
+//! area_km2 = 24585.0
+//! agency = "US National Park Service"
+//! ```
+//!
+//! Sidecar tables may be keyed by the full study name or by its code.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::error::WolfDataError;
+use crate::record::WolfRecord;
+
+/// Interned study identifier, stable for the lifetime of its registry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StudyId(u32);
+
+impl StudyId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Country {
+    UnitedStates,
+    Canada,
+    Unknown,
+}
+
+impl Country {
+    fn parse(text: &str) -> Self {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "usa" | "us" | "united states" => Country::UnitedStates,
+            "ca" | "can" | "canada" => Country::Canada,
+            _ => Country::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Country {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Country::UnitedStates => write!(f, "USA"),
+            Country::Canada => write!(f, "Canada"),
+            Country::Unknown => write!(f, "unknown country"),
+        }
+    }
+}
+
+/// Optional facts about a study area that are not in the ABoVE files
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct StudyMetadata {
+    pub protected_status: Option<String>,
+    pub area_km2: Option<f64>,
+    pub agency: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StudyArea {
+    pub id: StudyId,
+    /// The `study` string as it appears in the data
+    pub name: String,
+    /// Site name without jurisdiction and country
+    pub site: String,
+    /// Initials of the site plus the jurisdiction code, e.g. `DNP-AK`
+    pub code: String,
+    /// US state or Canadian province/territory, e.g. "Alaska" or "Alberta-BC"
+    pub jurisdiction: String,
+    pub country: Country,
+    /// Mean `(latitude, longitude)` of the study coordinates
+    pub centroid: (f64, f64),
+    pub metadata: StudyMetadata,
+    rows: usize,
+}
+
+impl StudyArea {
+    /// Splits "Site, Jurisdiction, Country" into its parts
+    fn parse(id: StudyId, name: &str) -> Self {
+        let mut parts: Vec<&str> = name.split(',').map(str::trim).collect();
+        let country = if parts.len() > 1 {
+            Country::parse(parts.pop().unwrap_or_default())
+        } else {
+            Country::Unknown
+        };
+        let jurisdiction = if parts.len() > 1 {
+            parts.pop().unwrap_or_default().to_string()
+        } else {
+            String::new()
+        };
+        let site = parts.join(", ");
+
+        let initials: String = site
+            .split(|c: char| c.is_whitespace() || c == '-')
+            .filter_map(|word| word.chars().find(char::is_ascii_alphanumeric))
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        let code = match jurisdiction_code(&jurisdiction) {
+            j if j.is_empty() => initials,
+            j => format!("{}-{}", initials, j),
+        };
+
+        StudyArea {
+            id,
+            name: name.to_string(),
+            site,
+            code,
+            jurisdiction,
+            country,
+            centroid: (0.0, 0.0),
+            metadata: StudyMetadata::default(),
+            rows: 0,
+        }
+    }
+
+    /// Number of records seen for this study
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn is_alaska(&self) -> bool {
+        self.jurisdiction == "Alaska"
+    }
+}
+
+impl fmt::Display for StudyArea {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.code)
+    }
+}
+
+/// Postal abbreviation of each part of a jurisdiction, e.g. "Alberta-BC" → "AB-BC"
+fn jurisdiction_code(jurisdiction: &str) -> String {
+    let codes: Vec<String> = jurisdiction
+        .split('-')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let code = match part {
+                "Alaska" => "AK",
+                "Alberta" => "AB",
+                "British Columbia" => "BC",
+                "Manitoba" => "MB",
+                "Northwest Territories" => "NT",
+                "Nunavut" => "NU",
+                "Saskatchewan" => "SK",
+                "Yukon" | "Yukon Territory" => "YT",
+                other => return other.to_string(),
+            };
+            code.to_string()
+        })
+        .collect();
+    codes.join("-")
+}
+
+/// The study areas found in a dataset
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StudyRegistry {
+    areas: Vec<StudyArea>,
+    by_name: HashMap<String, StudyId>,
+}
+
+#[derive(Deserialize)]
+struct MetadataFile {
+    #[serde(default)]
+    studies: BTreeMap<String, StudyMetadata>,
+}
+
+impl StudyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the record's study, updating its centroid, and returns its id
+    pub fn register<T: WolfRecord>(&mut self, record: &T) -> StudyId {
+        let id = self.intern(record.study());
+        let area = &mut self.areas[id.index()];
+        let (latitude, longitude) = record.location();
+        area.rows += 1;
+        let n = area.rows as f64;
+        area.centroid.0 += (latitude - area.centroid.0) / n;
+        area.centroid.1 += (longitude - area.centroid.1) / n;
+        id
+    }
+
+    /// The id for a study string, adding it if it is new
+    pub fn intern(&mut self, name: &str) -> StudyId {
+        if let Some(id) = self.by_name.get(name) {
+            return *id;
+        }
+        let id = StudyId(self.areas.len() as u32);
+        let mut area = StudyArea::parse(id, name);
+        // Keep codes unique if two sites share initials
+        let base = area.code.clone();
+        let mut n = 2;
+        while self.areas.iter().any(|a| a.code == area.code) {
+            area.code = format!("{}{}", base, n);
+            n += 1;
+        }
+        self.areas.push(area);
+        self.by_name.insert(name.to_string(), id);
+        id
+    }
+
+    pub fn id_of(&self, name: &str) -> Option<StudyId> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn get(&self, id: StudyId) -> &StudyArea {
+        &self.areas[id.index()]
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&StudyArea> {
+        self.id_of(name).map(|id| self.get(id))
+    }
+
+    pub fn by_code(&self, code: &str) -> Option<&StudyArea> {
+        self.areas.iter().find(|a| a.code == code)
+    }
+
+    /// The area a record belongs to, if its study is registered
+    pub fn area_of<T: WolfRecord>(&self, record: &T) -> Option<&StudyArea> {
+        self.by_name(record.study())
+    }
+
+    /// Areas in the order they were first seen
+    pub fn areas(&self) -> &[StudyArea] {
+        &self.areas
+    }
+
+    pub fn len(&self) -> usize {
+        self.areas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.areas.is_empty()
+    }
+
+    /// Groups records by study area; records of unregistered studies are skipped
+    pub fn group_by_area<'a, T: WolfRecord + 'a>(
+        &self,
+        records: impl IntoIterator<Item = &'a T>,
+    ) -> BTreeMap<StudyId, Vec<&'a T>> {
+        let mut groups: BTreeMap<StudyId, Vec<&T>> = BTreeMap::new();
+        for record in records {
+            if let Some(id) = self.id_of(record.study()) {
+                groups.entry(id).or_default().push(record);
+            }
+        }
+        groups
+    }
+
+    /// Groups records by the country of their study area
+    pub fn group_by_country<'a, T: WolfRecord + 'a>(
+        &self,
+        records: impl IntoIterator<Item = &'a T>,
+    ) -> BTreeMap<Country, Vec<&'a T>> {
+        let mut groups: BTreeMap<Country, Vec<&T>> = BTreeMap::new();
+        for record in records {
+            let country = self.area_of(record).map_or(Country::Unknown, |a| a.country);
+            groups.entry(country).or_default().push(record);
+        }
+        groups
+    }
+
+    /// Merges sidecar metadata, returning the keys that matched no study
+    pub fn apply_metadata_toml(&mut self, text: &str) -> Result<Vec<String>, WolfDataError> {
+        let file: MetadataFile = toml::from_str(text).map_err(|e| WolfDataError::Validation {
+            line: None,
+            column: None,
+            message: format!("invalid study metadata: {}", e),
+        })?;
+        let mut unmatched = Vec::new();
+        for (key, metadata) in file.studies {
+            let id = self
+                .id_of(&key)
+                .or_else(|| self.by_code(&key).map(|a| a.id));
+            match id {
+                Some(id) => self.areas[id.index()].metadata = metadata,
+                None => unmatched.push(key),
+            }
+        }
+        Ok(unmatched)
+    }
+
+    pub fn load_metadata(&mut self, path: impl AsRef<Path>) -> Result<Vec<String>, WolfDataError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| WolfDataError::io(path, e))?;
+        self.apply_metadata_toml(&text)
+    }
+}
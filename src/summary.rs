@@ -0,0 +1,425 @@
+//! Descriptive statistics of a numeric series
+//!
+//! `Summary` holds the counts, moments, quantiles and bootstrap intervals
+//! of one series; it is plain data, so it can be tested, serialized as
+//! JSON, rendered as a `Table` row or printed. Quantiles interpolate
+//! linearly between order statistics (R's default, type 7).
+//!
+//! ```no_run
+//! # use wolf_project_210::data::read_denning_csv;
+//! # use wolf_project_210::summary::{Grouping, SummaryOptions};
+//! let (denning, _) = read_denning_csv("data/Wolf_DenningPhenology_AK_CA.csv")?;
+//! let by_study = SummaryOptions::default().summarize_by(&denning, "denning_doy", Grouping::Study)?;
+//! println!("{}", by_study);
+//! # Ok::<(), wolf_project_210::error::WolfDataError>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+
+use crate::error::WolfDataError;
+use crate::export::{Column, ColumnType, Table, Value};
+use crate::record::{group_by, WolfRecord};
+use crate::stats::check_numeric;
+
+/// How records are split before summarizing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Grouping {
+    Study,
+    /// Season year
+    Year,
+}
+
+impl Grouping {
+    fn column(self) -> &'static str {
+        match self {
+            Grouping::Study => "study",
+            Grouping::Year => "year",
+        }
+    }
+}
+
+/// Settings for a summary.
+///
+/// `summarize` uses `SummaryOptions::default()`: the 5th to 95th
+/// percentiles and 95% intervals from 1000 bootstrap resamples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryOptions {
+    /// Percentiles to report, between 0 and 100
+    pub percentiles: Vec<f64>,
+    /// Bootstrap resamples for the mean and median intervals; 0 skips them
+    pub bootstrap: usize,
+    /// Coverage of the bootstrap intervals
+    pub confidence: f64,
+    /// Seed of the resampling, so a summary is reproducible
+    pub seed: u64,
+}
+
+impl Default for SummaryOptions {
+    fn default() -> Self {
+        SummaryOptions {
+            percentiles: vec![5.0, 10.0, 25.0, 50.0, 75.0, 90.0, 95.0],
+            bootstrap: 1000,
+            confidence: 0.95,
+            seed: 0,
+        }
+    }
+}
+
+impl SummaryOptions {
+    /// Summarizes a series in which `None` marks a missing value
+    pub fn summarize(&self, values: impl IntoIterator<Item = Option<f64>>) -> Summary {
+        let mut missing = 0;
+        let mut sorted: Vec<f64> = values
+            .into_iter()
+            .filter_map(|v| {
+                missing += usize::from(v.is_none());
+                v
+            })
+            .collect();
+        sorted.sort_by(f64::total_cmp);
+
+        let n = sorted.len();
+        let mean = (n > 0).then(|| sorted.iter().sum::<f64>() / n as f64);
+        let moment = |k: i32| {
+            let mean = mean.unwrap_or_default();
+            sorted.iter().map(|v| (v - mean).powi(k)).sum::<f64>() / n as f64
+        };
+        let sd = (n > 1).then(|| (moment(2) * n as f64 / (n - 1) as f64).sqrt());
+        let skewness = (n > 2 && moment(2) > 0.0).then(|| moment(3) / moment(2).powf(1.5));
+        let quartile = |p: f64| (n > 0).then(|| quantile(&sorted, p));
+        let (q1, q3) = (quartile(0.25), quartile(0.75));
+
+        let (mean_ci, median_ci) = match n > 1 && self.bootstrap > 0 {
+            true => {
+                let (mean, median) = self.bootstrap_intervals(&sorted);
+                (Some(mean), Some(median))
+            }
+            false => (None, None),
+        };
+        Summary {
+            n,
+            missing,
+            mean,
+            sd,
+            se: sd.map(|sd| sd / (n as f64).sqrt()),
+            median: quartile(0.5),
+            q1,
+            q3,
+            iqr: q1.zip(q3).map(|(q1, q3)| q3 - q1),
+            percentiles: match n {
+                0 => Vec::new(),
+                _ => self
+                    .percentiles
+                    .iter()
+                    .map(|&percent| Percentile {
+                        percent,
+                        value: quantile(&sorted, percent / 100.0),
+                    })
+                    .collect(),
+            },
+            skewness,
+            min: sorted.first().copied(),
+            max: sorted.last().copied(),
+            confidence: self.confidence,
+            mean_ci,
+            median_ci,
+        }
+    }
+
+    /// Summarizes a numeric column of `records`, counting records without
+    /// a value as missing
+    pub fn summarize_column<T: WolfRecord>(
+        &self,
+        records: &[T],
+        column: &str,
+    ) -> Result<Summary, WolfDataError> {
+        check_numeric::<T>([&column])?;
+        Ok(self.summarize(records.iter().map(|r| r.covariate(column))))
+    }
+
+    /// Summarizes a numeric column of `records` per study or per year
+    pub fn summarize_by<T: WolfRecord>(
+        &self,
+        records: &[T],
+        column: &str,
+        grouping: Grouping,
+    ) -> Result<GroupedSummary, WolfDataError> {
+        check_numeric::<T>([&column])?;
+        let summarize = |group: Vec<&T>| self.summarize(group.iter().map(|r| r.covariate(column)));
+        let groups = match grouping {
+            Grouping::Study => group_by(records, |r: &T| r.study().to_string())
+                .into_iter()
+                .map(|(study, group)| (study, summarize(group)))
+                .collect(),
+            Grouping::Year => group_by(records, |r: &T| r.season_year())
+                .into_iter()
+                .map(|(year, group)| (year.to_string(), summarize(group)))
+                .collect(),
+        };
+        Ok(GroupedSummary {
+            column: column.to_string(),
+            grouping,
+            overall: self.summarize(records.iter().map(|r| r.covariate(column))),
+            groups,
+        })
+    }
+
+    /// Percentile intervals of the mean and median over resamples of
+    /// `values` drawn with replacement
+    fn bootstrap_intervals(&self, values: &[f64]) -> (Interval, Interval) {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let n = values.len();
+        let mut means = Vec::with_capacity(self.bootstrap);
+        let mut medians = Vec::with_capacity(self.bootstrap);
+        let mut resample = vec![0.0; n];
+        for _ in 0..self.bootstrap {
+            for slot in resample.iter_mut() {
+                *slot = values[rng.gen_range(0..n)];
+            }
+            means.push(resample.iter().sum::<f64>() / n as f64);
+            resample.sort_by(f64::total_cmp);
+            medians.push(quantile(&resample, 0.5));
+        }
+        let tail = (1.0 - self.confidence) / 2.0;
+        let interval = |estimates: &mut Vec<f64>| {
+            estimates.sort_by(f64::total_cmp);
+            Interval {
+                lower: quantile(estimates, tail),
+                upper: quantile(estimates, 1.0 - tail),
+            }
+        };
+        (interval(&mut means), interval(&mut medians))
+    }
+}
+
+/// Summarizes a series with the default settings
+pub fn summarize(values: &[f64]) -> Summary {
+    SummaryOptions::default().summarize(values.iter().copied().map(Some))
+}
+
+/// A confidence interval
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Interval {
+    pub lower: f64,
+    pub upper: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Percentile {
+    /// Between 0 and 100
+    pub percent: f64,
+    pub value: f64,
+}
+
+/// Descriptive statistics of one series. Statistics that need more values
+/// than the series has are `None`: all of them for an empty series, the
+/// spread and intervals for a single value, and the skewness for fewer
+/// than three or constant values.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    /// Values present
+    pub n: usize,
+    pub missing: usize,
+    pub mean: Option<f64>,
+    /// Sample standard deviation, with `n − 1` in the denominator
+    pub sd: Option<f64>,
+    /// Standard error of the mean
+    pub se: Option<f64>,
+    pub median: Option<f64>,
+    pub q1: Option<f64>,
+    pub q3: Option<f64>,
+    pub iqr: Option<f64>,
+    pub percentiles: Vec<Percentile>,
+    /// Moment coefficient of skewness
+    pub skewness: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Coverage of `mean_ci` and `median_ci`
+    pub confidence: f64,
+    /// Bootstrap percentile interval of the mean
+    pub mean_ci: Option<Interval>,
+    /// Bootstrap percentile interval of the median
+    pub median_ci: Option<Interval>,
+}
+
+impl Summary {
+    pub fn percentile(&self, percent: f64) -> Option<f64> {
+        self.percentiles
+            .iter()
+            .find(|p| p.percent == percent)
+            .map(|p| p.value)
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<(), WolfDataError> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// A one-row table of this summary; see `summary_table`
+    pub fn table(&self) -> Table {
+        summary_table("series", [("all", self)])
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (Some(mean), Some(median), Some(min), Some(max)) =
+            (self.mean, self.median, self.min, self.max)
+        else {
+            return write!(f, "  • No data available ({} missing)", self.missing);
+        };
+        let interval = |ci: Option<Interval>| match ci {
+            Some(ci) => format!(
+                "; {:.0}% CI {:.2} to {:.2}",
+                self.confidence * 100.0,
+                ci.lower,
+                ci.upper
+            ),
+            None => String::new(),
+        };
+        writeln!(f, "  • Records: {} ({} missing)", self.n, self.missing)?;
+        write!(f, "  • Mean: {:.2}", mean)?;
+        if let (Some(sd), Some(se)) = (self.sd, self.se) {
+            write!(f, " (SD {:.2}, SE {:.2})", sd, se)?;
+        }
+        writeln!(f, "{}", interval(self.mean_ci))?;
+        writeln!(f, "  • Median: {:.2}{}", median, interval(self.median_ci))?;
+        if let (Some(q1), Some(q3), Some(iqr)) = (self.q1, self.q3, self.iqr) {
+            writeln!(f, "  • IQR: {:.2} ({:.2} to {:.2})", iqr, q1, q3)?;
+        }
+        if let Some(skewness) = self.skewness {
+            writeln!(f, "  • Skewness: {:.2}", skewness)?;
+        }
+        write!(f, "  • Min: {:.2}, Max: {:.2}", min, max)
+    }
+}
+
+/// Summaries of a column per study or per year
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GroupedSummary {
+    pub column: String,
+    pub grouping: Grouping,
+    /// All records together
+    pub overall: Summary,
+    /// Keyed by study name or year
+    pub groups: BTreeMap<String, Summary>,
+}
+
+impl GroupedSummary {
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<(), WolfDataError> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// One row per group, keyed by a `study` or `year` column
+    pub fn table(&self) -> Table {
+        summary_table(
+            self.grouping.column(),
+            self.groups.iter().map(|(key, s)| (key.as_str(), s)),
+        )
+    }
+}
+
+impl fmt::Display for GroupedSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} by {}:", self.column, self.grouping.column())?;
+        write!(f, "{}", self.table())
+    }
+}
+
+/// One row per summary under a text `key` column, then the counts,
+/// moments, quartiles, range, interval bounds and a `p<percent>` column
+/// per percentile of the first summary. Missing statistics are nulls.
+pub fn summary_table<'a>(
+    key: &str,
+    summaries: impl IntoIterator<Item = (&'a str, &'a Summary)>,
+) -> Table {
+    let summaries: Vec<_> = summaries.into_iter().collect();
+    let percentiles: Vec<f64> = summaries
+        .iter()
+        .find(|(_, s)| !s.percentiles.is_empty())
+        .map_or_else(Vec::new, |(_, s)| {
+            s.percentiles.iter().map(|p| p.percent).collect()
+        });
+
+    let names = [
+        "mean",
+        "sd",
+        "se",
+        "median",
+        "q1",
+        "q3",
+        "iqr",
+        "skewness",
+        "min",
+        "max",
+        "mean_lower",
+        "mean_upper",
+        "median_lower",
+        "median_upper",
+    ];
+    let columns = [
+        Column::new(key, ColumnType::Text),
+        Column::new("n", ColumnType::Integer),
+        Column::new("missing", ColumnType::Integer),
+    ]
+    .into_iter()
+    .chain(names.map(|name| Column::new(name, ColumnType::Float)))
+    .chain(
+        percentiles
+            .iter()
+            .map(|p| Column::new(format!("p{}", p), ColumnType::Float)),
+    )
+    .collect();
+
+    let mut table = Table::new(columns);
+    let float = |v: Option<f64>| v.map_or(Value::Null, Value::Float);
+    for (label, s) in summaries {
+        let (mean_ci, median_ci) = (s.mean_ci, s.median_ci);
+        let mut row = vec![
+            Value::Text(label.to_string()),
+            Value::Integer(s.n as i64),
+            Value::Integer(s.missing as i64),
+        ];
+        row.extend(
+            [
+                s.mean,
+                s.sd,
+                s.se,
+                s.median,
+                s.q1,
+                s.q3,
+                s.iqr,
+                s.skewness,
+                s.min,
+                s.max,
+                mean_ci.map(|ci| ci.lower),
+                mean_ci.map(|ci| ci.upper),
+                median_ci.map(|ci| ci.lower),
+                median_ci.map(|ci| ci.upper),
+            ]
+            .map(float),
+        );
+        row.extend(percentiles.iter().map(|&p| float(s.percentile(p))));
+        table.push_row(row).expect("one value per column");
+    }
+    table
+}
+
+/// Quantile `p` of sorted, non-empty `values`, interpolating linearly
+/// between the order statistics around `(n − 1) p`
+fn quantile(values: &[f64], p: f64) -> f64 {
+    let h = (values.len() - 1) as f64 * p.clamp(0.0, 1.0);
+    let below = h.floor() as usize;
+    match values.get(below + 1) {
+        Some(above) => values[below] + (h - below as f64) * (above - values[below]),
+        None => values[below],
+    }
+}
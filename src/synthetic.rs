@@ -0,0 +1,517 @@
+//! Seeded synthetic wolf datasets
+//!
+//! `SyntheticOptions` describes a study design (studies, packs per study,
+//! season years), how each covariate is distributed, how often values go
+//! missing and a known model for denning DOY and reproductive success. The
+//! same options and seed always produce the same records, so tests can
+//! check that an analysis recovers the planted effects.
+//!
+//! Every pack has a denning record in every year. Denning DOY is
+//!
+//! ```text
+//! intercept + study effect + pack effect + Σ β·(x − mean x) + noise
+//! ```
+//!
+//! rounded and clamped to the denning season, and success is a Bernoulli
+//! draw with the same kind of linear predictor on the logit scale. Slopes
+//! act on covariates centred at their distribution mean, so the intercepts
+//! are the mean DOY and the log-odds of success of an average pack.
+
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+use chrono::NaiveDate;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution as _, LogNormal, Normal};
+
+use crate::data::{DenningPhenology, ReproductiveSuccess};
+use crate::dataset::WolfDataset;
+use crate::error::WolfDataError;
+use crate::units::Quantity;
+use crate::validation::DENNING_SEASON;
+
+/// How a covariate is drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distribution {
+    Normal {
+        mean: f64,
+        sd: f64,
+    },
+    /// `exp` of a normal with the given parameters
+    LogNormal {
+        mu: f64,
+        sigma: f64,
+    },
+    Uniform {
+        low: f64,
+        high: f64,
+    },
+}
+
+impl Distribution {
+    pub fn mean(&self) -> f64 {
+        match *self {
+            Distribution::Normal { mean, .. } => mean,
+            Distribution::LogNormal { mu, sigma } => (mu + sigma * sigma / 2.0).exp(),
+            Distribution::Uniform { low, high } => (low + high) / 2.0,
+        }
+    }
+
+    /// Finite parameters, non-negative spreads and a finite mean
+    fn is_valid(&self) -> bool {
+        let (location, spread) = match *self {
+            Distribution::Normal { mean, sd } => (mean, sd),
+            Distribution::LogNormal { mu, sigma } => (mu, sigma),
+            Distribution::Uniform { low, high } => (low, high - low),
+        };
+        location.is_finite() && spread.is_finite() && spread >= 0.0 && self.mean().is_finite()
+    }
+
+    fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        match *self {
+            Distribution::Normal { mean, sd } => normal(rng, mean, sd),
+            Distribution::LogNormal { mu, sigma } => match LogNormal::new(mu, sigma) {
+                Ok(d) => d.sample(rng),
+                Err(_) => mu.exp(),
+            },
+            Distribution::Uniform { low, high } if high > low => rng.gen_range(low..high),
+            Distribution::Uniform { low, .. } => low,
+        }
+    }
+}
+
+/// A linear predictor with random study and pack intercepts
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlantedEffects {
+    pub intercept: f64,
+    /// Slope per unit of each named covariate, centred at its mean.
+    /// Success effects may also use `denning_doy`.
+    pub slopes: Vec<(String, f64)>,
+    /// Standard deviation of the per-study intercepts
+    pub study_sd: f64,
+    /// Standard deviation of the per-pack intercepts within a study
+    pub pack_sd: f64,
+    /// Standard deviation of the per-record noise; unused for success,
+    /// whose noise is the Bernoulli draw
+    pub residual_sd: f64,
+}
+
+impl PlantedEffects {
+    fn slope_names(&self) -> impl Iterator<Item = &str> {
+        self.slopes.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+/// Covariates drawn for each pack-season; shared ones take the same value
+/// in the denning and the reproductive record
+const COVARIATES: &[(&str, Distribution)] = &[
+    ("fall_tmax", Distribution::Normal { mean: 1.3, sd: 3.3 }),
+    (
+        "summer_tmax_prev1",
+        Distribution::Normal {
+            mean: 17.3,
+            sd: 2.8,
+        },
+    ),
+    (
+        "winter_tmax",
+        Distribution::Normal {
+            mean: -7.9,
+            sd: 5.2,
+        },
+    ),
+    (
+        "fall_prcp",
+        Distribution::Normal {
+            mean: 131.0,
+            sd: 68.0,
+        },
+    ),
+    (
+        "summer_prcp_prev1",
+        Distribution::Normal {
+            mean: 252.0,
+            sd: 97.0,
+        },
+    ),
+    (
+        "winter_swe",
+        Distribution::Normal {
+            mean: 90.0,
+            sd: 53.0,
+        },
+    ),
+    (
+        "tiNDVI_prev1",
+        Distribution::Normal {
+            mean: 11.6,
+            sd: 2.5,
+        },
+    ),
+    (
+        "annual_pdo",
+        Distribution::Normal {
+            mean: -0.1,
+            sd: 0.85,
+        },
+    ),
+    ("annual_ao", Distribution::Normal { mean: 0.0, sd: 0.4 }),
+    (
+        "sos_prev1",
+        Distribution::Normal {
+            mean: 138.0,
+            sd: 16.0,
+        },
+    ),
+    (
+        "los_prev1",
+        Distribution::Normal {
+            mean: 18.6,
+            sd: 3.0,
+        },
+    ),
+    (
+        "summer_prcp",
+        Distribution::Normal {
+            mean: 256.0,
+            sd: 95.0,
+        },
+    ),
+    (
+        "summer_tmax",
+        Distribution::Normal {
+            mean: 17.3,
+            sd: 2.9,
+        },
+    ),
+    (
+        "tiNDVI",
+        Distribution::Normal {
+            mean: 12.2,
+            sd: 3.1,
+        },
+    ),
+    (
+        "home_range_area",
+        Distribution::LogNormal {
+            mu: 7.3,
+            sigma: 0.6,
+        },
+    ),
+    (
+        "denning_match_growing_season",
+        Distribution::Normal {
+            mean: -13.8,
+            sd: 20.5,
+        },
+    ),
+];
+
+/// Study sites cycled through by the generator: name suffix and location
+const SITES: &[(&str, f64, f64)] = &[
+    ("Alaska, USA", 63.5, -150.0),
+    ("Yukon, CA", 62.0, -136.0),
+    ("Northwest Territories, CA", 62.5, -115.0),
+    ("Alberta, CA", 54.0, -117.0),
+];
+
+/// Settings for a synthetic dataset. The defaults give 4 studies of 10
+/// packs over 2000–2019 with covariate distributions close to the ABoVE
+/// files, no missing values and a few planted effects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntheticOptions {
+    pub seed: u64,
+    pub studies: usize,
+    pub packs_per_study: u32,
+    pub years: RangeInclusive<i32>,
+    /// Distribution of each covariate; covariates not listed keep their
+    /// default distribution
+    pub covariates: BTreeMap<String, Distribution>,
+    /// Probability that any single covariate value is missing
+    pub missing_rate: f64,
+    /// Probability that a pack-season also has a reproductive record
+    pub reproduction_rate: f64,
+    pub denning_doy: PlantedEffects,
+    /// On the logit scale
+    pub success: PlantedEffects,
+}
+
+impl Default for SyntheticOptions {
+    fn default() -> Self {
+        SyntheticOptions {
+            seed: 0,
+            studies: 4,
+            packs_per_study: 10,
+            years: 2000..=2019,
+            covariates: COVARIATES
+                .iter()
+                .map(|(name, d)| (name.to_string(), *d))
+                .collect(),
+            missing_rate: 0.0,
+            reproduction_rate: 1.0,
+            denning_doy: PlantedEffects {
+                intercept: 123.0,
+                slopes: vec![
+                    ("winter_swe".to_string(), 0.05),
+                    ("winter_tmax".to_string(), -0.8),
+                ],
+                study_sd: 5.0,
+                pack_sd: 2.0,
+                residual_sd: 8.0,
+            },
+            success: PlantedEffects {
+                intercept: 1.0,
+                slopes: vec![
+                    ("denning_doy".to_string(), -0.03),
+                    ("winter_swe".to_string(), -0.005),
+                ],
+                study_sd: 0.3,
+                pack_sd: 0.0,
+                residual_sd: 0.0,
+            },
+        }
+    }
+}
+
+impl SyntheticOptions {
+    /// Default settings with a different seed
+    pub fn with_seed(seed: u64) -> Self {
+        SyntheticOptions {
+            seed,
+            ..SyntheticOptions::default()
+        }
+    }
+
+    /// Generates the denning and reproductive records
+    pub fn generate(
+        &self,
+    ) -> Result<(Vec<DenningPhenology>, Vec<ReproductiveSuccess>), WolfDataError> {
+        self.check_parameters()?;
+        let covariates = self.distributions();
+        if let Some((name, d)) = covariates.iter().find(|(_, d)| !d.is_valid()) {
+            return Err(WolfDataError::Validation {
+                line: None,
+                column: Some(name.clone()),
+                message: format!("invalid covariate distribution {:?}", d),
+            });
+        }
+        let unknown = self
+            .denning_doy
+            .slope_names()
+            .chain(self.success.slope_names())
+            .find(|name| *name != "denning_doy" && !covariates.contains_key(*name));
+        if let Some(name) = unknown {
+            return Err(WolfDataError::Validation {
+                line: None,
+                column: Some(name.to_string()),
+                message: "planted effect on a covariate the generator does not draw".to_string(),
+            });
+        }
+        if self
+            .denning_doy
+            .slope_names()
+            .any(|name| name == "denning_doy")
+        {
+            return Err(WolfDataError::Validation {
+                line: None,
+                column: Some("denning_doy".to_string()),
+                message: "denning DOY cannot depend on itself".to_string(),
+            });
+        }
+
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let mut denning = Vec::new();
+        let mut reproduction = Vec::new();
+        for s in 0..self.studies {
+            let (suffix, latitude, longitude) = SITES[s % SITES.len()];
+            let study = format!("Synthetic Study {}, {}", s + 1, suffix);
+            let latitude = latitude + normal(&mut rng, 0.0, 1.0);
+            let longitude = longitude + normal(&mut rng, 0.0, 2.0);
+            let study_doy = normal(&mut rng, 0.0, self.denning_doy.study_sd);
+            let study_success = normal(&mut rng, 0.0, self.success.study_sd);
+
+            for pack_id in 1..=self.packs_per_study {
+                let pack_doy = study_doy + normal(&mut rng, 0.0, self.denning_doy.pack_sd);
+                let pack_success = study_success + normal(&mut rng, 0.0, self.success.pack_sd);
+                for year in self.years.clone() {
+                    let mut values: BTreeMap<&str, f64> = covariates
+                        .iter()
+                        .map(|(name, d)| (name.as_str(), clip(name, d.sample(&mut rng))))
+                        .collect();
+                    let means = |name: &str| match name {
+                        "denning_doy" => self.denning_doy.intercept,
+                        name => covariates[name].mean(),
+                    };
+                    let predictor = |effects: &PlantedEffects, values: &BTreeMap<&str, f64>| {
+                        effects
+                            .slopes
+                            .iter()
+                            .map(|(name, beta)| beta * (values[name.as_str()] - means(name)))
+                            .sum::<f64>()
+                            + effects.intercept
+                    };
+
+                    let doy = predictor(&self.denning_doy, &values)
+                        + pack_doy
+                        + normal(&mut rng, 0.0, self.denning_doy.residual_sd);
+                    let doy = (doy.round() as i64).clamp(
+                        i64::from(*DENNING_SEASON.start()),
+                        i64::from(*DENNING_SEASON.end()),
+                    ) as u16;
+                    values.insert("denning_doy", f64::from(doy));
+                    let logit = predictor(&self.success, &values) + pack_success;
+                    let success = rng.gen_bool(1.0 / (1.0 + (-logit).exp()));
+                    let date = NaiveDate::from_yo_opt(year, u32::from(doy)).unwrap_or_default();
+
+                    let mut draw =
+                        |name: &str| (!rng.gen_bool(self.missing_rate)).then(|| values[name]);
+                    let uid = denning.len() as u32 + 1;
+                    let record = DenningPhenology {
+                        uid,
+                        study: study.clone(),
+                        longitude_study: longitude,
+                        latitude_study: latitude,
+                        pack_id,
+                        denning_date: date,
+                        denning_doy: doy,
+                        denned: 1,
+                        fall_tmax: quantity(&mut draw, "fall_tmax")?,
+                        summer_tmax_prev1: quantity(&mut draw, "summer_tmax_prev1")?,
+                        winter_tmax: quantity(&mut draw, "winter_tmax")?,
+                        fall_prcp: quantity(&mut draw, "fall_prcp")?,
+                        summer_prcp_prev1: quantity(&mut draw, "summer_prcp_prev1")?,
+                        winter_swe: quantity(&mut draw, "winter_swe")?,
+                        ti_ndvi_prev1: draw("tiNDVI_prev1"),
+                        annual_pdo: draw("annual_pdo"),
+                        annual_ao: draw("annual_ao"),
+                        sos_prev1: draw("sos_prev1"),
+                        los_prev1: draw("los_prev1"),
+                        latitude_individual: latitude + normal(&mut rng, 0.0, 0.1),
+                    };
+                    denning.push(record);
+
+                    if !rng.gen_bool(self.reproduction_rate) {
+                        continue;
+                    }
+                    let mut draw =
+                        |name: &str| (!rng.gen_bool(self.missing_rate)).then(|| values[name]);
+                    let record = ReproductiveSuccess {
+                        uid: reproduction.len() as u32 + 1,
+                        study: study.clone(),
+                        longitude_study: longitude,
+                        latitude_study: latitude,
+                        pack_id,
+                        start_date: date,
+                        end_date: NaiveDate::from_ymd_opt(year, 9, 30).unwrap_or(date),
+                        success: u8::from(success),
+                        summer_prcp: quantity(&mut draw, "summer_prcp")?,
+                        fall_prcp: quantity(&mut draw, "fall_prcp")?,
+                        winter_swe: quantity(&mut draw, "winter_swe")?,
+                        fall_tmax: quantity(&mut draw, "fall_tmax")?,
+                        summer_tmax: quantity(&mut draw, "summer_tmax")?,
+                        winter_tmax: quantity(&mut draw, "winter_tmax")?,
+                        ti_ndvi_prev1: draw("tiNDVI_prev1"),
+                        ti_ndvi: draw("tiNDVI"),
+                        annual_pdo: draw("annual_pdo"),
+                        annual_ao: draw("annual_ao"),
+                        home_range_area: draw("home_range_area"),
+                        denning_match_growing_season: draw("denning_match_growing_season"),
+                    };
+                    reproduction.push(record);
+                }
+            }
+        }
+        Ok((denning, reproduction))
+    }
+
+    /// Generates the records and indexes them
+    pub fn dataset(&self) -> Result<WolfDataset, WolfDataError> {
+        let (denning, reproduction) = self.generate()?;
+        Ok(WolfDataset::new(denning, reproduction))
+    }
+
+    /// Rates must be probabilities and planted effects finite, or the
+    /// Bernoulli draws fail
+    fn check_parameters(&self) -> Result<(), WolfDataError> {
+        let invalid = |message: String| WolfDataError::Validation {
+            line: None,
+            column: None,
+            message,
+        };
+        for (name, rate) in [
+            ("missing_rate", self.missing_rate),
+            ("reproduction_rate", self.reproduction_rate),
+        ] {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(invalid(format!(
+                    "{} must be between 0 and 1, got {}",
+                    name, rate
+                )));
+            }
+        }
+        for (name, effects) in [
+            ("denning_doy", &self.denning_doy),
+            ("success", &self.success),
+        ] {
+            let finite = std::iter::once(effects.intercept)
+                .chain(effects.slopes.iter().map(|(_, beta)| *beta))
+                .all(f64::is_finite);
+            if !finite {
+                return Err(invalid(format!("planted {} effects must be finite", name)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Default distributions overridden by `covariates`
+    fn distributions(&self) -> BTreeMap<String, Distribution> {
+        let mut distributions: BTreeMap<String, Distribution> = COVARIATES
+            .iter()
+            .map(|(name, d)| (name.to_string(), *d))
+            .collect();
+        distributions.extend(self.covariates.iter().map(|(k, v)| (k.clone(), *v)));
+        distributions
+    }
+}
+
+/// A synthetic dataset with the default settings and the given seed
+pub fn synthetic_dataset(seed: u64) -> WolfDataset {
+    SyntheticOptions::with_seed(seed)
+        .dataset()
+        .expect("default planted effects use generated covariates")
+}
+
+fn normal<R: Rng>(rng: &mut R, mean: f64, sd: f64) -> f64 {
+    match Normal::new(mean, sd) {
+        Ok(d) if sd > 0.0 => d.sample(rng),
+        _ => mean,
+    }
+}
+
+/// Precipitation and snow cannot be negative, so low normal draws are
+/// clipped before they enter the planted effects
+fn clip(name: &str, value: f64) -> f64 {
+    match name {
+        "fall_prcp" | "summer_prcp_prev1" | "summer_prcp" | "winter_swe" => value.max(0.0),
+        "fall_tmax" | "summer_tmax_prev1" | "summer_tmax" | "winter_tmax" => value.max(-273.15),
+        _ => value,
+    }
+}
+
+/// Draws a value of `name` and wraps it in its unit. `clip` keeps finite
+/// draws in range, but a wide distribution can still overflow.
+fn quantity<Q: Quantity>(
+    draw: &mut impl FnMut(&str) -> Option<f64>,
+    name: &str,
+) -> Result<Option<Q>, WolfDataError> {
+    draw(name)
+        .map(|v| {
+            Q::new(v).map_err(|e| WolfDataError::Validation {
+                line: None,
+                column: Some(name.to_string()),
+                message: format!("drawn value is out of range: {}", e),
+            })
+        })
+        .transpose()
+}
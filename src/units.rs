@@ -0,0 +1,113 @@
+//! Unit-carrying newtypes for the climate covariates
+//!
+//! The ABoVE exports store temperatures in °C and precipitation and snow
+//! water equivalent in mm, often with fractional values. These types keep
+//! the full `f64` and reject values that cannot be physical instead of
+//! silently casting them.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::error::WolfDataError;
+
+/// A measurement with a unit and a range of valid values. The wrapped
+/// value is private, so every instance has passed `new`.
+pub trait Quantity: Copy + Sized {
+    /// Unit suffix used when printing
+    const UNIT: &'static str;
+
+    /// Checks `value` and wraps it, failing on NaN, infinities or
+    /// out-of-range values
+    fn new(value: f64) -> Result<Self, WolfDataError>;
+
+    fn value(self) -> f64;
+}
+
+/// Rejects values that are not finite or fall below `min`
+fn checked(value: f64, min: f64, what: &str) -> Result<f64, WolfDataError> {
+    if !value.is_finite() {
+        return Err(invalid(format!("{} must be finite, got {}", what, value)));
+    }
+    if value < min {
+        return Err(invalid(format!("{} cannot be below {}, got {}", what, min, value)));
+    }
+    Ok(value)
+}
+
+fn invalid(message: String) -> WolfDataError {
+    WolfDataError::Validation {
+        line: None,
+        column: None,
+        message,
+    }
+}
+
+/// Air temperature in degrees Celsius
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+#[serde(transparent)]
+pub struct Celsius(f64);
+
+/// Liquid precipitation in millimetres
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+#[serde(transparent)]
+pub struct Millimetres(f64);
+
+/// Snow water equivalent in millimetres
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+#[serde(transparent)]
+pub struct SweMm(f64);
+
+impl Quantity for Celsius {
+    const UNIT: &'static str = "°C";
+
+    fn new(value: f64) -> Result<Self, WolfDataError> {
+        checked(value, -273.15, "temperature").map(Celsius)
+    }
+
+    fn value(self) -> f64 {
+        self.0
+    }
+}
+
+impl Quantity for Millimetres {
+    const UNIT: &'static str = "mm";
+
+    fn new(value: f64) -> Result<Self, WolfDataError> {
+        checked(value, 0.0, "precipitation").map(Millimetres)
+    }
+
+    fn value(self) -> f64 {
+        self.0
+    }
+}
+
+impl Quantity for SweMm {
+    const UNIT: &'static str = "mm SWE";
+
+    fn new(value: f64) -> Result<Self, WolfDataError> {
+        checked(value, 0.0, "snow water equivalent").map(SweMm)
+    }
+
+    fn value(self) -> f64 {
+        self.0
+    }
+}
+
+impl fmt::Display for Celsius {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.0, Self::UNIT)
+    }
+}
+
+impl fmt::Display for Millimetres {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.0, Self::UNIT)
+    }
+}
+
+impl fmt::Display for SweMm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.0, Self::UNIT)
+    }
+}
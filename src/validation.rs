@@ -0,0 +1,401 @@
+//! Plausibility rules for loaded records
+//!
+//! A `Validator` runs a list of rules over a record set and collects a
+//! `ValidationReport`. The built-in rules check formats (unique `uid`,
+//! 0/1 flags, date order) and ecological plausibility (study extent,
+//! denning season); callers can add their own with `RecordRule` or by
+//! implementing `Rule`.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use chrono::Datelike;
+
+use crate::data::{DenningPhenology, PackKey, ReproductiveSuccess};
+use crate::record::WolfRecord;
+use crate::units::Quantity;
+
+/// Latitudes covered by the Alaska/Canada study extent
+pub const LATITUDE_RANGE: RangeInclusive<f64> = 41.0..=84.0;
+/// Longitudes covered by the Alaska/Canada study extent
+pub const LONGITUDE_RANGE: RangeInclusive<f64> = -180.0..=-52.0;
+/// Days of year in which wolves plausibly den (early March to mid July)
+pub const DENNING_SEASON: RangeInclusive<u16> = 60..=200;
+
+/// How serious a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Worth knowing, but the record is fine to use
+    Info,
+    /// Probably wrong; the record is kept
+    Warning,
+    /// Wrong; the record is quarantined in quarantine mode
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// One rule violation by one record
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub rule: String,
+    pub severity: Severity,
+    /// Position of the record in the validated slice
+    pub row: usize,
+    /// CSV line of the record, when validated while loading
+    pub line: Option<u64>,
+    pub uid: u32,
+    pub key: PackKey,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}] ", self.severity, self.rule)?;
+        if let Some(line) = self.line {
+            write!(f, "line {}, ", line)?;
+        }
+        write!(f, "uid {} ({})", self.uid, self.key)?;
+        if let Some(column) = &self.column {
+            write!(f, " `{}`", column)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Findings from running a `Validator` over a record set
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    /// Number of records checked
+    pub checked: usize,
+    /// Findings sorted by row, most severe first
+    pub findings: Vec<Finding>,
+    /// Records moved to the quarantine file
+    pub quarantined: usize,
+}
+
+impl ValidationReport {
+    /// Whether no record has an error-level finding
+    pub fn is_ok(&self) -> bool {
+        !self.findings.iter().any(|f| f.severity == Severity::Error)
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings.iter().filter(|f| f.severity == severity).count()
+    }
+
+    pub fn with_severity(&self, severity: Severity) -> impl Iterator<Item = &Finding> + '_ {
+        self.findings.iter().filter(move |f| f.severity == severity)
+    }
+
+    /// Rows with at least one error-level finding
+    pub fn failing_rows(&self) -> BTreeSet<usize> {
+        self.with_severity(Severity::Error).map(|f| f.row).collect()
+    }
+
+    /// Number of findings per rule
+    pub fn by_rule(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for finding in &self.findings {
+            *counts.entry(finding.rule.as_str()).or_default() += 1;
+        }
+        counts
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} records checked, {} errors, {} warnings",
+            self.checked,
+            self.count(Severity::Error),
+            self.count(Severity::Warning)
+        )?;
+        if self.quarantined > 0 {
+            write!(f, ", {} quarantined", self.quarantined)?;
+        }
+        Ok(())
+    }
+}
+
+/// A check over a whole record set
+pub trait Rule<T> {
+    fn name(&self) -> &str;
+    fn severity(&self) -> Severity;
+    /// The column the rule checks, if it is about a single column
+    fn column(&self) -> Option<&str> {
+        None
+    }
+    /// Returns the row index and message of every violation
+    fn check(&self, records: &[T]) -> Vec<(usize, String)>;
+}
+
+type RecordCheck<T> = Box<dyn Fn(&T) -> Option<String> + Send + Sync>;
+
+/// A rule that looks at one record at a time.
+///
+/// The check returns a message when the record violates the rule.
+pub struct RecordRule<T> {
+    name: String,
+    severity: Severity,
+    column: Option<String>,
+    check: RecordCheck<T>,
+}
+
+impl<T> RecordRule<T> {
+    pub fn new(
+        name: impl Into<String>,
+        severity: Severity,
+        check: impl Fn(&T) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        RecordRule {
+            name: name.into(),
+            severity,
+            column: None,
+            check: Box::new(check),
+        }
+    }
+
+    /// Attributes the rule's findings to `column`
+    pub fn on_column(mut self, column: impl Into<String>) -> Self {
+        self.column = Some(column.into());
+        self
+    }
+}
+
+impl<T> Rule<T> for RecordRule<T> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn column(&self) -> Option<&str> {
+        self.column.as_deref()
+    }
+
+    fn check(&self, records: &[T]) -> Vec<(usize, String)> {
+        records
+            .iter()
+            .enumerate()
+            .filter_map(|(i, record)| (self.check)(record).map(|message| (i, message)))
+            .collect()
+    }
+}
+
+/// Flags every record whose `uid` was already used by an earlier record
+pub struct UniqueUid;
+
+impl<T: Validated> Rule<T> for UniqueUid {
+    fn name(&self) -> &str {
+        "unique_uid"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn column(&self) -> Option<&str> {
+        Some("uid")
+    }
+
+    fn check(&self, records: &[T]) -> Vec<(usize, String)> {
+        let mut first_seen: HashMap<u32, usize> = HashMap::new();
+        let mut violations = Vec::new();
+        for (i, record) in records.iter().enumerate() {
+            if let Some(first) = first_seen.get(&record.uid()) {
+                violations.push((i, format!("uid {} already used by row {}", record.uid(), first)));
+            } else {
+                first_seen.insert(record.uid(), i);
+            }
+        }
+        violations
+    }
+}
+
+/// Record types a `Validator` can check
+pub trait Validated: WolfRecord + Sized + 'static {
+    /// Format and plausibility rules that apply to every file of this type
+    fn builtin_rules() -> Vec<Box<dyn Rule<Self>>>;
+}
+
+/// An ordered list of rules to run over a record set
+pub struct Validator<T> {
+    rules: Vec<Box<dyn Rule<T>>>,
+}
+
+impl<T: Validated> Validator<T> {
+    /// A validator with no rules
+    pub fn new() -> Self {
+        Validator { rules: Vec::new() }
+    }
+
+    /// A validator with the record type's built-in rules
+    pub fn builtin() -> Self {
+        Validator {
+            rules: T::builtin_rules(),
+        }
+    }
+
+    /// Adds a rule, run after the ones already present
+    pub fn with_rule(mut self, rule: impl Rule<T> + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    pub fn rule_names(&self) -> Vec<&str> {
+        self.rules.iter().map(|r| r.name()).collect()
+    }
+
+    pub fn validate(&self, records: &[T]) -> ValidationReport {
+        let mut findings = Vec::new();
+        for rule in &self.rules {
+            for (row, message) in rule.check(records) {
+                let record = &records[row];
+                findings.push(Finding {
+                    rule: rule.name().to_string(),
+                    severity: rule.severity(),
+                    row,
+                    line: None,
+                    uid: record.uid(),
+                    key: record.pack_key(),
+                    column: rule.column().map(str::to_string),
+                    message,
+                });
+            }
+        }
+        findings.sort_by(|a, b| a.row.cmp(&b.row).then(b.severity.cmp(&a.severity)));
+        ValidationReport {
+            checked: records.len(),
+            findings,
+            quarantined: 0,
+        }
+    }
+}
+
+impl<T: Validated> Default for Validator<T> {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+fn check_extent(latitude: f64, longitude: f64) -> Option<String> {
+    if LATITUDE_RANGE.contains(&latitude) && LONGITUDE_RANGE.contains(&longitude) {
+        None
+    } else {
+        Some(format!(
+            "({}, {}) is outside the Alaska/Canada study extent",
+            latitude, longitude
+        ))
+    }
+}
+
+impl Validated for DenningPhenology {
+    fn builtin_rules() -> Vec<Box<dyn Rule<Self>>> {
+        vec![
+            Box::new(UniqueUid),
+            Box::new(
+                RecordRule::new("doy_matches_date", Severity::Error, |d: &Self| {
+                    let ordinal = d.denning_date.ordinal();
+                    (u32::from(d.denning_doy) != ordinal).then(|| {
+                        format!(
+                            "{} is day {} of the year, not {}",
+                            d.denning_date, ordinal, d.denning_doy
+                        )
+                    })
+                })
+                .on_column("denning_doy"),
+            ),
+            Box::new(
+                RecordRule::new("denned_flag", Severity::Error, |d: &Self| {
+                    (!matches!(d.denned, 0 | 1))
+                        .then(|| format!("expected 0 or 1, got {}", d.denned))
+                })
+                .on_column("denned"),
+            ),
+            Box::new(RecordRule::new("study_extent", Severity::Error, |d: &Self| {
+                check_extent(d.latitude_study, d.longitude_study)
+            })),
+            Box::new(
+                RecordRule::new("individual_latitude", Severity::Warning, |d: &Self| {
+                    (d.latitude_individual != 0.0 && !LATITUDE_RANGE.contains(&d.latitude_individual))
+                        .then(|| {
+                            format!("{} is outside the study extent", d.latitude_individual)
+                        })
+                })
+                .on_column("latitude_individual"),
+            ),
+            Box::new(
+                RecordRule::new("denning_season", Severity::Warning, |d: &Self| {
+                    (!DENNING_SEASON.contains(&d.denning_doy)).then(|| {
+                        format!(
+                            "day {} is outside the denning season ({}-{})",
+                            d.denning_doy,
+                            DENNING_SEASON.start(),
+                            DENNING_SEASON.end()
+                        )
+                    })
+                })
+                .on_column("denning_doy"),
+            ),
+        ]
+    }
+}
+
+impl Validated for ReproductiveSuccess {
+    fn builtin_rules() -> Vec<Box<dyn Rule<Self>>> {
+        vec![
+            Box::new(UniqueUid),
+            Box::new(
+                RecordRule::new("start_before_end", Severity::Error, |r: &Self| {
+                    (r.start_date >= r.end_date).then(|| {
+                        format!("start {} is not before end {}", r.start_date, r.end_date)
+                    })
+                })
+                .on_column("end_date"),
+            ),
+            Box::new(
+                RecordRule::new("success_flag", Severity::Error, |r: &Self| {
+                    (!matches!(r.success, 0 | 1))
+                        .then(|| format!("expected 0 or 1, got {}", r.success))
+                })
+                .on_column("success"),
+            ),
+            Box::new(RecordRule::new("study_extent", Severity::Error, |r: &Self| {
+                check_extent(r.latitude_study, r.longitude_study)
+            })),
+            Box::new(
+                RecordRule::new("home_range_positive", Severity::Warning, |r: &Self| {
+                    r.home_range_area
+                        .filter(|area| *area <= 0.0)
+                        .map(|area| format!("home range area {} km² is not positive", area))
+                })
+                .on_column("home_range_area"),
+            ),
+            Box::new(
+                RecordRule::new("winter_colder_than_summer", Severity::Warning, |r: &Self| {
+                    match (r.winter_tmax, r.summer_tmax) {
+                        (Some(w), Some(s)) if w.value() > s.value() => {
+                            Some(format!("winter max {} is above summer max {}", w, s))
+                        }
+                        _ => None,
+                    }
+                })
+                .on_column("winter_tmax"),
+            ),
+        ]
+    }
+}
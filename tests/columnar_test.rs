@@ -0,0 +1,225 @@
+mod common;
+
+use common::{mock_denning_data, mock_reproductive_data};
+use wolf_project_210::columnar::{Aggregate, ColumnStore, GroupKey, Predicate};
+use wolf_project_210::data::{read_denning_csv, DenningPhenology, PackKey};
+use wolf_project_210::dataset::WolfDataset;
+use wolf_project_210::export::{write_csv, Value};
+use wolf_project_210::study::{Country, StudyRegistry};
+
+fn three_packs() -> ColumnStore {
+    let template = &mock_denning_data()[0];
+    let denning: Vec<_> = [
+        (1, "Study A, Alaska, USA", 110),
+        (2, "Study A, Alaska, USA", 130),
+        (3, "Study B, Yukon, Canada", 140),
+    ]
+    .into_iter()
+    .map(|(pack_id, study, doy)| {
+        let mut record = template.clone();
+        record.uid = pack_id;
+        record.pack_id = pack_id;
+        record.study = study.to_string();
+        record.denning_doy = doy;
+        // 0 marks a missing individual location
+        record.latitude_individual = 0.0;
+        record
+    })
+    .collect();
+    ColumnStore::from_records(&denning, &StudyRegistry::new())
+}
+
+#[test]
+fn test_filters_combine() {
+    let store = three_packs();
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.query().study("Study A, Alaska, USA").count(), 2);
+    assert_eq!(store.query().country(Country::Canada).count(), 1);
+    assert_eq!(store.query().years(2021..=2030).count(), 0);
+
+    let late = store.query().filter("denning_doy", Predicate::Ge(130.0));
+    assert_eq!(late.rows(), &[1, 2]);
+    let packs = late.study("Study A, Alaska, USA").packs();
+    assert_eq!(packs, vec![PackKey::new("Study A, Alaska, USA", 2)]);
+
+    // Unknown columns and missing values never match
+    assert_eq!(
+        store
+            .query()
+            .filter("no_such_column", Predicate::Ge(0.0))
+            .count(),
+        0
+    );
+    assert_eq!(
+        store
+            .query()
+            .filter("latitude_individual", Predicate::Ne(1.0))
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn test_group_by_country_aggregates() {
+    let table = three_packs()
+        .query()
+        .group_by(&[GroupKey::Country])
+        .aggregate(&[
+            Aggregate::Count,
+            Aggregate::Mean("denning_doy".into()),
+            Aggregate::Sd("denning_doy".into()),
+            Aggregate::Proportion("denning_doy".into(), Predicate::Lt(120.0)),
+            Aggregate::Max("latitude_individual".into()),
+        ]);
+
+    let names: Vec<&str> = table.columns().iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "country",
+            "count",
+            "mean(denning_doy)",
+            "sd(denning_doy)",
+            "prop(denning_doy < 120)",
+            "max(latitude_individual)"
+        ]
+    );
+    // Countries sort in declaration order: USA before Canada
+    assert_eq!(table.rows()[0][0], Value::Text("USA".to_string()));
+    assert_eq!(table.rows()[0][1], Value::Integer(2));
+    assert_eq!(table.rows()[0][2], Value::Float(120.0));
+    assert!((table.rows()[0][3].as_f64().unwrap() - 200f64.sqrt()).abs() < 1e-9);
+    assert_eq!(table.rows()[0][4], Value::Float(0.5));
+    assert_eq!(table.rows()[0][5], Value::Null);
+    assert_eq!(
+        table.rows()[1][3],
+        Value::Null,
+        "sd of one value is undefined"
+    );
+
+    let mut csv = Vec::new();
+    write_csv(&table, &mut csv).unwrap();
+    assert!(String::from_utf8(csv)
+        .unwrap()
+        .starts_with("country,count,"));
+    assert!(table.to_string().lines().nth(2).unwrap().starts_with("USA"));
+}
+
+#[test]
+fn test_dataset_columns_match_records() {
+    let (denning, _) = read_denning_csv("data/Wolf_DenningPhenology_AK_CA.csv").unwrap();
+    let expected: f64 = denning
+        .iter()
+        .map(|d| f64::from(d.denning_doy))
+        .sum::<f64>()
+        / denning.len() as f64;
+    let dataset = WolfDataset::new(denning, mock_reproductive_data());
+
+    let table = dataset
+        .denning_columns()
+        .query()
+        .aggregate(&[Aggregate::Count, Aggregate::Mean("denning_doy".into())]);
+    assert_eq!(
+        table.rows()[0][0],
+        Value::Integer(dataset.denning().len() as i64)
+    );
+    assert!((table.rows()[0][1].as_f64().unwrap() - expected).abs() < 1e-9);
+
+    let by_year = dataset
+        .denning_columns()
+        .query()
+        .group_by(&[GroupKey::Year]);
+    let years = dataset
+        .denning()
+        .iter()
+        .map(|d| d.denning_date)
+        .collect::<std::collections::BTreeSet<_>>();
+    assert!(by_year.len() <= years.len());
+    let series = by_year
+        .aggregate(&[Aggregate::Median("denning_doy".into())])
+        .series("year", "median(denning_doy)");
+    assert!(series.windows(2).all(|w| w[0].0 < w[1].0));
+}
+
+#[test]
+fn test_predicates_and_empty_selections() {
+    for (predicate, hits, name) in [
+        (Predicate::Lt(2.0), [true, false, false], "< 2"),
+        (Predicate::Le(2.0), [true, true, false], "<= 2"),
+        (Predicate::Gt(2.0), [false, false, true], "> 2"),
+        (Predicate::Eq(2.0), [false, true, false], "= 2"),
+        (
+            Predicate::Between(2.0, 3.0),
+            [false, true, true],
+            "in [2, 3]",
+        ),
+    ] {
+        assert_eq!([1.0, 2.0, 3.0].map(|v| predicate.test(v)), hits, "{}", name);
+        assert_eq!(predicate.to_string(), name);
+    }
+    assert!(!Predicate::Eq(f64::NAN).test(f64::NAN));
+
+    let store = three_packs();
+    let none = store.query().study("Study C");
+    assert_eq!(none.count(), 0);
+    assert!(none.packs().is_empty());
+    assert!(none.values("denning_doy").is_empty());
+    let table = none.aggregate(&[Aggregate::Count, Aggregate::Min("denning_doy".into())]);
+    assert_eq!(table.rows(), &[vec![Value::Integer(0), Value::Null]]);
+    assert!(store
+        .query()
+        .study("Study C")
+        .group_by(&[GroupKey::Study])
+        .is_empty());
+
+    let pack = PackKey::new("Study A, Alaska, USA", 2);
+    assert_eq!(store.query().pack(&pack).rows(), &[1]);
+    assert_eq!(
+        store.column("denning_doy"),
+        Some(&[110.0, 130.0, 140.0][..])
+    );
+    assert!(store.column("no_such_column").is_none());
+    assert_eq!(
+        ColumnStore::from_records::<DenningPhenology>(&[], &StudyRegistry::new()).len(),
+        0
+    );
+}
+
+#[test]
+fn test_group_by_pack_and_year_orders_keys() {
+    let table = three_packs()
+        .query()
+        .group_by(&[GroupKey::Pack, GroupKey::Year])
+        .aggregate(&[
+            Aggregate::Median("denning_doy".into()),
+            Aggregate::Min("denning_doy".into()),
+            Aggregate::Max("denning_doy".into()),
+            Aggregate::Mean("no_such_column".into()),
+        ]);
+    let names: Vec<&str> = table.columns().iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "study",
+            "pack_id",
+            "year",
+            "median(denning_doy)",
+            "min(denning_doy)",
+            "max(denning_doy)",
+            "mean(no_such_column)"
+        ]
+    );
+    assert_eq!(table.len(), 3);
+    assert_eq!(
+        table.rows()[2],
+        [
+            Value::Text("Study B, Yukon, Canada".to_string()),
+            Value::Integer(3),
+            Value::Integer(2020),
+            Value::Float(140.0),
+            Value::Float(140.0),
+            Value::Float(140.0),
+            Value::Null,
+        ]
+    );
+}
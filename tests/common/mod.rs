@@ -0,0 +1,63 @@
+//! Shared mock records for the integration tests
+
+use chrono::NaiveDate;
+use wolf_project_210::data::{DenningPhenology, ReproductiveSuccess};
+use wolf_project_210::units::{Celsius, Millimetres, Quantity, SweMm};
+
+#[allow(dead_code)]
+pub fn mock_reproductive_data() -> Vec<ReproductiveSuccess> {
+    vec![
+        ReproductiveSuccess {
+            uid: 1,
+            study: "Study A".to_string(),
+            longitude_study: 0.0,
+            latitude_study: 0.0,
+            pack_id: 1,
+            start_date: NaiveDate::from_ymd_opt(2020, 6, 1).unwrap(),
+            end_date: NaiveDate::from_ymd_opt(2020, 9, 1).unwrap(),
+            success: 1,
+            summer_prcp: Millimetres::new(100.0).ok(),
+            fall_prcp: Millimetres::new(50.0).ok(),
+            winter_swe: SweMm::new(200.0).ok(),
+            fall_tmax: Celsius::new(10.0).ok(),
+            summer_tmax: Celsius::new(20.0).ok(),
+            winter_tmax: Celsius::new(5.0).ok(),
+            ti_ndvi_prev1: Some(0.5),
+            ti_ndvi: Some(0.6),
+            annual_pdo: Some(1.0),
+            annual_ao: Some(0.5),
+            home_range_area: Some(100.0),
+            denning_match_growing_season: Some(0.8),
+        },
+        // Add more mock records if needed
+    ]
+}
+
+#[allow(dead_code)]
+pub fn mock_denning_data() -> Vec<DenningPhenology> {
+    vec![
+        DenningPhenology {
+            uid: 1,
+            study: "Study A".to_string(),
+            longitude_study: 0.0,
+            latitude_study: 0.0,
+            pack_id: 1,
+            denning_date: NaiveDate::from_ymd_opt(2020, 5, 10).unwrap(),
+            denning_doy: 130,
+            denned: 1,
+            fall_tmax: Celsius::new(15.0).ok(),
+            summer_tmax_prev1: Celsius::new(25.0).ok(),
+            winter_tmax: Celsius::new(10.0).ok(),
+            fall_prcp: Millimetres::new(120.0).ok(),
+            summer_prcp_prev1: Millimetres::new(110.0).ok(),
+            winter_swe: SweMm::new(250.0).ok(),
+            ti_ndvi_prev1: Some(0.6),
+            annual_pdo: Some(1.0),
+            annual_ao: Some(0.5),
+            sos_prev1: Some(0.5),
+            los_prev1: Some(0.8),
+            latitude_individual: 60.0,
+        },
+        // Add more mock records if needed
+    ]
+}
@@ -0,0 +1,276 @@
+mod common;
+
+use common::mock_denning_data;
+use statrs::distribution::{ContinuousCDF, StudentsT};
+use wolf_project_210::data::DenningPhenology;
+use wolf_project_210::export::export_table;
+use wolf_project_210::export::Value;
+use wolf_project_210::stats::correlation::{
+    correlation_matrix, Adjustment, CorrelationMatrix, CorrelationOptions, Method,
+};
+use wolf_project_210::synthetic::synthetic_dataset;
+use wolf_project_210::units::{Celsius, Quantity};
+
+const COVARIATES: [&str; 3] = ["annual_pdo", "annual_ao", "winter_tmax"];
+
+/// Six seasons with one gap in `annual_pdo` and one in `winter_tmax`
+fn six_seasons() -> Vec<DenningPhenology> {
+    let template = &mock_denning_data()[0];
+    let pdo = [Some(1.0), Some(2.0), Some(3.0), Some(4.0), Some(5.0), None];
+    let ao = [2.0, 1.0, 4.0, 3.0, 10.0, 7.0];
+    let tmax = [None, Some(1.0), Some(1.0), Some(2.0), Some(3.0), Some(3.0)];
+    (0..6)
+        .map(|i| {
+            let mut record = template.clone();
+            record.uid = i as u32 + 1;
+            record.annual_pdo = pdo[i];
+            record.annual_ao = Some(ao[i]);
+            record.winter_tmax = tmax[i].map(|t| Celsius::new(t).unwrap());
+            record
+        })
+        .collect()
+}
+
+#[test]
+fn test_pairwise_complete_pearson_and_spearman() {
+    let records = six_seasons();
+    let pearson = correlation_matrix(&records, &COVARIATES).unwrap();
+    let spearman = CorrelationOptions {
+        method: Method::Spearman,
+        ..CorrelationOptions::default()
+    }
+    .compute(&records, &COVARIATES)
+    .unwrap();
+
+    let pair = pearson.get("annual_ao", "annual_pdo").unwrap();
+    assert_eq!(pair.n, 5);
+    assert!((pair.r.unwrap() - 18.0 / 500f64.sqrt()).abs() < 1e-12);
+    let pair = pearson.get("annual_pdo", "winter_tmax").unwrap();
+    assert_eq!(pair.n, 4);
+    assert!((pair.r.unwrap() - 3.5 / 13.75f64.sqrt()).abs() < 1e-12);
+    assert_eq!(pearson.get("annual_ao", "winter_tmax").unwrap().n, 5);
+
+    // Ranks of annual_ao are 2 1 4 3 5; tied winter_tmax values share 1.5
+    let rho = spearman.get("annual_pdo", "annual_ao").unwrap();
+    assert!((rho.r.unwrap() - 0.8).abs() < 1e-12);
+    let t = 0.8 * (3.0f64 / 0.36).sqrt();
+    let p = 2.0 * StudentsT::new(0.0, 1.0, 3.0).unwrap().sf(t);
+    assert!((rho.p_value.unwrap() - p).abs() < 1e-12);
+    let rho = spearman.get("annual_pdo", "winter_tmax").unwrap();
+    assert!((rho.r.unwrap() - 4.5 / 22.5f64.sqrt()).abs() < 1e-12);
+
+    let matrix = pearson.matrix();
+    assert_eq!(matrix.dim(), (3, 3));
+    assert_eq!(matrix[[0, 0]], 1.0);
+    assert_eq!(matrix[[0, 2]], matrix[[2, 0]]);
+}
+
+#[test]
+fn test_holm_and_benjamini_hochberg_adjustments() {
+    let dataset = synthetic_dataset(6);
+    let covariates = [
+        "winter_swe",
+        "winter_tmax",
+        "annual_pdo",
+        "annual_ao",
+        "sos_prev1",
+    ];
+    let options = |adjustment| CorrelationOptions {
+        adjustment,
+        ..CorrelationOptions::default()
+    };
+    let raw = options(Adjustment::None)
+        .compute(dataset.denning(), &covariates)
+        .unwrap();
+    let holm = options(Adjustment::Holm)
+        .compute(dataset.denning(), &covariates)
+        .unwrap();
+    let bh = options(Adjustment::BenjaminiHochberg)
+        .compute(dataset.denning(), &covariates)
+        .unwrap();
+    assert_eq!(raw.pairs.len(), 10);
+
+    let mut p: Vec<f64> = raw.pairs.iter().map(|c| c.p_value.unwrap()).collect();
+    p.sort_by(f64::total_cmp);
+    let adjusted = |m: &CorrelationMatrix| {
+        let mut a: Vec<f64> = m.pairs.iter().map(|c| c.adjusted_p.unwrap()).collect();
+        a.sort_by(f64::total_cmp);
+        a
+    };
+    let (holm_p, bh_p) = (adjusted(&holm), adjusted(&bh));
+    assert!((holm_p[0] - (10.0 * p[0]).min(1.0)).abs() < 1e-12);
+    assert!(
+        (bh_p[9] - p[9]).abs() < 1e-12,
+        "largest p is unchanged by BH"
+    );
+    for k in 0..10 {
+        assert!(p[k] <= bh_p[k] + 1e-15 && bh_p[k] <= holm_p[k] + 1e-15);
+    }
+    for c in &holm.pairs {
+        assert_eq!(c.significant, c.adjusted_p.unwrap() < 0.05);
+        assert!(c.adjusted_p >= c.p_value);
+    }
+}
+
+#[test]
+fn test_exports_heatmap_and_errors() {
+    let records = six_seasons();
+    let matrix = correlation_matrix(&records, &COVARIATES).unwrap();
+
+    assert_eq!(matrix.table().len(), 3);
+    let square = matrix.matrix_table();
+    assert_eq!((square.len(), square.columns().len()), (3, 4));
+    assert!(matrix.to_string().contains("Holm"));
+
+    let mut json = Vec::new();
+    matrix.write_json(&mut json).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(value["method"], "pearson");
+    assert_eq!(value["pairs"].as_array().unwrap().len(), 3);
+
+    let dir = std::env::temp_dir();
+    let csv = dir.join("wolf_correlations.csv");
+    export_table(&matrix.table(), &csv).unwrap();
+    let text = std::fs::read_to_string(&csv).unwrap();
+    assert!(text.starts_with("x,y,n,r,p_value,adjusted_p,significant"));
+    let png = dir.join("wolf_correlations.png");
+    matrix.plot_heatmap(&png).unwrap();
+    assert!(png.exists());
+    std::fs::remove_file(csv).ok();
+    std::fs::remove_file(png).ok();
+
+    let error = correlation_matrix(&records, &["annual_pdo"]).unwrap_err();
+    assert!(error.to_string().contains("two covariates"), "{}", error);
+    let error = correlation_matrix(&records, &["annual_pdo", "success"]).unwrap_err();
+    assert!(error.to_string().contains("success"), "{}", error);
+}
+
+/// Five seasons where `annual_pdo` and `annual_ao` correlate at 0.8,
+/// `los_prev1` falls exactly as `annual_pdo` rises, `winter_tmax` is
+/// constant and `sos_prev1` is observed twice
+fn five_seasons() -> Vec<DenningPhenology> {
+    let template = &mock_denning_data()[0];
+    let ao = [2.0, 1.0, 4.0, 3.0, 5.0];
+    (0..5)
+        .map(|i| {
+            let pdo = i as f64 + 1.0;
+            DenningPhenology {
+                uid: i as u32 + 1,
+                annual_pdo: Some(pdo),
+                annual_ao: Some(ao[i]),
+                los_prev1: Some(10.0 - pdo),
+                winter_tmax: Celsius::new(2.0).ok(),
+                sos_prev1: (i < 2).then_some(130.0),
+                ..template.clone()
+            }
+        })
+        .collect()
+}
+
+const WITH_UNDEFINED: [&str; 5] = [
+    "annual_pdo",
+    "annual_ao",
+    "winter_tmax",
+    "sos_prev1",
+    "los_prev1",
+];
+
+#[test]
+fn test_undefined_pairs_are_left_out_of_the_adjustments() {
+    let records = five_seasons();
+    let options = |adjustment| CorrelationOptions {
+        adjustment,
+        ..CorrelationOptions::default()
+    };
+    let holm = options(Adjustment::Holm)
+        .compute(&records, &WITH_UNDEFINED)
+        .unwrap();
+    assert_eq!(holm.pairs.len(), 10);
+
+    let constant = holm.get("winter_tmax", "annual_pdo").unwrap();
+    assert_eq!((constant.n, constant.r, constant.p_value), (5, None, None));
+    assert_eq!(constant.adjusted_p, None);
+    assert!(!constant.significant);
+    let sparse = holm.get("sos_prev1", "los_prev1").unwrap();
+    assert_eq!((sparse.n, sparse.r), (2, None));
+
+    let exact = holm.get("los_prev1", "annual_pdo").unwrap();
+    assert_eq!((exact.r, exact.p_value), (Some(-1.0), Some(0.0)));
+    assert!(exact.significant);
+
+    // Three defined pairs, so Holm multiplies by at most 3
+    let p = holm
+        .get("annual_pdo", "annual_ao")
+        .unwrap()
+        .p_value
+        .unwrap();
+    for (x, y) in [("annual_pdo", "annual_ao"), ("annual_ao", "los_prev1")] {
+        let pair = holm.get(x, y).unwrap();
+        assert!((pair.r.unwrap().abs() - 0.8).abs() < 1e-12);
+        assert!((pair.adjusted_p.unwrap() - 2.0 * p).abs() < 1e-12);
+    }
+    let bh = options(Adjustment::BenjaminiHochberg)
+        .compute(&records, &WITH_UNDEFINED)
+        .unwrap();
+    let bh_p = bh
+        .get("annual_ao", "los_prev1")
+        .unwrap()
+        .adjusted_p
+        .unwrap();
+    assert!((bh_p - p).abs() < 1e-12);
+    let raw = options(Adjustment::None)
+        .compute(&records, &WITH_UNDEFINED)
+        .unwrap();
+    assert!(raw.pairs.iter().all(|c| c.adjusted_p == c.p_value));
+
+    let matrix = holm.matrix();
+    assert!(matrix[[2, 0]].is_nan() && matrix[[3, 4]].is_nan());
+    assert_eq!(matrix[[2, 2]], 1.0, "the diagonal is 1 even when constant");
+    let square = holm.matrix_table();
+    assert_eq!(square.rows()[0][3], Value::Null);
+    assert_eq!(holm.table().rows()[1][3], Value::Null);
+    assert!(holm.get("annual_pdo", "denning_doy").is_none());
+}
+
+#[test]
+fn test_alpha_sets_significance_and_shows_in_the_summary() {
+    let records = five_seasons();
+    let with_alpha = |alpha| {
+        CorrelationOptions {
+            method: Method::Spearman,
+            adjustment: Adjustment::BenjaminiHochberg,
+            alpha,
+        }
+        .compute(&records, &WITH_UNDEFINED)
+        .unwrap()
+    };
+    let everything = with_alpha(1.0);
+    let significant: Vec<(&str, &str)> = everything
+        .pairs
+        .iter()
+        .filter(|c| c.significant)
+        .map(|c| (c.x.as_str(), c.y.as_str()))
+        .collect();
+    assert_eq!(
+        significant,
+        [
+            ("annual_pdo", "annual_ao"),
+            ("annual_pdo", "los_prev1"),
+            ("annual_ao", "los_prev1")
+        ]
+    );
+    assert!(with_alpha(0.0).pairs.iter().all(|c| !c.significant));
+
+    let matrix = with_alpha(0.1);
+    assert!(matrix
+        .to_string()
+        .starts_with("Spearman correlations (Benjamini–Hochberg p-values, significant below 0.1)"));
+    let mut json = Vec::new();
+    matrix.write_json(&mut json).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(value["adjustment"], "benjamini_hochberg");
+    assert_eq!(value["pairs"][1]["r"], serde_json::Value::Null);
+
+    let error = correlation_matrix(&records, &[]).unwrap_err();
+    assert!(error.to_string().contains("two covariates"), "{}", error);
+}
@@ -1,23 +1,184 @@
-use wolf_project_210::data::{read_denning_csv, read_reproductive_csv};
+mod common;
+
+use chrono::NaiveDate;
+use common::{mock_denning_data, mock_reproductive_data};
+use wolf_project_210::data::{
+    find_reused_pack_ids, join_pack_seasons, read_denning_csv, read_reproductive_csv,
+    read_reproductive_from_reader, stream_denning_records, stream_reproductive_records, JoinKind,
+    PackKey, RejectReason,
+};
+use wolf_project_210::error::WolfDataError;
+use wolf_project_210::units::{Celsius, Millimetres, Quantity, SweMm};
 
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
+    use super::*;
 
     #[test]
     fn test_read_denning_csv() {
-        let denning_data = mock_denning_data();
-        let reproductive_data = mock_reproductive_data();
-
+        let denning_data = read_denning_csv("data/Wolf_DenningPhenology_AK_CA.csv");
+        assert!(denning_data.is_ok(), "Failed to read denning CSV");
+        let (data, report) = denning_data.unwrap();
+        assert!(!data.is_empty(), "Denning data should not be empty");
+        assert_eq!(report.accepted, data.len());
+        assert_eq!(report.total_rows, data.len() + report.rejected_count());
     }
 
     #[test]
     fn test_read_reproductive_csv() {
         let reproductive_data = read_reproductive_csv("data/Wolf_ReproductiveSuccess_AK_CA.csv");
         assert!(reproductive_data.is_ok(), "Failed to read reproductive CSV");
-        let data = reproductive_data.unwrap();
+        let (data, _) = reproductive_data.unwrap();
         assert!(!data.is_empty(), "Reproductive data should not be empty");
     }
-}
 
+    #[test]
+    fn test_load_report_lists_rejected_rows() {
+        let csv = "\
+uid,study,longitude_study,latitude_study,pack_id,start_date,end_date,success,summer_prcp,fall_prcp,winter_swe,fall_tmax,summer_tmax,winter_tmax,tiNDVI_prev1,tiNDVI,annual_pdo,annual_ao,home_range_area,denning_match_growing_season
+1,Study A,0,0,1,2020-04-01,2020-08-31,1,100,50,200,10,20,5,0.5,0.6,1,0.5,100,0.8
+2,Study A,0,0,2,2020-04-01,2020-08-31,1,-9999,50,200,10,20,5,0.5,0.6,1,0.5,100,NA
+3,Study B,0,0,3,2020-04-01,2020-08-31,1,100,50,200,10,20,5,0.5,0.6,1,0.5,100,0.8
+4,Study B,0,0,4,not-a-date,2020-08-31,1,100,50,200,10,20,5,0.5,0.6,1,0.5,100,0.8
+";
+        let (data, report) = read_reproductive_from_reader(csv.as_bytes()).unwrap();
+        assert_eq!(data.len(), 2);
+        assert_eq!(report.total_rows, 4);
+        assert_eq!(report.rejected_count(), 2);
+
+        let missing = &report.rejected[0];
+        assert_eq!(missing.line, 3);
+        assert_eq!(missing.uid, Some(2));
+        assert_eq!(missing.issues[0].column, "summer_prcp");
+        assert_eq!(missing.issues[0].reason, RejectReason::Sentinel);
+        assert_eq!(missing.issues[1].reason, RejectReason::NotAvailable);
+
+        let bad_date = &report.rejected[1];
+        assert_eq!(bad_date.issues[0].column, "start_date");
+        assert!(matches!(bad_date.issues[0].reason, RejectReason::Parse(_)));
+
+        assert_eq!(report.by_study["Study A"], 1);
+        assert_eq!(report.by_study["Study B"], 1);
+        assert!(report.ensure_rejection_rate(0.25).is_err());
+        assert!(report.ensure_rejection_rate(0.5).is_ok());
+    }
+
+    #[test]
+    fn test_find_reused_pack_ids() {
+        let keys = vec![
+            PackKey::new("Denali", 1),
+            PackKey::new("Denali", 1),
+            PackKey::new("Denali", 2),
+            PackKey::new("Yukon-Charley", 2),
+        ];
+        let reused = find_reused_pack_ids(keys);
+
+        assert_eq!(reused.len(), 1);
+        assert_eq!(reused[&2].len(), 2);
+    }
+
+    #[test]
+    fn test_join_pack_seasons_matches_on_pack_and_year() {
+        let mut denning_data = mock_denning_data();
+        let mut other_year = denning_data[0].clone();
+        other_year.uid = 2;
+        other_year.denning_date = NaiveDate::from_ymd_opt(2021, 5, 12).unwrap();
+        denning_data.push(other_year);
+
+        let mut reproductive_data = mock_reproductive_data();
+        let mut other_study = reproductive_data[0].clone();
+        other_study.uid = 2;
+        other_study.study = "Study B".to_string();
+        reproductive_data.push(other_study);
+
+        let inner = join_pack_seasons(&denning_data, &reproductive_data, JoinKind::Inner);
+        assert_eq!(inner.seasons.len(), 1);
+        assert_eq!(inner.seasons[0].year, 2020);
+        assert_eq!(inner.seasons[0].reproduction.as_ref().unwrap().uid, 1);
+        assert_eq!(inner.unmatched_denning.len(), 1);
+        assert_eq!(inner.unmatched_denning[0].uid, 2);
+        assert_eq!(inner.unmatched_reproduction.len(), 1);
+        assert_eq!(inner.unmatched_reproduction[0].uid, 2);
+
+        let left = join_pack_seasons(&denning_data, &reproductive_data, JoinKind::Left);
+        assert_eq!(left.seasons.len(), 2);
+        assert!(left.seasons[1].reproduction.is_none());
+
+        let anti = join_pack_seasons(&denning_data, &reproductive_data, JoinKind::Anti);
+        assert_eq!(anti.seasons.len(), 1);
+        assert_eq!(anti.seasons[0].denning.uid, 2);
+    }
+
+    #[test]
+    fn test_stream_denning_records() {
+        let file = std::fs::File::open("data/Wolf_DenningPhenology_AK_CA.csv").unwrap();
+        let stream = stream_denning_records(file).unwrap();
+
+        let (ok, rejected): (Vec<_>, Vec<_>) = stream.partition(Result::is_ok);
+        let (data, report) = read_denning_csv("data/Wolf_DenningPhenology_AK_CA.csv").unwrap();
+        assert_eq!(ok.len(), data.len());
+        assert_eq!(rejected.len(), report.rejected_count());
+    }
+
+    #[test]
+    fn test_stream_rejects_missing_header_column() {
+        let csv = "uid,study\n1,Study A\n";
+        let error = stream_denning_records(csv.as_bytes()).err().unwrap();
+        match error {
+            WolfDataError::Schema(report) => {
+                assert!(report.missing.contains(&"denning_date".to_string()))
+            }
+            other => panic!("Expected a schema error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_typed_errors() {
+        let missing_file = read_denning_csv("data/does_not_exist.csv").err().unwrap();
+        assert!(matches!(missing_file, WolfDataError::Io { path: Some(_), .. }));
+
+        let csv = "\
+uid,study,longitude_study,latitude_study,pack_id,start_date,end_date,success,summer_prcp,fall_prcp,winter_swe,fall_tmax,summer_tmax,winter_tmax,tiNDVI_prev1,tiNDVI,annual_pdo,annual_ao,home_range_area,denning_match_growing_season
+7,Study A,0,0,1,2020-04-01,2020-08-31,1,100,50,200,warm,20,5,0.5,0.6,1,0.5,100,0.8
+";
+        let row = stream_reproductive_records(csv.as_bytes()).unwrap().next().unwrap();
+        match WolfDataError::from(row.err().unwrap()) {
+            WolfDataError::Parse { line, column, value, .. } => {
+                assert_eq!(line, 2);
+                assert_eq!(column, "fall_tmax");
+                assert_eq!(value, "warm");
+            }
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_covariates_keep_precision_and_reject_negative_precipitation() {
+        let csv = "\
+uid,study,longitude_study,latitude_study,pack_id,start_date,end_date,success,summer_prcp,fall_prcp,winter_swe,fall_tmax,summer_tmax,winter_tmax,tiNDVI_prev1,tiNDVI,annual_pdo,annual_ao,home_range_area,denning_match_growing_season
+1,Study A,0,0,1,2020-04-01,2020-08-31,1,100.4,50,212.75,10,19.6,-27.3,0.5,0.6,1,0.5,100,0.8
+2,Study A,0,0,2,2020-04-01,2020-08-31,1,-3.5,50,200,10,20,5,0.5,0.6,1,0.5,100,0.8
+";
+        let (data, report) = read_reproductive_from_reader(csv.as_bytes()).unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0].summer_prcp.map(Quantity::value), Some(100.4));
+        assert_eq!(data[0].winter_swe.map(Quantity::value), Some(212.75));
+        assert_eq!(data[0].summer_tmax.map(Quantity::value), Some(19.6));
+        assert_eq!(data[0].winter_tmax.map(Quantity::value), Some(-27.3));
+
+        let negative = &report.rejected[0];
+        assert_eq!(negative.issues[0].column, "summer_prcp");
+        assert!(matches!(&negative.issues[0].reason, RejectReason::Parse(m) if m.contains("below 0")));
+    }
+
+    #[test]
+    fn test_quantities_are_only_built_through_checked_constructors() {
+        assert!(Millimetres::new(-5.0).is_err());
+        assert!(SweMm::new(f64::INFINITY).is_err());
+        assert!(Celsius::new(-300.0).is_err());
+        assert!(Celsius::new(f64::NAN).is_err());
+        assert_eq!(Celsius::new(-27.3).unwrap().value(), -27.3);
+        assert_eq!(Millimetres::new(0.0).unwrap().to_string(), "0 mm");
+    }
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDate;
+use common::{mock_denning_data, mock_reproductive_data};
+use wolf_project_210::data::{DenningPhenology, PackKey, ReproductiveSuccess};
+use wolf_project_210::dataset::WolfDataset;
+use wolf_project_210::{
+    analyze_snow_cover_impact, analyze_temperature_impact, identify_vulnerable_regions,
+};
+
+/// Builds `n` pack-seasons spread over 10 studies and 20 years
+fn scaled_dataset(n: usize) -> WolfDataset {
+    let denning_template = &mock_denning_data()[0];
+    let reproduction_template = &mock_reproductive_data()[0];
+
+    let mut denning = Vec::with_capacity(n);
+    let mut reproduction = Vec::with_capacity(n);
+    for i in 0..n {
+        let study = format!("Study {}", i % 10);
+        let pack_id = (i / 10) as u32 + 1;
+        let year = 2000 + (i % 20) as i32;
+
+        denning.push(DenningPhenology {
+            uid: i as u32 + 1,
+            study: study.clone(),
+            pack_id,
+            denning_date: NaiveDate::from_ymd_opt(year, 5, 10).unwrap(),
+            ..denning_template.clone()
+        });
+        reproduction.push(ReproductiveSuccess {
+            uid: i as u32 + 1,
+            study,
+            pack_id,
+            start_date: NaiveDate::from_ymd_opt(year, 4, 1).unwrap(),
+            success: (i % 2) as u8,
+            ..reproduction_template.clone()
+        });
+    }
+    WolfDataset::new(denning, reproduction)
+}
+
+/// Best of three runs of all indexed analyses over `dataset`
+fn time_analyses(dataset: &WolfDataset) -> Duration {
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            analyze_temperature_impact(dataset);
+            analyze_snow_cover_impact(dataset);
+            identify_vulnerable_regions(dataset);
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+#[test]
+fn test_dataset_indexes() {
+    let dataset = scaled_dataset(100);
+    let key = PackKey::new("Study 3", 1);
+
+    assert!(dataset.has_denning(&key));
+    assert!(!dataset.has_denning(&PackKey::new("Study 3", 999)));
+    assert_eq!(dataset.denning_for_pack(&key).count(), 1);
+    assert_eq!(dataset.reproduction_for_study("Study 3").count(), 10);
+    assert_eq!(dataset.denning_for_year(2003).count(), 5);
+    assert_eq!(dataset.reproduction_for_season(&key, 2003).count(), 1);
+    assert_eq!(dataset.studies().len(), 10);
+    assert_eq!(dataset.years(), (2000..2020).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_analyses_link_only_the_same_season() {
+    let denning = |year| DenningPhenology {
+        denning_date: NaiveDate::from_ymd_opt(year, 5, 10).unwrap(),
+        ..mock_denning_data()[0].clone()
+    };
+    let reproduction = |uid, year| ReproductiveSuccess {
+        uid,
+        start_date: NaiveDate::from_ymd_opt(year, 4, 1).unwrap(),
+        success: 0,
+        ..mock_reproductive_data()[0].clone()
+    };
+
+    // Pack 1 of Study A denned in 2003 but was only followed in 2009
+    let apart = WolfDataset::new(vec![denning(2003)], vec![reproduction(1, 2009)]);
+    assert!(apart.has_denning(&PackKey::new("Study A", 1)));
+    assert!(analyze_temperature_impact(&apart).is_empty());
+    assert!(analyze_snow_cover_impact(&apart).is_empty());
+    assert!(identify_vulnerable_regions(&apart).is_empty());
+
+    let both = WolfDataset::new(
+        vec![denning(2003), denning(2009)],
+        vec![reproduction(1, 2009), reproduction(2, 2010)],
+    );
+    assert_eq!(analyze_temperature_impact(&both).len(), 1);
+    assert_eq!(analyze_snow_cover_impact(&both).len(), 1);
+    let vulnerable: Vec<_> = identify_vulnerable_regions(&both).into_iter().collect();
+    assert_eq!(vulnerable, [PackKey::new("Study A", 1)]);
+
+    // A pack that failed in two denning seasons is listed once
+    let twice = WolfDataset::new(
+        vec![denning(2003), denning(2009)],
+        vec![reproduction(1, 2003), reproduction(2, 2009)],
+    );
+    assert_eq!(identify_vulnerable_regions(&twice).len(), 1);
+    assert_eq!(
+        both.denning_for_season(&PackKey::new("Study A", 1), 2009)
+            .count(),
+        1
+    );
+}
+
+/// A timing benchmark, so it is left out of normal runs where a loaded
+/// machine could make it flaky; run it with `cargo test -- --ignored`
+#[test]
+#[ignore]
+fn test_analyses_scale_linearly() {
+    let small = scaled_dataset(2_000);
+    let large = scaled_dataset(16_000);
+    assert_eq!(analyze_temperature_impact(&large).len(), 16_000);
+
+    let small_time = time_analyses(&small);
+    let large_time = time_analyses(&large);
+
+    // 8x the rows: a linear scan per record would take ~64x as long
+    let ratio = large_time.as_secs_f64() / small_time.as_secs_f64().max(1e-6);
+    assert!(ratio < 32.0, "8x rows took {:.1}x as long", ratio);
+}
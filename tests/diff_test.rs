@@ -0,0 +1,211 @@
+mod common;
+
+use common::{mock_denning_data, mock_reproductive_data};
+use wolf_project_210::data::{DenningPhenology, PackKey};
+use wolf_project_210::dataset::WolfDataset;
+use wolf_project_210::diff::{diff_datasets, DatasetDiff, DiffKey, DiffOptions, RowKey};
+use wolf_project_210::export::Value;
+use wolf_project_210::units::{Celsius, Quantity};
+
+/// Three packs of "Study A" with uids 1 to 3
+fn release() -> Vec<DenningPhenology> {
+    (1..=3)
+        .map(|uid| DenningPhenology {
+            uid,
+            pack_id: uid,
+            ..mock_denning_data()[0].clone()
+        })
+        .collect()
+}
+
+#[test]
+fn test_diff_reports_added_removed_and_changed_rows() {
+    let old = WolfDataset::new(release(), mock_reproductive_data());
+    let mut new_denning = release();
+    new_denning.remove(0);
+    new_denning[0].denning_doy = 135;
+    new_denning[0].winter_tmax = None;
+    new_denning[1].winter_tmax = Celsius::new(12.5).ok();
+    new_denning.push(DenningPhenology {
+        uid: 4,
+        ..release()[0].clone()
+    });
+    let new = WolfDataset::new(new_denning, mock_reproductive_data());
+
+    let diff = diff_datasets(&old, &new).unwrap();
+    assert!(diff.reproduction.is_empty());
+    let denning = &diff.denning;
+    assert_eq!(denning.added[0].key, RowKey::Uid(4));
+    assert_eq!(denning.removed[0].key, RowKey::Uid(1));
+    assert_eq!(denning.changed.len(), 2);
+    assert_eq!(denning.unchanged, 0);
+
+    let uid2 = &denning.changed[0];
+    assert_eq!(uid2.key, RowKey::Uid(2));
+    let columns: Vec<&str> = uid2.changes.iter().map(|c| c.column.as_str()).collect();
+    assert_eq!(columns, ["denning_doy", "winter_tmax"]);
+    assert_eq!(uid2.changes[1].new, Value::Null);
+
+    // Missing values are changes but not deltas
+    let deltas: Vec<(&str, f64)> = denning
+        .deltas
+        .iter()
+        .map(|d| (d.column.as_str(), d.mean_delta))
+        .collect();
+    assert_eq!(deltas, [("denning_doy", 5.0), ("winter_tmax", 2.5)]);
+}
+
+#[test]
+fn test_season_key_matches_renumbered_uids() {
+    let old = WolfDataset::new(release(), Vec::new());
+    let renumbered: Vec<_> = release()
+        .into_iter()
+        .map(|d| DenningPhenology {
+            uid: d.uid + 100,
+            ..d
+        })
+        .collect();
+    let new = WolfDataset::new(renumbered, Vec::new());
+
+    let by_uid = diff_datasets(&old, &new).unwrap();
+    assert_eq!(
+        (by_uid.denning.added.len(), by_uid.denning.removed.len()),
+        (3, 3)
+    );
+
+    let options = DiffOptions {
+        key: DiffKey::Season,
+    };
+    let by_season = options.diff_datasets(&old, &new).unwrap();
+    assert!(by_season.denning.added.is_empty());
+    assert_eq!(by_season.denning.changed.len(), 3);
+    assert!(by_season.denning.changed[0]
+        .changes
+        .iter()
+        .all(|c| c.column == "uid"));
+}
+
+#[test]
+fn test_reports_render_as_markdown_and_json() {
+    let old = WolfDataset::new(release(), Vec::new());
+    let mut changed = release();
+    changed[0].study = "Study | B".to_string();
+    changed[0].denning_doy = 140;
+    let options = DiffOptions {
+        key: DiffKey::Season,
+    };
+    let diff = options
+        .diff_datasets(&old, &WolfDataset::new(changed, Vec::new()))
+        .unwrap();
+
+    let mut markdown = Vec::new();
+    diff.write_markdown(&mut markdown).unwrap();
+    let markdown = String::from_utf8(markdown).unwrap();
+    assert!(markdown.starts_with("# Dataset diff\n"));
+    assert!(markdown.contains("| Study \\| B / pack 1, 2020 | Study \\| B |"));
+    assert!(markdown.contains("1 added, 1 removed, 0 changed, 2 unchanged"));
+
+    let mut json = Vec::new();
+    diff.write_json(&mut json).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(json["denning"]["key"], "season");
+    assert_eq!(
+        json["denning"]["added"][0]["key"],
+        "Study | B / pack 1, 2020"
+    );
+}
+
+#[test]
+fn test_deltas_cover_covariates_but_not_key_columns() {
+    let old = release();
+    let mut new: Vec<_> = release()
+        .into_iter()
+        .map(|d| DenningPhenology {
+            uid: d.uid + 100,
+            pack_id: d.pack_id + 10,
+            ..d
+        })
+        .collect();
+    new[0].denning_doy += 3;
+    new[0].annual_pdo = new[0].annual_pdo.map(|v| v + 0.5);
+
+    let by_uid = DiffOptions::default().diff_records(&old, &new).unwrap();
+    assert!(by_uid.deltas.is_empty(), "no uid appears in both releases");
+
+    let old: Vec<_> = old
+        .into_iter()
+        .map(|d| DenningPhenology {
+            pack_id: d.pack_id + 10,
+            ..d
+        })
+        .collect();
+    let options = DiffOptions {
+        key: DiffKey::Season,
+    };
+    let by_season = options.diff_records(&old, &new).unwrap();
+    let changed: Vec<&str> = by_season.changed[0]
+        .changes
+        .iter()
+        .map(|c| c.column.as_str())
+        .collect();
+    assert_eq!(changed, ["uid", "denning_doy", "annual_pdo"]);
+    let deltas: Vec<(&str, f64)> = by_season
+        .deltas
+        .iter()
+        .map(|d| (d.column.as_str(), d.mean_delta))
+        .collect();
+    assert_eq!(deltas, [("annual_pdo", 0.5)]);
+
+    let by_uid = DiffOptions::default()
+        .diff_records(&release(), &{
+            let mut moved = release();
+            moved[1].pack_id = 7;
+            moved[1].denning_doy -= 4;
+            moved
+        })
+        .unwrap();
+    let deltas: Vec<&str> = by_uid.deltas.iter().map(|d| d.column.as_str()).collect();
+    assert_eq!(deltas, ["denning_doy"], "pack_id is an identifier");
+}
+
+#[test]
+fn test_duplicate_keys_compare_only_the_first_record() {
+    let mut old = release();
+    old.push(DenningPhenology {
+        denning_doy: 150,
+        ..old[0].clone()
+    });
+    let mut new = release();
+    new[0].denning_doy += 2;
+    new.push(DenningPhenology {
+        uid: 9,
+        ..new[2].clone()
+    });
+
+    let diff = DiffOptions::default().diff_records(&old, &new).unwrap();
+    assert_eq!(diff.duplicate_keys, [RowKey::Uid(1)]);
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(diff.changed[0].changes[0].old, Value::Integer(130));
+    assert_eq!(diff.added[0].key, RowKey::Uid(9));
+    assert_eq!((diff.old_rows, diff.new_rows, diff.unchanged), (4, 4, 2));
+
+    let options = DiffOptions {
+        key: DiffKey::Season,
+    };
+    let diff = options.diff_records(&old, &new).unwrap();
+    let season = |pack| RowKey::Season(PackKey::new("Study A", pack), 2020);
+    assert_eq!(diff.duplicate_keys, [season(1), season(3)]);
+
+    let mut markdown = Vec::new();
+    let both = DatasetDiff {
+        denning: diff,
+        reproduction: DiffOptions::default()
+            .diff_records(&mock_reproductive_data(), &mock_reproductive_data())
+            .unwrap(),
+    };
+    both.write_markdown(&mut markdown).unwrap();
+    let markdown = String::from_utf8(markdown).unwrap();
+    assert!(markdown.contains(
+        "Duplicate keys (only the first record compared): Study A / pack 1, 2020; Study A / pack 3, 2020"
+    ));
+}
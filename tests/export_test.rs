@@ -0,0 +1,176 @@
+mod common;
+
+use std::fs::File;
+
+use chrono::NaiveDate;
+use common::{mock_denning_data, mock_reproductive_data};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use wolf_project_210::data::{join_pack_seasons, read_denning_csv, read_denning_from_reader, JoinKind};
+use wolf_project_210::error::WolfDataError;
+use wolf_project_210::export::{
+    export_table, write_csv, write_json_lines, Column, ColumnType, ExportFormat, Table, Value,
+};
+
+#[test]
+fn test_csv_export_round_trips_with_original_headers() {
+    let (denning, _) = read_denning_csv("data/Wolf_DenningPhenology_AK_CA.csv").unwrap();
+    let table = Table::from_records(&denning).unwrap();
+
+    let mut csv = Vec::new();
+    write_csv(&table, &mut csv).unwrap();
+    let text = String::from_utf8(csv).unwrap();
+    let original = std::fs::read_to_string("data/Wolf_DenningPhenology_AK_CA.csv").unwrap();
+    assert_eq!(text.lines().next(), original.lines().next(), "Headers should match");
+
+    let (reloaded, report) = read_denning_from_reader(text.as_bytes()).unwrap();
+    assert_eq!(report.rejected_count(), 0);
+    assert_eq!(reloaded.len(), denning.len());
+    assert_eq!(reloaded[0].denning_date, denning[0].denning_date);
+    assert_eq!(reloaded[0].ti_ndvi_prev1, denning[0].ti_ndvi_prev1);
+    assert_eq!(reloaded[0].winter_swe, denning[0].winter_swe);
+}
+
+#[test]
+fn test_json_lines_keep_column_order_and_iso_dates() {
+    let table = Table::from_records(&mock_reproductive_data()).unwrap();
+    let mut out = Vec::new();
+    write_json_lines(&table, &mut out).unwrap();
+    let line = String::from_utf8(out).unwrap();
+
+    assert!(line.starts_with(r#"{"uid":1,"study":"Study A""#));
+    assert!(line.contains(r#""start_date":"2020-06-01""#));
+    assert!(line.contains(r#""tiNDVI_prev1":0.5"#));
+    assert!(line.ends_with("}\n"));
+}
+
+#[test]
+fn test_pack_season_table_exports_to_parquet() {
+    let mut denning = mock_denning_data();
+    let mut unmatched = denning[0].clone();
+    unmatched.pack_id = 2;
+    denning.push(unmatched);
+    let join = join_pack_seasons(&denning, &mock_reproductive_data(), JoinKind::Left);
+    let table = Table::from_pack_seasons(&join.seasons).unwrap();
+
+    assert_eq!(table.len(), 2);
+    let success = table.column_index("success").unwrap();
+    assert_eq!(table.rows()[0][success], Value::Integer(1));
+    assert_eq!(table.rows()[1][success], Value::Null);
+    assert!(table.column_index("repro_fall_tmax").is_some());
+
+    let path = std::env::temp_dir().join("wolf_export_test.parquet");
+    export_table(&table, &path).unwrap();
+    let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+    let metadata = reader.metadata().file_metadata();
+    assert_eq!(metadata.num_rows(), 2);
+    let names: Vec<_> = metadata
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|c| c.name().to_string())
+        .collect();
+    assert_eq!(names.len(), table.columns().len());
+    assert_eq!(names[1], "uid");
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_hand_built_tables_check_row_width_and_write_nulls() {
+    let mut table = Table::new(vec![
+        Column::new("pack", ColumnType::Text),
+        Column::new("doy", ColumnType::Integer),
+        Column::new("swe", ColumnType::Float),
+        Column::new("date", ColumnType::Date),
+    ]);
+    let date = NaiveDate::from_ymd_opt(2021, 4, 2).unwrap();
+    table
+        .push_row(vec![
+            Value::Text("a, b".to_string()),
+            Value::Integer(120),
+            Value::Float(2.5),
+            Value::Date(date),
+        ])
+        .unwrap();
+    table
+        .push_row(vec![
+            Value::Null,
+            Value::Integer(130),
+            Value::Null,
+            Value::Null,
+        ])
+        .unwrap();
+    let error = table.push_row(vec![Value::Integer(1)]).unwrap_err();
+    assert!(
+        error.to_string().contains("1 values but the table has 4"),
+        "{}",
+        error
+    );
+    assert_eq!(table.len(), 2);
+
+    assert_eq!(table.series("doy", "swe"), [(120.0, 2.5)]);
+    assert!(table.series("doy", "missing").is_empty());
+    assert_eq!(Value::Text("1".to_string()).as_f64(), None);
+
+    let mut csv = Vec::new();
+    write_csv(&table, &mut csv).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "pack,doy,swe,date\n\"a, b\",120,2.5,2021-04-02\n,130,,\n"
+    );
+    let mut json = Vec::new();
+    write_json_lines(&table, &mut json).unwrap();
+    let lines: Vec<String> = String::from_utf8(json)
+        .unwrap()
+        .lines()
+        .map(String::from)
+        .collect();
+    assert_eq!(
+        lines[1],
+        r#"{"pack":null,"doy":130,"swe":null,"date":null}"#
+    );
+
+    let empty = Table::new(table.columns().to_vec());
+    let mut json = Vec::new();
+    write_json_lines(&empty, &mut json).unwrap();
+    assert!(json.is_empty());
+}
+
+#[test]
+fn test_export_format_follows_the_file_extension() {
+    assert_eq!(ExportFormat::from_path("a/b.CSV"), Some(ExportFormat::Csv));
+    assert_eq!(
+        ExportFormat::from_path("b.ndjson"),
+        Some(ExportFormat::JsonLines)
+    );
+    assert_eq!(
+        ExportFormat::from_path("b.jsonl"),
+        Some(ExportFormat::JsonLines)
+    );
+    assert_eq!(
+        ExportFormat::from_path("b.parquet"),
+        Some(ExportFormat::Parquet)
+    );
+    assert_eq!(ExportFormat::from_path("b.xlsx"), None);
+    assert_eq!(ExportFormat::from_path("no_extension"), None);
+
+    let table = Table::from_records(&mock_denning_data()).unwrap();
+    let error = export_table(&table, std::env::temp_dir().join("wolf_export.xlsx")).unwrap_err();
+    assert!(
+        error.to_string().contains("use .csv, .jsonl or .parquet"),
+        "{}",
+        error
+    );
+
+    let missing_dir = std::env::temp_dir()
+        .join("wolf_no_such_dir")
+        .join("out.csv");
+    let error = export_table(&table, &missing_dir).unwrap_err();
+    assert!(matches!(error, WolfDataError::Io { path: Some(_), .. }));
+
+    let path = std::env::temp_dir().join("wolf_export_test.jsonl");
+    export_table(&table, &path).unwrap();
+    let written = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(written.lines().count(), 1);
+    assert!(written.contains(r#""denning_date":"2020-05-10""#));
+    std::fs::remove_file(path).ok();
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use chrono::Datelike;
+use common::{mock_denning_data, mock_reproductive_data};
+use wolf_project_210::data::{join_pack_seasons, JoinKind};
+use wolf_project_210::features::{to_feature_matrix, FeatureOptions, StudyEncoding};
+use wolf_project_210::missing::{ImputeMethod, MissingPolicy};
+use wolf_project_210::units::{Quantity, SweMm};
+
+fn three_packs() -> Vec<wolf_project_210::data::DenningPhenology> {
+    let mut records = mock_denning_data();
+    let mut second = records[0].clone();
+    second.uid = 2;
+    second.pack_id = 2;
+    second.winter_swe = None;
+    let mut third = records[0].clone();
+    third.uid = 3;
+    third.pack_id = 3;
+    third.study = "Study B".to_string();
+    third.denning_doy = 110;
+    records.extend([second, third]);
+    records
+}
+
+#[test]
+fn test_default_matrix_drops_incomplete_rows() {
+    let records = three_packs();
+    let (matrix, columns, rows) =
+        to_feature_matrix(&records, &["denning_doy", "winter_swe"]).unwrap();
+
+    assert_eq!(matrix.dim(), (2, 2));
+    assert_eq!(columns, vec!["denning_doy", "winter_swe"]);
+    assert_eq!(rows.iter().map(|r| r.index).collect::<Vec<_>>(), vec![0, 2]);
+    assert_eq!(rows[1].uid, 3);
+    assert_eq!(matrix[[1, 0]], 110.0);
+
+    assert!(
+        to_feature_matrix(&records, &["success"]).is_err(),
+        "Not a denning column"
+    );
+}
+
+#[test]
+fn test_imputation_standardization_and_study_encoding() {
+    let records = three_packs();
+    let options = FeatureOptions {
+        missing: MissingPolicy::Impute(ImputeMethod::StudyMean),
+        standardize: true,
+        study: StudyEncoding::OneHot,
+    };
+    let (matrix, columns, rows) = options
+        .to_matrix(&records, &["denning_doy", "winter_swe"])
+        .unwrap();
+
+    assert_eq!(rows.len(), 3);
+    assert_eq!(columns[2..], ["study:Study A", "study:Study B"]);
+    // Study A's mean fills pack 2, so every winter_swe is 250 and the column is constant
+    assert!(matrix.column(1).iter().all(|v| *v == 0.0));
+    let doy = matrix.column(0);
+    assert!(doy.sum().abs() < 1e-9, "Standardized columns are centred");
+    assert_eq!(matrix.row(2).to_vec()[2..], [0.0, 1.0]);
+
+    let dummy = FeatureOptions {
+        study: StudyEncoding::Dummy,
+        ..FeatureOptions::default()
+    };
+    let (_, columns, _) = dummy.to_matrix(&records, &["denning_doy"]).unwrap();
+    assert_eq!(columns, vec!["denning_doy", "study:Study B"]);
+
+    let keep = FeatureOptions {
+        missing: MissingPolicy::KeepNone,
+        ..FeatureOptions::default()
+    };
+    let (matrix, _, _) = keep.to_matrix(&records, &["winter_swe"]).unwrap();
+    assert!(matrix[[1, 0]].is_nan());
+}
+
+#[test]
+fn test_pack_season_matrix() {
+    let join = join_pack_seasons(
+        &mock_denning_data(),
+        &mock_reproductive_data(),
+        JoinKind::Inner,
+    );
+    let (matrix, _, rows) = to_feature_matrix(
+        &join.seasons,
+        &["denning_doy", "success", "winter_swe", "repro_winter_swe"],
+    )
+    .unwrap();
+    assert_eq!(matrix.row(0).to_vec(), vec![130.0, 1.0, 250.0, 200.0]);
+    assert_eq!(rows[0].year, 2020);
+}
+
+#[test]
+fn test_matrix_imputation_matches_load_time_imputation() {
+    let mut records = three_packs();
+    let mut later = records[1].clone();
+    later.uid = 4;
+    later.winter_swe = None;
+    later.denning_date = later.denning_date.with_year(2021).unwrap();
+    let mut earlier = later.clone();
+    earlier.uid = 5;
+    earlier.winter_swe = Some(SweMm::new(180.0).unwrap());
+    earlier.denning_date = earlier.denning_date.with_year(2019).unwrap();
+    records.extend([later, earlier]);
+
+    for method in [
+        ImputeMethod::StudyMean,
+        ImputeMethod::StudyMedian,
+        ImputeMethod::LastObservation,
+    ] {
+        let policy = MissingPolicy::Impute(method);
+        let options = FeatureOptions {
+            missing: policy.clone(),
+            ..FeatureOptions::default()
+        };
+        let (matrix, _, _) = options.to_matrix(&records, &["winter_swe"]).unwrap();
+        let (filled, _) = policy.apply_denning(records.clone());
+        let (expected, _, _) = to_feature_matrix(&filled, &["winter_swe"]).unwrap();
+        assert_eq!(matrix, expected, "{:?}", method);
+    }
+}
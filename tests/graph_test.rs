@@ -1,16 +1,18 @@
-use wolf_project_210::graph::{build_graph, compute_degree_centrality};
-use wolf_project_210::data::DenningPhenology;
+mod common;
+
 use chrono::NaiveDate;
+use common::mock_denning_data;
+use wolf_project_210::data::PackKey;
+use wolf_project_210::graph::{build_graph, compute_degree_centrality};
 
 
 #[test]
 fn test_build_graph() {
     let denning_data = mock_denning_data();
-    let reproductive_data = mock_reproductive_data();
+    let graph = build_graph(&denning_data);
 
-    
     assert!(graph.node_count() > 0, "Graph should have at least one node");
-    assert!(graph.edge_count() >= 0, "Graph should have at least zero edges");
+    assert_eq!(graph.edge_count(), 0, "A single pack should have no edges");
 }
 
 #[test]
@@ -21,3 +23,42 @@ fn test_compute_degree_centrality() {
     
     assert!(!centrality.is_empty(), "Centrality map should not be empty");
 }
+
+#[test]
+fn test_same_pack_id_in_two_studies_gives_two_nodes() {
+    let mut denning_data = mock_denning_data();
+    let mut other = denning_data[0].clone();
+    other.study = "Study B".to_string();
+    denning_data.push(other);
+
+    let graph = build_graph(&denning_data);
+    let centrality = compute_degree_centrality(&graph);
+
+    assert_eq!(graph.node_count(), 2, "Packs from different studies should not collapse");
+    assert_eq!(graph.edge_count(), 0, "Packs from different studies should not be linked");
+    assert!(centrality.contains_key(&PackKey::new("Study B", 1)));
+}
+
+#[test]
+fn test_degree_counts_neighbouring_packs_not_seasons() {
+    let template = mock_denning_data()[0].clone();
+    let mut denning_data = Vec::new();
+    // Three packs in one study with two seasons each, one pack elsewhere
+    for (study, pack_id) in [("Study A", 1), ("Study A", 2), ("Study A", 3), ("Study B", 1)] {
+        for year in [2010, 2011] {
+            let mut record = template.clone();
+            record.study = study.to_string();
+            record.pack_id = pack_id;
+            record.denning_date = NaiveDate::from_ymd_opt(year, 4, 20).unwrap();
+            denning_data.push(record);
+        }
+    }
+
+    let graph = build_graph(&denning_data);
+    let centrality = compute_degree_centrality(&graph);
+
+    assert_eq!(graph.node_count(), 4);
+    assert_eq!(graph.edge_count(), 3, "One edge per pair of packs in Study A");
+    assert_eq!(centrality[&PackKey::new("Study A", 2)], 2);
+    assert_eq!(centrality[&PackKey::new("Study B", 1)], 0);
+}
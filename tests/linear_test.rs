@@ -0,0 +1,281 @@
+mod common;
+
+use common::mock_denning_data;
+use wolf_project_210::data::DenningPhenology;
+use wolf_project_210::error::WolfDataError;
+use wolf_project_210::stats::linear::{
+    denning_regression, CovarianceKind, Estimator, LinearFit, LinearOptions,
+};
+use wolf_project_210::synthetic::SyntheticOptions;
+use wolf_project_210::units::{Celsius, Quantity};
+
+/// Four packs whose denning DOY rises with winter_tmax
+fn four_packs() -> Vec<DenningPhenology> {
+    let template = &mock_denning_data()[0];
+    [(0.0, 100), (1.0, 102), (2.0, 104), (3.0, 107)]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (tmax, doy))| {
+            let mut record = template.clone();
+            record.uid = i as u32 + 1;
+            record.pack_id = i as u32 + 1;
+            record.winter_tmax = Celsius::new(tmax).ok();
+            record.denning_doy = doy;
+            record
+        })
+        .collect()
+}
+
+#[test]
+fn test_matches_hand_computed_fit() {
+    let packs = four_packs();
+    let fit = denning_regression(&packs, &["winter_tmax"]).unwrap();
+    let slope = fit.coefficient("winter_tmax").unwrap();
+    assert!((slope.estimate - 2.3).abs() < 1e-9);
+    assert!((fit.coefficients[0].estimate - 99.8).abs() < 1e-9);
+    assert!((slope.std_error - (0.15f64 / 5.0).sqrt()).abs() < 1e-9);
+    assert!((fit.r_squared - (1.0 - 0.3 / 26.75)).abs() < 1e-9);
+    assert_eq!(fit.df_residual, 2);
+
+    let robust = LinearOptions {
+        covariance: CovarianceKind::Hc0,
+        ..LinearOptions::default()
+    }
+    .fit(&packs, "denning_doy", &["winter_tmax"])
+    .unwrap();
+    let slope = robust.coefficient("winter_tmax").unwrap();
+    assert!((slope.std_error - (0.335f64 / 25.0).sqrt()).abs() < 1e-9);
+
+    let error = denning_regression(&packs[..2], &["winter_tmax"]).unwrap_err();
+    assert!(error.to_string().contains("too few"), "{}", error);
+}
+
+#[test]
+fn test_recovers_planted_doy_effects_with_study_effects() {
+    let options = SyntheticOptions {
+        studies: 6,
+        ..SyntheticOptions::with_seed(21)
+    };
+    let dataset = options.dataset().unwrap();
+    let fit = LinearOptions {
+        study_effects: true,
+        covariance: CovarianceKind::Hc3,
+        ..LinearOptions::default()
+    }
+    .fit(
+        dataset.denning(),
+        "denning_doy",
+        &["winter_swe", "winter_tmax"],
+    )
+    .unwrap();
+
+    for (term, truth) in [("winter_swe", 0.05), ("winter_tmax", -0.8)] {
+        let c = fit.coefficient(term).unwrap();
+        assert!((c.estimate - truth).abs() < 3.0 * c.std_error, "{:?}", c);
+        assert!(c.p_value < 0.001, "{:?}", c);
+    }
+    assert_eq!(
+        fit.coefficients.len(),
+        3 + 5,
+        "one effect per non-reference study"
+    );
+    assert!(fit.r_squared > 0.0 && fit.r_squared < 1.0);
+    assert!(fit.f_p_value.unwrap() < 0.001);
+
+    let predicted = fit.predict(dataset.denning());
+    for (&row, fitted) in fit.rows.iter().zip(&fit.fitted) {
+        assert!((predicted[row].unwrap() - fitted).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn test_gls_diagnostics_and_exports() {
+    let dataset = SyntheticOptions::with_seed(4).dataset().unwrap();
+    let ols = denning_regression(dataset.denning(), &["winter_tmax"]).unwrap();
+    let gls = LinearOptions {
+        estimator: Estimator::StudyGls,
+        ..LinearOptions::default()
+    }
+    .fit(dataset.denning(), "denning_doy", &["winter_tmax"])
+    .unwrap();
+
+    let mut weights = gls.weights.clone();
+    weights.sort_by(f64::total_cmp);
+    weights.dedup();
+    assert_eq!(weights.len(), 4, "one weight per study");
+    let (a, b) = (
+        ols.coefficient("winter_tmax").unwrap(),
+        gls.coefficient("winter_tmax").unwrap(),
+    );
+    assert!((a.estimate - b.estimate).abs() < 2.0 * a.std_error);
+
+    let d = &ols.diagnostics;
+    assert!(d.mean.abs() < 1e-9, "OLS residuals sum to zero");
+    assert!(d.durbin_watson > 0.0 && d.durbin_watson < 4.0);
+    assert!(d.breusch_pagan_p.is_some());
+    assert!(d
+        .influential
+        .iter()
+        .all(|&i| ols.cooks_distance[i] > 4.0 / ols.n as f64));
+
+    assert_eq!(ols.residual_table().len(), ols.n);
+    assert_eq!(ols.coefficient_table().columns()[3].name, "t");
+    assert!(ols.to_string().contains("R²"));
+
+    let path = std::env::temp_dir().join("wolf_linear_residuals.png");
+    ols.plot_residuals(&path).unwrap();
+    assert!(path.exists());
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_robust_variants_intervals_and_intercept_only_fit() {
+    let packs = four_packs();
+    let fit_with = |covariance, confidence| {
+        LinearOptions {
+            covariance,
+            confidence,
+            ..LinearOptions::default()
+        }
+        .fit(&packs, "denning_doy", &["winter_tmax"])
+        .unwrap()
+    };
+    let se = |fit: &LinearFit| fit.coefficient("winter_tmax").unwrap().std_error;
+    let hc0 = se(&fit_with(CovarianceKind::Hc0, 0.95));
+    // HC1 scales HC0 by n / (n - p) = 4 / 2
+    assert!((se(&fit_with(CovarianceKind::Hc1, 0.95)) - hc0 * 2f64.sqrt()).abs() < 1e-9);
+    let hc2 = se(&fit_with(CovarianceKind::Hc2, 0.95));
+    let hc3 = se(&fit_with(CovarianceKind::Hc3, 0.95));
+    assert!(hc0 < hc2 && hc2 < hc3, "{} {} {}", hc0, hc2, hc3);
+
+    // t quantile with 2 degrees of freedom
+    let fit = fit_with(CovarianceKind::Classical, 0.95);
+    let slope = fit.coefficient("winter_tmax").unwrap();
+    let half_width = 4.302652729911275 * slope.std_error;
+    assert!((slope.lower - (slope.estimate - half_width)).abs() < 1e-6);
+    assert!((slope.upper - (slope.estimate + half_width)).abs() < 1e-6);
+    let narrow = fit_with(CovarianceKind::Classical, 0.8);
+    let narrow = narrow.coefficient("winter_tmax").unwrap();
+    assert!(narrow.upper - narrow.lower < slope.upper - slope.lower);
+
+    let mean_only = denning_regression(&packs, &[]).unwrap();
+    assert_eq!(mean_only.coefficients.len(), 1);
+    assert!((mean_only.coefficients[0].estimate - 103.25).abs() < 1e-9);
+    assert!(mean_only.r_squared.abs() < 1e-9);
+    assert_eq!(mean_only.f_statistic, None);
+    assert_eq!(mean_only.f_p_value, None);
+}
+
+#[test]
+fn test_invalid_designs_and_missing_rows() {
+    let mut packs = four_packs();
+    let column = |error: WolfDataError| match error {
+        WolfDataError::Validation { column, .. } => column,
+        other => panic!("Expected a validation error, got {:?}", other),
+    };
+    let unknown = denning_regression(&packs, &["snow_depth"]).unwrap_err();
+    assert_eq!(column(unknown), Some("snow_depth".to_string()));
+    let other_record = denning_regression(&packs, &["tiNDVI"]).unwrap_err();
+    assert_eq!(column(other_record), Some("tiNDVI".to_string()));
+    let response = LinearOptions::default()
+        .fit(&packs, "study", &["winter_tmax"])
+        .unwrap_err();
+    assert_eq!(column(response), Some("study".to_string()));
+
+    // A constant covariate cannot be separated from the intercept
+    let constant = denning_regression(&packs, &["annual_pdo"]).unwrap_err();
+    assert!(constant.to_string().contains("collinear"), "{}", constant);
+
+    // Study effects can use up the degrees of freedom the covariates left
+    for (i, pack) in packs.iter_mut().enumerate() {
+        pack.study = format!("Study {}", i);
+    }
+    let saturated = LinearOptions {
+        study_effects: true,
+        ..LinearOptions::default()
+    }
+    .fit(&packs, "denning_doy", &["winter_tmax"])
+    .unwrap_err();
+    assert!(
+        saturated
+            .to_string()
+            .contains("4 complete rows are too few to estimate 5"),
+        "{}",
+        saturated
+    );
+
+    let mut packs = four_packs();
+    packs.push(DenningPhenology {
+        uid: 5,
+        winter_tmax: None,
+        ..packs[3].clone()
+    });
+    packs.push(DenningPhenology {
+        uid: 6,
+        winter_tmax: Celsius::new(4.0).ok(),
+        denning_doy: 109,
+        ..packs[0].clone()
+    });
+    let fit = denning_regression(&packs, &["winter_tmax"]).unwrap();
+    assert_eq!((fit.n, fit.dropped), (5, 1));
+    assert_eq!(fit.rows, [0, 1, 2, 3, 5]);
+    let predicted = fit.predict(&packs);
+    assert_eq!(predicted[4], None);
+    assert!((predicted[5].unwrap() - fit.fitted[4]).abs() < 1e-9);
+}
+
+#[test]
+fn test_undefined_robust_errors_and_gls_weights() {
+    // A study of one pack has its own effect, which fits that row exactly
+    let mut packs = four_packs();
+    packs.push(DenningPhenology {
+        uid: 5,
+        study: "Study B".to_string(),
+        ..packs[3].clone()
+    });
+    let with = |covariance| {
+        LinearOptions {
+            covariance,
+            study_effects: true,
+            ..LinearOptions::default()
+        }
+        .fit(&packs, "denning_doy", &["winter_tmax"])
+    };
+    assert!(with(CovarianceKind::Classical).is_ok());
+    assert!(with(CovarianceKind::Hc0).is_ok());
+    for kind in [CovarianceKind::Hc2, CovarianceKind::Hc3] {
+        let error = with(kind).unwrap_err();
+        assert!(
+            error.to_string().contains("row 4 has leverage 1"),
+            "{}",
+            error
+        );
+    }
+
+    let gls = LinearOptions {
+        estimator: Estimator::StudyGls,
+        ..LinearOptions::default()
+    };
+    let mut exact = four_packs();
+    for pack in &mut exact {
+        pack.denning_doy = 100 + 2 * pack.winter_tmax.unwrap().value() as u16;
+    }
+    let error = gls
+        .fit(&exact, "denning_doy", &["winter_tmax"])
+        .unwrap_err();
+    assert!(error.to_string().contains("study Study A"), "{}", error);
+
+    // Two studies: GLS estimates two variances where OLS has one
+    let dataset = SyntheticOptions {
+        studies: 2,
+        ..SyntheticOptions::with_seed(4)
+    }
+    .dataset()
+    .unwrap();
+    let ols = denning_regression(dataset.denning(), &["winter_tmax"]).unwrap();
+    let fit = gls
+        .fit(dataset.denning(), "denning_doy", &["winter_tmax"])
+        .unwrap();
+    assert!((ols.aic - (-2.0 * ols.log_likelihood + 2.0 * 3.0)).abs() < 1e-9);
+    assert!((fit.aic - (-2.0 * fit.log_likelihood + 2.0 * 4.0)).abs() < 1e-9);
+}
@@ -0,0 +1,198 @@
+use ndarray::{Array1, Array2};
+use wolf_project_210::data::JoinKind;
+use wolf_project_210::error::WolfDataError;
+use wolf_project_210::stats::logistic::{logistic_regression, LogisticOptions};
+use wolf_project_210::stats::{Design, INTERCEPT};
+use wolf_project_210::synthetic::{synthetic_dataset, PlantedEffects, SyntheticOptions};
+
+#[test]
+fn test_recovers_planted_success_effects() {
+    let defaults = SyntheticOptions::default();
+    let options = SyntheticOptions {
+        seed: 11,
+        studies: 8,
+        success: PlantedEffects {
+            study_sd: 0.0,
+            ..defaults.success.clone()
+        },
+        ..defaults
+    };
+    let seasons = options
+        .dataset()
+        .unwrap()
+        .join_pack_seasons(JoinKind::Inner)
+        .seasons;
+    let fit = logistic_regression(&seasons, &["denning_doy", "winter_swe"]).unwrap();
+    assert!(fit.converged);
+    assert_eq!((fit.n, fit.dropped), (1600, 0));
+
+    for (term, truth) in [("denning_doy", -0.03), ("winter_swe", -0.005)] {
+        let c = fit.coefficient(term).unwrap();
+        assert!((c.estimate - truth).abs() < 3.0 * c.std_error, "{:?}", c);
+        assert!(c.p_value < 0.05, "{:?}", c);
+    }
+    let doy = &fit.odds_ratios()[1];
+    assert!(doy.odds_ratio < 1.0 && doy.upper < 1.0, "{:?}", doy);
+}
+
+#[test]
+fn test_fit_statistics_are_consistent() {
+    let dataset = synthetic_dataset(5);
+    let fit = logistic_regression(dataset.reproduction(), &["winter_swe", "summer_tmax"]).unwrap();
+
+    // With an intercept the fitted probabilities sum to the successes
+    let successes = dataset
+        .reproduction()
+        .iter()
+        .filter(|r| r.success == 1)
+        .count();
+    let expected: f64 = fit.fitted.iter().sum();
+    assert!((expected - successes as f64).abs() < 1e-6, "{}", expected);
+
+    assert!(fit.deviance <= fit.null_deviance);
+    assert!((fit.aic - (fit.deviance + 6.0)).abs() < 1e-9);
+    assert_eq!(fit.coefficients[0].term, INTERCEPT);
+    let c = &fit.coefficients[1];
+    assert!((c.statistic - c.estimate / c.std_error).abs() < 1e-12);
+
+    let predicted = fit.predict(dataset.reproduction());
+    for (&row, fitted) in fit.rows.iter().zip(&fit.fitted) {
+        assert!((predicted[row].unwrap() - fitted).abs() < 1e-12);
+    }
+
+    let table = fit.coefficient_table();
+    assert_eq!(table.len(), 3);
+    assert!(table.column_index("odds_ratio").is_some());
+    assert!(fit.to_string().contains("AIC"));
+}
+
+#[test]
+fn test_invalid_models_are_rejected() {
+    let dataset = synthetic_dataset(5);
+    let options = LogisticOptions::default();
+
+    let error = options
+        .fit(dataset.denning(), "denning_doy", &["winter_swe"])
+        .unwrap_err();
+    assert!(error.to_string().contains("0/1 response"), "{}", error);
+
+    let error = logistic_regression(dataset.reproduction(), &["snow_depth"]).unwrap_err();
+    assert!(error.to_string().contains("snow_depth"), "{}", error);
+
+    let error =
+        logistic_regression(dataset.reproduction(), &["winter_swe", "winter_swe"]).unwrap_err();
+    assert!(error.to_string().contains("collinear"), "{}", error);
+
+    let mut all_successful = dataset.reproduction().to_vec();
+    for record in &mut all_successful {
+        record.success = 1;
+    }
+    match logistic_regression(&all_successful, &["winter_swe"]).unwrap_err() {
+        WolfDataError::Validation {
+            column, message, ..
+        } => {
+            assert_eq!(column.as_deref(), Some("success"));
+            assert!(message.contains("both outcomes"), "{}", message);
+        }
+        other => panic!("Expected a validation error, got {:?}", other),
+    }
+
+    let empty = Design {
+        response: "success".to_string(),
+        terms: vec![INTERCEPT.to_string()],
+        x: Array2::zeros((0, 1)),
+        y: Array1::zeros(0),
+        rows: Vec::new(),
+        groups: Vec::new(),
+        dropped: 0,
+    };
+    let error = options.fit_design(empty).unwrap_err();
+    assert!(error.to_string().contains("at least one row"), "{}", error);
+}
+
+/// Eight rows of `y ~ x` with one success of four at `x = 0` and three of
+/// four at `x = 1`
+fn two_by_two() -> Design {
+    let x = [0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0];
+    let y = [1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 0.0];
+    Design {
+        response: "success".to_string(),
+        terms: vec![INTERCEPT.to_string(), "x".to_string()],
+        x: Array2::from_shape_fn((8, 2), |(i, j)| if j == 0 { 1.0 } else { x[i] }),
+        y: Array1::from(y.to_vec()),
+        rows: (0..8).collect(),
+        groups: Vec::new(),
+        dropped: 0,
+    }
+}
+
+#[test]
+fn test_two_by_two_table_matches_closed_form() {
+    let fit = LogisticOptions::default().fit_design(two_by_two()).unwrap();
+    assert!(fit.converged);
+    assert_eq!(fit.df_residual(), 6);
+
+    // The log odds of each group and the log of their ratio
+    let ln3 = 3f64.ln();
+    let (intercept, slope) = (&fit.coefficients[0], fit.coefficient("x").unwrap());
+    assert!((intercept.estimate + ln3).abs() < 1e-6, "{:?}", intercept);
+    assert!((slope.estimate - 2.0 * ln3).abs() < 1e-6, "{:?}", slope);
+    // Woolf's standard error: sqrt(1/1 + 1/3 + 1/3 + 1/1)
+    assert!((slope.std_error - (8.0f64 / 3.0).sqrt()).abs() < 1e-6);
+    let odds = &fit.odds_ratios()[1];
+    assert!((odds.odds_ratio - 9.0).abs() < 1e-5);
+    assert!((odds.lower - slope.lower.exp()).abs() < 1e-12);
+    assert!(odds.lower < 1.0 && 1.0 < odds.upper, "{:?}", odds);
+
+    let expected = [0.25, 0.25, 0.25, 0.25, 0.75, 0.75, 0.75, 0.75];
+    for (fitted, expected) in fit.fitted.iter().zip(expected) {
+        assert!((fitted - expected).abs() < 1e-6);
+    }
+    let deviance = -2.0 * 2.0 * (0.25f64.ln() + 3.0 * 0.75f64.ln());
+    assert!((fit.deviance - deviance).abs() < 1e-6);
+    assert!((fit.null_deviance - 16.0 * 2f64.ln()).abs() < 1e-9);
+    assert!((fit.log_likelihood + fit.deviance / 2.0).abs() < 1e-12);
+
+    let narrow = LogisticOptions {
+        confidence: 0.8,
+        ..LogisticOptions::default()
+    }
+    .fit_design(two_by_two())
+    .unwrap();
+    let narrow = narrow.coefficient("x").unwrap();
+    assert!(narrow.upper - narrow.lower < slope.upper - slope.lower);
+    assert_eq!(narrow.p_value, slope.p_value);
+}
+
+#[test]
+fn test_iteration_limit_and_missing_covariates() {
+    let fit = LogisticOptions {
+        max_iterations: 1,
+        ..LogisticOptions::default()
+    }
+    .fit_design(two_by_two())
+    .unwrap();
+    assert_eq!(fit.iterations, 1);
+    assert!(!fit.converged);
+    assert!(fit.to_string().contains(", not converged)"), "{}", fit);
+
+    let mut records = synthetic_dataset(5).reproduction().to_vec();
+    records[0].winter_swe = None;
+    records[3].winter_swe = None;
+    let fit = logistic_regression(&records, &["winter_swe"]).unwrap();
+    assert_eq!((fit.n, fit.dropped), (records.len() - 2, 2));
+    assert_eq!(&fit.rows[..2], [1, 2]);
+    let predicted = fit.predict(&records);
+    assert_eq!((predicted[0], predicted[3]), (None, None));
+    assert!(predicted.iter().flatten().all(|p| 0.0 < *p && *p < 1.0));
+    assert!(fit.to_string().starts_with(&format!(
+        "Logistic regression of success (n = {}, 2 dropped)",
+        fit.n
+    )));
+
+    // An intercept-only model fits the overall success rate
+    let mean_only = logistic_regression(&records, &[]).unwrap();
+    let rate = records.iter().filter(|r| r.success == 1).count() as f64 / records.len() as f64;
+    assert!((mean_only.fitted[0] - rate).abs() < 1e-9);
+    assert!((mean_only.deviance - mean_only.null_deviance).abs() < 1e-6);
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use std::fs::File;
+
+use chrono::NaiveDate;
+use common::mock_denning_data;
+use wolf_project_210::data::LoadOptions;
+use wolf_project_210::missing::{ImputeMethod, MissingPolicy};
+use wolf_project_210::units::{Millimetres, SweMm, Quantity};
+
+const DENNING_CSV: &str = "data/Wolf_DenningPhenology_AK_CA.csv";
+
+#[test]
+fn test_policies_at_load_time() {
+    let load = |policy: MissingPolicy| {
+        let file = File::open(DENNING_CSV).unwrap();
+        LoadOptions::with_missing_policy(policy)
+            .read_denning(file)
+            .unwrap()
+    };
+
+    let (drop_any, report) = load(MissingPolicy::DropAny);
+    assert_eq!(report.missing.dropped, 3);
+
+    let (keep, report) = load(MissingPolicy::KeepNone);
+    assert_eq!(keep.len(), drop_any.len() + 3);
+    assert_eq!(keep.iter().filter(|d| d.los_prev1.is_none()).count(), 3);
+    assert_eq!(report.missing.policy, MissingPolicy::KeepNone);
+
+    let (required, _) = load(MissingPolicy::require(&["denning_doy", "winter_swe"]));
+    assert_eq!(required.len(), keep.len());
+
+    let (imputed, report) = load(MissingPolicy::Impute(ImputeMethod::StudyMean));
+    assert_eq!(imputed.len(), keep.len());
+    assert!(imputed.iter().all(|d| d.los_prev1.is_some()));
+    assert_eq!(report.missing.imputed["los_prev1"], 3);
+}
+
+#[test]
+fn test_last_observation_carries_forward_within_pack() {
+    let mut first = mock_denning_data().remove(0);
+    first.winter_swe = SweMm::new(300.0).ok();
+    let mut second = first.clone();
+    second.uid = 2;
+    second.denning_date = NaiveDate::from_ymd_opt(2021, 5, 10).unwrap();
+    second.winter_swe = None;
+    let mut other_pack = second.clone();
+    other_pack.uid = 3;
+    other_pack.pack_id = 2;
+
+    let policy = MissingPolicy::Impute(ImputeMethod::LastObservation);
+    let (records, report) = policy.apply_denning(vec![second, other_pack, first]);
+
+    assert_eq!(records[0].winter_swe, SweMm::new(300.0).ok());
+    assert_eq!(
+        records[1].winter_swe, None,
+        "Other packs should not borrow values"
+    );
+    assert_eq!(report.imputed["winter_swe"], 1);
+}
+
+#[test]
+fn test_drop_if_missing_at_analysis_time() {
+    let mut records = mock_denning_data();
+    let mut gap = records[0].clone();
+    gap.sos_prev1 = None;
+    records.push(gap);
+
+    let (kept, report) = MissingPolicy::require(&["winter_swe"]).apply_denning(records.clone());
+    assert_eq!((kept.len(), report.dropped), (2, 0));
+
+    let (kept, report) = MissingPolicy::require(&["sos_prev1"]).apply_denning(records);
+    assert_eq!((kept.len(), report.dropped), (1, 1));
+}
+
+#[test]
+fn test_out_of_range_fills_are_not_counted() {
+    let mut records = mock_denning_data();
+    records[0].fall_prcp = Millimetres::new(1.5e308).ok();
+    let mut second = records[0].clone();
+    second.uid = 2;
+    let mut gap = records[0].clone();
+    gap.uid = 3;
+    gap.fall_prcp = None;
+    gap.winter_swe = None;
+    records.extend([second, gap]);
+
+    // The study mean of fall_prcp overflows to infinity, which no
+    // precipitation value can hold
+    let policy = MissingPolicy::Impute(ImputeMethod::StudyMean);
+    let (records, report) = policy.apply_denning(records);
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[2].fall_prcp, None);
+    assert!(!report.imputed.contains_key("fall_prcp"));
+    assert_eq!(records[2].winter_swe, records[0].winter_swe);
+    assert_eq!(report.imputed["winter_swe"], 1);
+    assert_eq!(
+        report.to_string(),
+        "impute with study mean, 1 values imputed"
+    );
+}
@@ -0,0 +1,244 @@
+use wolf_project_210::data::JoinKind;
+use wolf_project_210::error::WolfDataError;
+use wolf_project_210::export::Value;
+use wolf_project_210::stats::mixed::{linear_mixed, logistic_mixed, Family, MixedOptions};
+use wolf_project_210::synthetic::{synthetic_dataset, SyntheticOptions};
+
+#[test]
+fn test_balanced_one_way_reml_matches_anova() {
+    let dataset = synthetic_dataset(8);
+    let records = dataset.denning();
+    let fit = MixedOptions {
+        pack_effects: false,
+        tolerance: 1e-10,
+        ..MixedOptions::default()
+    }
+    .fit_linear(records, "denning_doy", &[])
+    .unwrap();
+
+    // Four studies of 200 seasons each: σ²_e = MSW, σ²_study = (MSB − MSW) / 200
+    let mut by_study = std::collections::BTreeMap::<&str, Vec<f64>>::new();
+    for r in records {
+        by_study
+            .entry(&r.study)
+            .or_default()
+            .push(r.denning_doy as f64);
+    }
+    let grand = records.iter().map(|r| r.denning_doy as f64).sum::<f64>() / 800.0;
+    let (mut within, mut between) = (0.0, 0.0);
+    for values in by_study.values() {
+        assert_eq!(values.len(), 200);
+        let mean = values.iter().sum::<f64>() / 200.0;
+        within += values.iter().map(|v| (v - mean).powi(2)).sum::<f64>();
+        between += 200.0 * (mean - grand).powi(2);
+    }
+    let (msw, msb) = (within / 796.0, between / 3.0);
+    assert!(msb > msw);
+    let v = &fit.variance;
+    assert!(
+        (v.residual - msw).abs() < 1e-4 * msw,
+        "{} vs {}",
+        v.residual,
+        msw
+    );
+    let study = (msb - msw) / 200.0;
+    assert!(
+        (v.study - study).abs() < 1e-3 * study,
+        "{} vs {}",
+        v.study,
+        study
+    );
+    assert_eq!(v.pack, 0.0);
+    assert!((fit.coefficients[0].estimate - grand).abs() < 1e-6);
+}
+
+#[test]
+fn test_recovers_planted_variance_components() {
+    let options = SyntheticOptions {
+        studies: 8,
+        ..SyntheticOptions::with_seed(3)
+    };
+    let dataset = options.dataset().unwrap();
+    let covariates = ["winter_swe", "winter_tmax"];
+    let fit = linear_mixed(dataset.denning(), "denning_doy", &covariates).unwrap();
+    assert!(fit.converged);
+    assert_eq!((fit.studies, fit.packs, fit.n), (8, 80, 1600));
+
+    for (term, truth) in [("winter_swe", 0.05), ("winter_tmax", -0.8)] {
+        let c = fit.coefficient(term).unwrap();
+        assert!((c.estimate - truth).abs() < 3.0 * c.std_error, "{:?}", c);
+    }
+    // Planted sds: study 5, pack 2, residual 8
+    let v = &fit.variance;
+    assert!((2.5..10.0).contains(&v.study.sqrt()), "{:?}", v);
+    assert!((1.0..3.5).contains(&v.pack.sqrt()), "{:?}", v);
+    assert!((7.5..8.5).contains(&v.residual.sqrt()), "{:?}", v);
+    assert!(0.0 < fit.icc_study && fit.icc_study < fit.icc_pack && fit.icc_pack < 1.0);
+
+    let conditional = fit.predict_conditional(dataset.denning());
+    let marginal = fit.predict(dataset.denning());
+    for (&row, fitted) in fit.rows.iter().zip(&fit.fitted) {
+        assert!((conditional[row].unwrap() - fitted).abs() < 1e-9);
+    }
+    let study_effects: f64 = fit.study_effects.values().sum();
+    assert!(
+        study_effects.abs() < 1.0,
+        "BLUPs are centred: {}",
+        study_effects
+    );
+    let r = &dataset.denning()[0];
+    let shift = fit.study_effects[&r.study] + fit.pack_effects[&r.pack_key()];
+    assert!((conditional[0].unwrap() - marginal[0].unwrap() - shift).abs() < 1e-9);
+
+    let ml = MixedOptions {
+        reml: false,
+        ..MixedOptions::default()
+    }
+    .fit_linear(dataset.denning(), "denning_doy", &covariates)
+    .unwrap();
+    assert!(
+        ml.variance.study < fit.variance.study,
+        "ML shrinks variances"
+    );
+    assert!(ml.aic.is_some() && fit.to_string().contains("ICC"));
+    assert_eq!(fit.variance_table().len(), 3);
+}
+
+#[test]
+fn test_logistic_mixed_model() {
+    let options = SyntheticOptions {
+        studies: 8,
+        ..SyntheticOptions::with_seed(2)
+    };
+    let dataset = options.dataset().unwrap();
+    let seasons = dataset.join_pack_seasons(JoinKind::Inner).seasons;
+    let fit = logistic_mixed(&seasons, &["denning_doy", "winter_swe"]).unwrap();
+    assert_eq!(fit.family, Family::Binomial);
+    assert!(fit.converged && fit.iterations > 1);
+    assert!(fit.log_likelihood.is_none());
+
+    for (term, truth) in [("denning_doy", -0.03), ("winter_swe", -0.005)] {
+        let c = fit.coefficient(term).unwrap();
+        assert!((c.estimate - truth).abs() < 3.0 * c.std_error, "{:?}", c);
+    }
+    assert!(fit.variance.study > 0.0);
+    assert!((fit.variance.residual - std::f64::consts::PI.powi(2) / 3.0).abs() < 1e-12);
+    assert!(fit.fitted.iter().all(|p| 0.0 < *p && *p < 1.0));
+    assert_eq!(fit.coefficient_table().columns()[3].name, "z");
+
+    let error = MixedOptions::default()
+        .fit_logistic(dataset.denning(), "denning_doy", &["winter_swe"])
+        .unwrap_err();
+    assert!(error.to_string().contains("0/1 response"), "{}", error);
+
+    let failed: Vec<_> = seasons
+        .iter()
+        .cloned()
+        .map(|mut season| {
+            if let Some(reproduction) = &mut season.reproduction {
+                reproduction.success = 0;
+            }
+            season
+        })
+        .collect();
+    let error = logistic_mixed(&failed, &["winter_swe"]).unwrap_err();
+    assert!(error.to_string().contains("both outcomes"), "{}", error);
+}
+
+/// Three studies of five packs over five years
+fn small_options(seed: u64) -> SyntheticOptions {
+    SyntheticOptions {
+        studies: 3,
+        packs_per_study: 5,
+        years: 2010..=2014,
+        ..SyntheticOptions::with_seed(seed)
+    }
+}
+
+#[test]
+fn test_invalid_designs_missing_rows_and_unseen_groups() {
+    let dataset = small_options(5).dataset().unwrap();
+    let records = dataset.denning();
+    match linear_mixed(records, "denning_doy", &["snow_depth"]).unwrap_err() {
+        WolfDataError::Validation { column, .. } => {
+            assert_eq!(column, Some("snow_depth".to_string()))
+        }
+        other => panic!("Expected a validation error, got {:?}", other),
+    }
+    let error = linear_mixed(&records[..2], "denning_doy", &["winter_tmax"]).unwrap_err();
+    assert!(error.to_string().contains("too few"), "{}", error);
+
+    let mut constant = records.to_vec();
+    for r in &mut constant {
+        r.annual_ao = Some(0.25);
+    }
+    let error = linear_mixed(&constant, "denning_doy", &["annual_ao"]).unwrap_err();
+    assert!(error.to_string().contains("collinear"), "{}", error);
+
+    let mut records = records.to_vec();
+    records[0].winter_tmax = None;
+    let fit = linear_mixed(&records, "denning_doy", &["winter_tmax"]).unwrap();
+    assert_eq!((fit.n, fit.dropped), (74, 1));
+    assert_eq!(fit.rows[0], 1);
+    assert_eq!(fit.predict(&records)[0], None);
+
+    // A study the model has not seen falls back to the fixed effects
+    let mut unseen = records[1].clone();
+    unseen.study = "Unseen Study".to_string();
+    let unseen = [unseen];
+    assert_eq!(fit.predict_conditional(&unseen), fit.predict(&unseen));
+    assert_ne!(
+        fit.predict_conditional(&records[1..2]),
+        fit.predict(&records[1..2])
+    );
+}
+
+#[test]
+fn test_options_show_in_components_and_summary() {
+    assert_eq!(Family::Gaussian.to_string(), "Linear");
+    assert_eq!(Family::Binomial.to_string(), "Logistic");
+
+    let dataset = small_options(6).dataset().unwrap();
+    let study_only = MixedOptions {
+        pack_effects: false,
+        reml: false,
+        ..MixedOptions::default()
+    }
+    .fit_linear(dataset.denning(), "denning_doy", &["winter_tmax"])
+    .unwrap();
+    assert_eq!(study_only.variance.pack, 0.0);
+    assert_eq!(study_only.icc_study, study_only.icc_pack);
+    assert!(study_only.pack_effects.values().all(|e| *e == 0.0));
+    let table = study_only.variance_table();
+    assert_eq!(table.rows()[1][0], Value::Text("pack:study".to_string()));
+    assert_eq!(table.rows()[1][2], Value::Float(0.0));
+    let summary = study_only.to_string();
+    assert!(
+        summary.starts_with(
+            "Linear mixed model of denning_doy (ML; n = 75, 0 dropped; 3 studies, 15 packs)"
+        ),
+        "{}",
+        summary
+    );
+    assert!(summary.contains("AIC"));
+    let icc = format!(
+        "ICC study {:.3}, same pack (incl. study) {:.3}",
+        study_only.icc_study, study_only.icc_pack
+    );
+    assert!(summary.contains(&icc), "{}", summary);
+
+    let seasons = dataset.join_pack_seasons(JoinKind::Inner).seasons;
+    let one_step = MixedOptions {
+        max_iterations: 1,
+        ..MixedOptions::default()
+    }
+    .fit_logistic(&seasons, "success", &["denning_doy"])
+    .unwrap();
+    assert_eq!(one_step.iterations, 1);
+    assert!(!one_step.converged);
+    assert_eq!(one_step.aic, None);
+    let summary = one_step.to_string();
+    assert!(summary.contains("(PQL, REML;"), "{}", summary);
+    assert!(summary.contains(", not converged)"), "{}", summary);
+    assert!(!summary.contains("AIC"));
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use chrono::Datelike;
+use common::{mock_denning_data, mock_reproductive_data};
+use wolf_project_210::data::{
+    join_pack_seasons, DenningPhenology, JoinKind, PackKey, PackSeason, ReproductiveSuccess,
+};
+use wolf_project_210::graph::build_graph;
+use wolf_project_210::record::{
+    covariate_values, group_by, yearly_means, WolfRecord, SHARED_COVARIATES,
+};
+
+/// Written once, used for both datasets below
+fn mean_shared_covariates<T: WolfRecord>(records: &[T]) -> Vec<Option<f64>> {
+    SHARED_COVARIATES
+        .iter()
+        .map(|name| {
+            let values = covariate_values(records, name);
+            (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+        })
+        .collect()
+}
+
+#[test]
+fn test_covariate_by_name() {
+    let denning = &mock_denning_data()[0];
+    let reproduction = &mock_reproductive_data()[0];
+
+    assert_eq!(denning.covariate("winter_swe"), Some(250.0));
+    assert_eq!(reproduction.covariate("winter_swe"), Some(200.0));
+    assert_eq!(denning.covariate("tiNDVI_prev1"), denning.covariate("ti_ndvi_prev1"));
+    assert_eq!(denning.covariate("denning_doy"), Some(130.0));
+    assert_eq!(denning.covariate("home_range_area"), None, "Not a denning column");
+
+    for name in SHARED_COVARIATES {
+        assert!(denning.covariate(name).is_some(), "{} missing for denning", name);
+        assert!(reproduction.covariate(name).is_some(), "{} missing for reproduction", name);
+    }
+    assert_eq!(mean_shared_covariates(std::slice::from_ref(denning)).len(), SHARED_COVARIATES.len());
+    assert!(mean_shared_covariates(std::slice::from_ref(reproduction)).iter().all(Option::is_some));
+}
+
+#[test]
+fn test_identity_and_season() {
+    let denning = &mock_denning_data()[0];
+    let reproduction = &mock_reproductive_data()[0];
+    assert_eq!(WolfRecord::pack_key(denning), WolfRecord::pack_key(reproduction));
+    assert_eq!(WolfRecord::season_key(denning), (PackKey::new("Study A", 1), 2020));
+    assert_eq!(reproduction.season_year(), 2020);
+    assert_eq!(denning.location(), (0.0, 0.0));
+}
+
+#[test]
+fn test_generic_grouping_and_graph() {
+    let mut reproduction = mock_reproductive_data();
+    let mut later = reproduction[0].clone();
+    later.start_date = later.start_date.with_year(2021).unwrap();
+    later.success = 0;
+    let mut other = later.clone();
+    other.study = "Study B".to_string();
+    reproduction.extend([later, other]);
+
+    let by_study = group_by(&reproduction, |r| r.study().to_string());
+    assert_eq!(by_study["Study A"].len(), 2);
+    assert_eq!(yearly_means(&reproduction, "success"), vec![(2020, 1.0), (2021, 0.0)]);
+
+    let graph = build_graph(&reproduction);
+    assert_eq!(graph.node_count(), 2);
+}
+
+#[test]
+fn test_every_numeric_column_resolves_and_missing_values_are_none() {
+    let mut denning = mock_denning_data()[0].clone();
+    let reproduction = mock_reproductive_data()[0].clone();
+    for name in DenningPhenology::NUMERIC_COLUMNS {
+        assert!(denning.covariate(name).is_some(), "denning {}", name);
+    }
+    for name in ReproductiveSuccess::NUMERIC_COLUMNS {
+        assert!(
+            reproduction.covariate(name).is_some(),
+            "reproduction {}",
+            name
+        );
+    }
+
+    denning.winter_swe = None;
+    denning.latitude_individual = 0.0;
+    assert_eq!(denning.covariate("winter_swe"), None);
+    assert_eq!(
+        denning.covariate("latitude_individual"),
+        None,
+        "0 marks a missing location"
+    );
+    assert_eq!(
+        denning.covariate("uid"),
+        None,
+        "identifiers are not covariates"
+    );
+    assert_eq!(denning.covariate(""), None);
+}
+
+#[test]
+fn test_pack_season_covariates_and_years() {
+    let mut denning = mock_denning_data();
+    let mut unmatched = denning[0].clone();
+    unmatched.uid = 2;
+    unmatched.denning_date = unmatched.denning_date.with_year(2021).unwrap();
+    unmatched.winter_swe = None;
+    denning.push(unmatched);
+    let join = join_pack_seasons(&denning, &mock_reproductive_data(), JoinKind::Left);
+    let (matched, unmatched) = (&join.seasons[0], &join.seasons[1]);
+
+    for name in PackSeason::NUMERIC_COLUMNS {
+        assert!(matched.covariate(name).is_some(), "pack season {}", name);
+    }
+    assert_eq!(
+        matched.covariate("winter_swe"),
+        Some(250.0),
+        "denning value"
+    );
+    assert_eq!(matched.covariate("repro_winter_swe"), Some(200.0));
+    assert_eq!(matched.covariate("success"), Some(1.0));
+    assert_eq!(unmatched.covariate("denning_doy"), Some(130.0));
+    assert_eq!(unmatched.covariate("success"), None);
+    assert_eq!(unmatched.covariate("repro_winter_swe"), None);
+    assert_eq!(unmatched.season_year(), 2021);
+    assert_eq!(unmatched.uid(), 2);
+
+    // 2021 has no winter_swe at all, so it has no mean
+    assert_eq!(
+        yearly_means(&join.seasons, "winter_swe"),
+        vec![(2020, 250.0)]
+    );
+    assert_eq!(yearly_means(&join.seasons, "denning_doy").len(), 2);
+    assert!(group_by(Vec::<&PackSeason>::new(), |s| s.year).is_empty());
+}
@@ -0,0 +1,59 @@
+use wolf_project_210::data::{DenningPhenology, LoadOptions};
+use wolf_project_210::error::WolfDataError;
+use wolf_project_210::schema::{check_header, ColumnMapping};
+
+/// The first denning row, with tiNDVI_prev1 renamed and moved to the end
+const RENAMED_EXPORT: &str = "\
+uid,study,longitude_study,latitude_study,pack,denning_date,denning_doy,denned,fall_tmax,summer_tmax_prev1,winter_tmax,fall_prcp,summer_prcp_prev1,winter_swe,annual_pdo,annual_ao,sos_prev1,los_prev1,latitude_individual,observer,ti_ndvi_prev1
+1,Study A,-146.8,62.4,1,2003-05-16,136,1,5,16,-7,143,193,68,0.22,0.07,137.3,20.8,62.79,JD,13.9
+";
+
+#[test]
+fn test_check_header_reports_differences() {
+    let header = RENAMED_EXPORT.lines().next().unwrap().split(',');
+    let mapping = ColumnMapping::new().rename("pack", "pack_id");
+    let report = check_header(header, DenningPhenology::COLUMNS, &mapping);
+
+    assert!(!report.is_ok());
+    assert_eq!(report.missing, vec!["tiNDVI_prev1"]);
+    assert_eq!(report.extra, vec!["observer", "ti_ndvi_prev1"]);
+    assert_eq!(
+        report.renamed,
+        vec![("pack".to_string(), "pack_id".to_string())]
+    );
+    assert_eq!(
+        report.likely_renamed,
+        vec![("ti_ndvi_prev1".to_string(), "tiNDVI_prev1".to_string())]
+    );
+}
+
+#[test]
+fn test_unmapped_export_fails_before_parsing_rows() {
+    let error = LoadOptions::default()
+        .read_denning(RENAMED_EXPORT.as_bytes())
+        .err()
+        .unwrap();
+    assert!(matches!(error, WolfDataError::Schema(report) if report.missing.len() == 2));
+}
+
+#[test]
+fn test_toml_mapping_loads_alternate_export() {
+    let mapping = ColumnMapping::from_toml_str(
+        r#"
+        [columns]
+        pack = "pack_id"
+        ti_ndvi_prev1 = "tiNDVI_prev1"
+        "#,
+    )
+    .unwrap();
+
+    let (data, report) = LoadOptions::with_mapping(mapping)
+        .read_denning(RENAMED_EXPORT.as_bytes())
+        .unwrap();
+
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0].pack_id, 1);
+    assert_eq!(data[0].ti_ndvi_prev1, Some(13.9));
+    assert_eq!(report.schema.extra, vec!["observer"]);
+    assert_eq!(report.schema.renamed.len(), 2);
+}
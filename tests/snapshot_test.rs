@@ -0,0 +1,179 @@
+use std::cell::Cell;
+use std::fs::File;
+use std::path::PathBuf;
+
+use wolf_project_210::data::{LoadOptions, LoadReport, RejectReason};
+use wolf_project_210::dataset::WolfDataset;
+use wolf_project_210::error::WolfDataError;
+use wolf_project_210::export::{export_table, Table};
+use wolf_project_210::missing::MissingPolicy;
+use wolf_project_210::schema::ColumnMapping;
+use wolf_project_210::snapshot::{SnapshotCache, SnapshotStatus};
+use wolf_project_210::synthetic::synthetic_dataset;
+
+/// Writes a synthetic release to a fresh temp directory
+fn write_sources(name: &str) -> (PathBuf, [PathBuf; 2]) {
+    let dir = std::env::temp_dir().join(name);
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    let dataset = synthetic_dataset(3);
+    let denning = dir.join("denning.csv");
+    let reproduction = dir.join("reproduction.csv");
+    export_table(&Table::from_records(dataset.denning()).unwrap(), &denning).unwrap();
+    export_table(
+        &Table::from_records(dataset.reproduction()).unwrap(),
+        &reproduction,
+    )
+    .unwrap();
+    (dir, [denning, reproduction])
+}
+
+/// Loads both sources with the settings the cache passes in
+fn parse(
+    sources: &[PathBuf; 2],
+    options: &LoadOptions,
+) -> Result<(WolfDataset, Vec<LoadReport>), WolfDataError> {
+    let open = |path: &PathBuf| File::open(path).map_err(|e| WolfDataError::io(path, e));
+    let (denning, denning_report) = options.read_denning(open(&sources[0])?)?;
+    let (reproduction, reproduction_report) = options.read_reproductive(open(&sources[1])?)?;
+    let reports = vec![denning_report, reproduction_report];
+    Ok((WolfDataset::new(denning, reproduction), reports))
+}
+
+/// Replaces one cell of a CSV file, `row` counting data rows from 0
+fn set_cell(path: &PathBuf, row: usize, column: &str, value: &str) {
+    let mut reader = csv::Reader::from_path(path).unwrap();
+    let header = reader.headers().unwrap().clone();
+    let index = header.iter().position(|c| c == column).unwrap();
+    let mut records: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    let mut cells: Vec<&str> = records[row].iter().collect();
+    cells[index] = value;
+    records[row] = cells.into_iter().collect();
+
+    let mut writer = csv::Writer::from_path(path).unwrap();
+    writer.write_record(&header).unwrap();
+    for record in &records {
+        writer.write_record(record).unwrap();
+    }
+    writer.flush().unwrap();
+}
+
+#[test]
+fn test_snapshot_is_reused_while_sources_are_unchanged() {
+    let (dir, sources) = write_sources("wolf_snapshot_reuse");
+    let cache = SnapshotCache::new(dir.join("cache"));
+    let builds = Cell::new(0);
+    let load = || {
+        cache.load(&sources, &LoadOptions::default(), |options| {
+            builds.set(builds.get() + 1);
+            parse(&sources, options)
+        })
+    };
+
+    let (built, built_reports, status) = load().unwrap();
+    assert_eq!(status, SnapshotStatus::Rebuilt(cache.path_for(&sources)));
+    let (reused, reused_reports, status) = load().unwrap();
+    assert!(matches!(status, SnapshotStatus::Reused(_)), "{}", status);
+    assert_eq!(builds.get(), 1);
+    assert_eq!(reused_reports.len(), 2);
+    for (reused, built) in reused_reports.iter().zip(&built_reports) {
+        assert_eq!(reused.to_string(), built.to_string());
+        assert_eq!(reused.total_rows, built.total_rows);
+    }
+    assert_eq!(
+        Table::from_records(reused.denning()).unwrap(),
+        Table::from_records(built.denning()).unwrap()
+    );
+    assert_eq!(
+        Table::from_records(reused.reproduction()).unwrap(),
+        Table::from_records(built.reproduction()).unwrap()
+    );
+    assert_eq!(reused.studies(), built.studies());
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_changed_source_or_settings_invalidate_snapshot() {
+    let (dir, sources) = write_sources("wolf_snapshot_invalidate");
+    let cache = SnapshotCache::new(dir.join("cache"));
+    let load = |options: &LoadOptions| {
+        cache
+            .load(&sources, options, |options| parse(&sources, options))
+            .map(|(dataset, _, status)| (dataset.denning().len(), status))
+            .unwrap()
+    };
+    load(&LoadOptions::default());
+
+    // Drop the last denning row
+    let csv = std::fs::read_to_string(&sources[0]).unwrap();
+    let trimmed: Vec<&str> = csv.lines().collect();
+    std::fs::write(&sources[0], trimmed[..trimmed.len() - 1].join("\n")).unwrap();
+    let (rows, status) = load(&LoadOptions::default());
+    assert!(matches!(status, SnapshotStatus::Rebuilt(_)));
+    assert_eq!(rows, trimmed.len() - 2);
+    assert!(matches!(
+        load(&LoadOptions::default()).1,
+        SnapshotStatus::Reused(_)
+    ));
+
+    // A missing value is dropped by default and kept by `KeepNone`; each
+    // setting gets its own load rather than the other's snapshot
+    set_cell(&sources[0], 0, "winter_swe", "NA");
+    let (dropped, status) = load(&LoadOptions::default());
+    assert!(matches!(status, SnapshotStatus::Rebuilt(_)));
+    assert_eq!(dropped, rows - 1);
+    let keep = LoadOptions::with_missing_policy(MissingPolicy::KeepNone);
+    let (kept, status) = load(&keep);
+    assert!(matches!(status, SnapshotStatus::Rebuilt(_)));
+    assert_eq!(kept, rows);
+    assert!(matches!(load(&keep), (n, SnapshotStatus::Reused(_)) if n == rows));
+    assert!(matches!(
+        load(&LoadOptions::default()),
+        (n, SnapshotStatus::Rebuilt(_)) if n == rows - 1
+    ));
+
+    // Column renames are part of the key too, even ones that match nothing
+    let renamed = LoadOptions::with_mapping(ColumnMapping::new().rename("pack_number", "pack_id"));
+    assert!(matches!(load(&renamed), (n, SnapshotStatus::Rebuilt(_)) if n == rows - 1));
+    assert!(matches!(load(&renamed).1, SnapshotStatus::Reused(_)));
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_corrupt_snapshot_is_rebuilt() {
+    let (dir, sources) = write_sources("wolf_snapshot_corrupt");
+    let cache = SnapshotCache::new(dir.join("cache"));
+    let load = || cache.load(&sources, &LoadOptions::default(), |o| parse(&sources, o));
+    load().unwrap();
+    std::fs::write(cache.path_for(&sources), b"not a snapshot").unwrap();
+
+    let (dataset, _, status) = load().unwrap();
+    assert!(matches!(status, SnapshotStatus::Rebuilt(_)));
+    assert_eq!(dataset.denning().len(), 800);
+
+    cache.clear().unwrap();
+    assert!(!cache.path_for(&sources).exists());
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_reused_snapshot_keeps_rejected_rows() {
+    let (dir, sources) = write_sources("wolf_snapshot_reports");
+    set_cell(&sources[1], 4, "summer_tmax", "-9999");
+    let cache = SnapshotCache::new(dir.join("cache"));
+    let load = || cache.load(&sources, &LoadOptions::default(), |o| parse(&sources, o));
+    load().unwrap();
+
+    let (_, reports, status) = load().unwrap();
+    assert!(matches!(status, SnapshotStatus::Reused(_)));
+    assert_eq!(reports[0].rejected_count(), 0);
+    let rejected = &reports[1].rejected[0];
+    assert_eq!(rejected.line, 6);
+    assert_eq!(rejected.issues[0].reason, RejectReason::Sentinel);
+    assert_eq!(reports[1].by_column["summer_tmax"], 1);
+    assert!(reports[1].ensure_rejection_rate(0.0).is_err());
+
+    std::fs::remove_dir_all(dir).ok();
+}
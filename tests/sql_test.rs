@@ -0,0 +1,151 @@
+#![cfg(feature = "sql")]
+
+mod common;
+
+use chrono::NaiveDate;
+use common::{mock_denning_data, mock_reproductive_data};
+use wolf_project_210::dataset::WolfDataset;
+use wolf_project_210::error::WolfDataError;
+use wolf_project_210::export::{write_csv, Column, ColumnType, Table, Value};
+use wolf_project_210::sql::SqlSession;
+
+fn session() -> SqlSession {
+    SqlSession::from_dataset(&WolfDataset::new(
+        mock_denning_data(),
+        mock_reproductive_data(),
+    ))
+    .unwrap()
+}
+
+#[test]
+fn test_session_has_record_and_join_tables() {
+    let session = session();
+    assert_eq!(
+        session.table_names().unwrap(),
+        ["denning", "pack_seasons", "reproduction"]
+    );
+
+    let table = session
+        .query(r#"SELECT uid, denning_date, "tiNDVI_prev1", success FROM pack_seasons"#)
+        .unwrap();
+    let kinds: Vec<ColumnType> = table.columns().iter().map(|c| c.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            ColumnType::Integer,
+            ColumnType::Date,
+            ColumnType::Float,
+            ColumnType::Integer
+        ]
+    );
+    assert_eq!(
+        table.rows()[0],
+        [
+            Value::Integer(1),
+            Value::Date(NaiveDate::from_ymd_opt(2020, 5, 10).unwrap()),
+            Value::Float(0.6),
+            Value::Integer(1),
+        ]
+    );
+}
+
+#[test]
+fn test_computed_columns_infer_their_type() {
+    let table = session()
+        .query(
+            "SELECT study, COUNT(*) AS n, AVG(winter_swe) AS swe, NULL AS blank \
+             FROM reproduction GROUP BY study",
+        )
+        .unwrap();
+    let kinds: Vec<ColumnType> = table.columns().iter().map(|c| c.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            ColumnType::Text,
+            ColumnType::Integer,
+            ColumnType::Float,
+            ColumnType::Integer
+        ]
+    );
+
+    let mut csv = Vec::new();
+    write_csv(&table, &mut csv).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "study,n,swe,blank\nStudy A,1,200,\n"
+    );
+}
+
+#[test]
+fn test_invalid_sql_is_reported() {
+    let error = session().query("SELECT * FROM wolves").unwrap_err();
+    assert!(error.to_string().starts_with("SQL error:"), "{}", error);
+}
+
+#[test]
+fn test_loaded_tables_round_trip_and_replace() {
+    let mut session = SqlSession::new().unwrap();
+    assert!(session.table_names().unwrap().is_empty());
+
+    let mut table = Table::new(vec![
+        Column::new("weird \"name\"", ColumnType::Text),
+        Column::new("day", ColumnType::Date),
+        Column::new("swe", ColumnType::Float),
+    ]);
+    let day = NaiveDate::from_ymd_opt(2021, 3, 1).unwrap();
+    table
+        .push_row(vec![
+            Value::Text("a".to_string()),
+            Value::Date(day),
+            Value::Null,
+        ])
+        .unwrap();
+    table
+        .push_row(vec![Value::Null, Value::Null, Value::Float(1.5)])
+        .unwrap();
+    session.load_table("my table", &table).unwrap();
+    let back = session
+        .query(r#"SELECT * FROM "my table" ORDER BY rowid"#)
+        .unwrap();
+    assert_eq!(back, table, "names, types and nulls survive the round trip");
+
+    // A date column holding something else falls back to text
+    let mixed = session
+        .query(r#"SELECT day FROM "my table" UNION ALL SELECT 'soon'"#)
+        .unwrap();
+    assert_eq!(mixed.columns()[0].kind, ColumnType::Text);
+    assert_eq!(mixed.rows()[0][0], Value::Text("2021-03-01".to_string()));
+
+    session
+        .load_table("my table", &Table::new(table.columns().to_vec()))
+        .unwrap();
+    assert!(session
+        .query(r#"SELECT * FROM "my table""#)
+        .unwrap()
+        .is_empty());
+    assert_eq!(session.table_names().unwrap(), ["my table"]);
+
+    let empty = SqlSession::from_dataset(&WolfDataset::default()).unwrap();
+    let counts = empty
+        .query("SELECT COUNT(*) AS n FROM pack_seasons")
+        .unwrap();
+    assert_eq!(counts.rows()[0][0], Value::Integer(0));
+}
+
+#[test]
+fn test_query_errors_leave_the_session_usable() {
+    let session = session();
+    for sql in ["SELEC 1", "SELECT no_such_column FROM denning", ""] {
+        let error = session.query(sql).unwrap_err();
+        assert!(
+            matches!(error, WolfDataError::Sql(_)),
+            "{}: {:?}",
+            sql,
+            error
+        );
+    }
+    let table = session
+        .query("SELECT COUNT(*) AS n FROM denning WHERE denning_doy > 100")
+        .unwrap();
+    assert_eq!(table.rows()[0][0], Value::Integer(1));
+}
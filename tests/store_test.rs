@@ -0,0 +1,161 @@
+#![cfg(feature = "store")]
+
+use std::path::PathBuf;
+
+use wolf_project_210::data::{read_denning_csv, DenningPhenology};
+use wolf_project_210::error::WolfDataError;
+use wolf_project_210::export::{export_table, Table};
+use wolf_project_210::store::{ConflictPolicy, IngestOptions, RecordKind, RecordStore};
+
+const DENNING_CSV: &str = "data/Wolf_DenningPhenology_AK_CA.csv";
+
+/// Writes records to a CSV file in the temp directory
+fn write_denning(name: &str, records: &[DenningPhenology]) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    export_table(&Table::from_records(records).unwrap(), &path).unwrap();
+    path
+}
+
+#[test]
+fn test_snapshot_round_trips_ingested_records() {
+    let (denning, _) = read_denning_csv(DENNING_CSV).unwrap();
+    let mut store = RecordStore::open_in_memory().unwrap();
+    let report = store.ingest_denning(DENNING_CSV).unwrap();
+    assert_eq!(report.inserted, denning.len());
+    assert_eq!(report.source.sha256.len(), 64);
+
+    let snapshot = store.snapshot().unwrap();
+    assert_eq!(
+        Table::from_records(snapshot.denning()).unwrap(),
+        Table::from_records(&denning).unwrap()
+    );
+    assert!(snapshot.reproduction().is_empty());
+
+    let provenance = store
+        .provenance(RecordKind::Denning, denning[0].uid)
+        .unwrap()
+        .unwrap();
+    assert_eq!(provenance.source.path, DENNING_CSV);
+    assert_eq!(provenance.line, 2, "first data row follows the header");
+
+    let again = store.ingest_denning(DENNING_CSV).unwrap();
+    assert!(again.already_ingested);
+    assert_eq!(store.sources().unwrap().len(), 1);
+}
+
+#[test]
+fn test_new_season_appends_and_skips_unchanged_rows() {
+    let (denning, _) = read_denning_csv(DENNING_CSV).unwrap();
+    let first = write_denning("wolf_store_first.csv", &denning[..10]);
+    let second = write_denning("wolf_store_second.csv", &denning[..20]);
+
+    let mut store = RecordStore::open_in_memory().unwrap();
+    store.ingest_denning(&first).unwrap();
+    let report = store.ingest_denning(&second).unwrap();
+    assert_eq!((report.inserted, report.unchanged), (10, 10));
+    assert_eq!(store.denning().unwrap().len(), 20);
+
+    // Unchanged rows keep their original provenance
+    let sources = store.sources().unwrap();
+    let provenance = |uid| store.provenance(RecordKind::Denning, uid).unwrap().unwrap();
+    assert_eq!(provenance(denning[0].uid).source, sources[0]);
+    assert_eq!(provenance(denning[15].uid).source, sources[1]);
+    assert_eq!(provenance(denning[15].uid).line, 17);
+
+    std::fs::remove_file(first).ok();
+    std::fs::remove_file(second).ok();
+}
+
+#[test]
+fn test_uid_conflicts_follow_policy() {
+    let (denning, _) = read_denning_csv(DENNING_CSV).unwrap();
+    let original = write_denning("wolf_store_original.csv", &denning[..5]);
+    let mut corrected = denning[..5].to_vec();
+    corrected[2].annual_pdo = corrected[2].annual_pdo.map(|v| v + 0.25);
+    let corrected = write_denning("wolf_store_corrected.csv", &corrected);
+    let uid = denning[2].uid;
+
+    let mut store = RecordStore::open_in_memory().unwrap();
+    store.ingest_denning(&original).unwrap();
+    let error = store.ingest_denning(&corrected).unwrap_err();
+    assert!(error.to_string().contains("line 4"), "{}", error);
+    assert_eq!(
+        store.sources().unwrap().len(),
+        1,
+        "rejected ingest writes nothing"
+    );
+
+    let skip = IngestOptions {
+        on_conflict: ConflictPolicy::Skip,
+        ..IngestOptions::default()
+    };
+    let report = skip.ingest_denning(&mut store, &corrected).unwrap();
+    assert_eq!(report.conflicts.len(), 1);
+    assert_eq!(report.conflicts[0].uid, uid);
+    let stored = store.denning().unwrap();
+    assert_eq!(stored[2].annual_pdo, denning[2].annual_pdo);
+
+    let mut store = RecordStore::open_in_memory().unwrap();
+    store.ingest_denning(&original).unwrap();
+    let replace = IngestOptions {
+        on_conflict: ConflictPolicy::Replace,
+        ..IngestOptions::default()
+    };
+    let report = replace.ingest_denning(&mut store, &corrected).unwrap();
+    assert_eq!((report.replaced, report.unchanged), (1, 4));
+    let provenance = store.provenance(RecordKind::Denning, uid).unwrap().unwrap();
+    assert_eq!(provenance.source.path, corrected.display().to_string());
+    assert!(store
+        .denning()
+        .unwrap()
+        .iter()
+        .any(|d| d.uid == uid && d.annual_pdo != denning[2].annual_pdo));
+
+    std::fs::remove_file(original).ok();
+    std::fs::remove_file(corrected).ok();
+}
+
+#[test]
+fn test_store_reopens_from_disk_and_rejects_unknown_kinds() {
+    let path = std::env::temp_dir().join("wolf_store_reopen.db");
+    std::fs::remove_file(&path).ok();
+    {
+        let mut store = RecordStore::open(&path).unwrap();
+        store
+            .ingest_reproductive("data/Wolf_ReproductiveSuccess_AK_CA.csv")
+            .unwrap();
+    }
+    let store = RecordStore::open(&path).unwrap();
+    let sources = store.sources().unwrap();
+    assert_eq!(sources.len(), 1);
+    assert_eq!(sources[0].kind, RecordKind::Reproductive);
+    assert!(!store.reproduction().unwrap().is_empty());
+    assert!(store.denning().unwrap().is_empty());
+    drop(store);
+
+    let connection = rusqlite::Connection::open(&path).unwrap();
+    connection
+        .execute("UPDATE sources SET kind = 'howling'", [])
+        .unwrap();
+    drop(connection);
+    let store = RecordStore::open(&path).unwrap();
+    match store.sources().unwrap_err() {
+        WolfDataError::Validation {
+            column, message, ..
+        } => {
+            assert_eq!(column.as_deref(), Some("kind"));
+            assert!(message.contains("howling"), "{}", message);
+        }
+        other => panic!("Expected a validation error, got {:?}", other),
+    }
+    drop(store);
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_missing_source_file_is_an_io_error() {
+    let mut store = RecordStore::open_in_memory().unwrap();
+    let error = store.ingest_denning("data/does_not_exist.csv").unwrap_err();
+    assert!(matches!(error, WolfDataError::Io { path: Some(_), .. }));
+    assert!(store.sources().unwrap().is_empty());
+}
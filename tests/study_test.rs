@@ -0,0 +1,180 @@
+mod common;
+
+use common::{mock_denning_data, mock_reproductive_data};
+use wolf_project_210::data::read_denning_csv;
+use wolf_project_210::dataset::WolfDataset;
+use wolf_project_210::error::WolfDataError;
+use wolf_project_210::study::{Country, StudyMetadata, StudyRegistry};
+
+#[test]
+fn test_registry_parses_study_strings() {
+    let (denning, _) = read_denning_csv("data/Wolf_DenningPhenology_AK_CA.csv").unwrap();
+    let mut registry = StudyRegistry::new();
+    for record in &denning {
+        registry.register(record);
+    }
+    assert_eq!(registry.len(), 8);
+
+    let denali = registry
+        .by_name("Denali National Park, Alaska, USA")
+        .unwrap();
+    assert_eq!(denali.code, "DNP-AK");
+    assert_eq!(denali.site, "Denali National Park");
+    assert_eq!(denali.jurisdiction, "Alaska");
+    assert_eq!(denali.country, Country::UnitedStates);
+    assert!(denali.is_alaska());
+    assert!((denali.centroid.0 - 63.6352304).abs() < 1e-9);
+
+    let jasper = registry.by_code("JBNP-AB-BC").unwrap();
+    assert_eq!(jasper.jurisdiction, "Alberta-BC");
+    assert_eq!(jasper.country, Country::Canada);
+
+    let by_country = registry.group_by_country(&denning);
+    assert_eq!(by_country.len(), 2);
+    assert_eq!(
+        by_country.values().map(Vec::len).sum::<usize>(),
+        denning.len()
+    );
+}
+
+#[test]
+fn test_interned_ids_and_codes_are_unique() {
+    let mut registry = StudyRegistry::new();
+    let a = registry.intern("Big Lake, Alaska, USA");
+    let b = registry.intern("Birch Loop, Alaska, USA");
+    assert_ne!(a, b);
+    assert_eq!(registry.intern("Big Lake, Alaska, USA"), a);
+    assert_eq!(registry.get(a).code, "BL-AK");
+    assert_eq!(registry.get(b).code, "BL-AK2");
+    let unparsed = registry.intern("Study A");
+    assert_eq!(registry.get(unparsed).country, Country::Unknown);
+}
+
+#[test]
+fn test_sidecar_metadata_and_dataset_grouping() {
+    let mut denning = mock_denning_data();
+    denning[0].study = "Denali National Park, Alaska, USA".to_string();
+    let mut reproduction = mock_reproductive_data();
+    reproduction[0].study = denning[0].study.clone();
+    let mut dataset = WolfDataset::new(denning, reproduction);
+
+    let path = std::env::temp_dir().join("wolf_study_metadata.toml");
+    std::fs::write(
+        &path,
+        r#"
+[studies."DNP-AK"]
+protected_status = "National Park and Preserve"
+agency = "US National Park Service"
+
+[studies."Nowhere, Yukon, CA"]
+area_km2 = 1.0
+"#,
+    )
+    .unwrap();
+    let unmatched = dataset.load_study_metadata(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+
+    assert_eq!(unmatched, vec!["Nowhere, Yukon, CA"]);
+    let areas = dataset.study_areas();
+    let denali = &areas.areas()[0];
+    assert_eq!(denali.rows(), 2, "Both datasets are registered");
+    assert_eq!(
+        denali.metadata.agency.as_deref(),
+        Some("US National Park Service")
+    );
+    assert_eq!(denali.metadata.area_km2, None);
+
+    let groups = areas.group_by_area(dataset.reproduction());
+    assert_eq!(groups[&denali.id].len(), 1);
+
+    // The column stores share the registry, metadata included
+    for columns in [dataset.denning_columns(), dataset.reproduction_columns()] {
+        let rows = columns.query().country(Country::UnitedStates);
+        let area = columns.study_area(rows.rows()[0]);
+        assert_eq!(area, denali);
+        assert_eq!(columns.studies(), areas);
+    }
+}
+
+#[test]
+fn test_unusual_study_strings_and_centroids() {
+    let mut registry = StudyRegistry::new();
+    let multi = registry.intern("Wood Buffalo, Northwest Territories-Alberta, Canada");
+    let area = registry.get(multi);
+    assert_eq!(area.code, "WB-NT-AB");
+    assert_eq!(area.country, Country::Canada);
+    assert!(!area.is_alaska());
+    assert_eq!(area.to_string(), format!("{} (WB-NT-AB)", area.name));
+
+    let two_parts = registry.intern("Yukon-Charley, USA");
+    let two_parts = registry.get(two_parts);
+    assert_eq!(
+        (two_parts.site.as_str(), two_parts.jurisdiction.as_str()),
+        ("Yukon-Charley", "")
+    );
+    assert_eq!(
+        (two_parts.code.as_str(), two_parts.country),
+        ("YC", Country::UnitedStates)
+    );
+    let unknown = registry.intern("Somewhere, Ontario, Mars");
+    let unknown = registry.get(unknown);
+    assert_eq!(
+        (unknown.code.as_str(), unknown.country),
+        ("S-Ontario", Country::Unknown)
+    );
+
+    let mut records = mock_denning_data();
+    records[0].latitude_study = 60.0;
+    records[0].longitude_study = -140.0;
+    let mut second = records[0].clone();
+    second.latitude_study = 64.0;
+    second.longitude_study = -150.0;
+    records.push(second);
+    let mut registry = StudyRegistry::new();
+    let id = registry.register(&records[0]);
+    assert_eq!(registry.register(&records[1]), id);
+    assert_eq!(registry.get(id).centroid, (62.0, -145.0));
+    assert_eq!(registry.get(id).rows(), 2);
+
+    let mut elsewhere = mock_reproductive_data();
+    elsewhere[0].study = "Unregistered".to_string();
+    assert!(registry.area_of(&elsewhere[0]).is_none());
+    assert!(registry.group_by_area(&elsewhere).is_empty());
+    assert_eq!(
+        registry.group_by_country(&elsewhere)[&Country::Unknown].len(),
+        1
+    );
+}
+
+#[test]
+fn test_invalid_or_missing_metadata_is_an_error() {
+    let mut registry = StudyRegistry::new();
+    registry.intern("Denali National Park, Alaska, USA");
+
+    let error = registry
+        .apply_metadata_toml("[studies.\"DNP-AK\"]\narea_km2 = \"large\"\n")
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("invalid study metadata"),
+        "{}",
+        error
+    );
+    assert_eq!(registry.areas()[0].metadata, StudyMetadata::default());
+
+    assert_eq!(
+        registry.apply_metadata_toml("").unwrap(),
+        Vec::<String>::new()
+    );
+    let unmatched = registry
+        .apply_metadata_toml(
+            "[studies.\"Denali National Park, Alaska, USA\"]\narea_km2 = 24585.0\n",
+        )
+        .unwrap();
+    assert!(unmatched.is_empty(), "study names match as well as codes");
+    assert_eq!(registry.areas()[0].metadata.area_km2, Some(24585.0));
+
+    let error = registry
+        .load_metadata(std::env::temp_dir().join("wolf_no_such_metadata.toml"))
+        .unwrap_err();
+    assert!(matches!(error, WolfDataError::Io { path: Some(_), .. }));
+}
@@ -0,0 +1,237 @@
+mod common;
+
+use chrono::NaiveDate;
+use common::{mock_denning_data, mock_reproductive_data};
+use wolf_project_210::data::DenningPhenology;
+use wolf_project_210::dataset::WolfDataset;
+use wolf_project_210::export::Value;
+use wolf_project_210::summary::{summarize, summary_table, Grouping, SummaryOptions};
+use wolf_project_210::synthetic::synthetic_dataset;
+use wolf_project_210::units::{Celsius, Quantity};
+use wolf_project_210::{analyze_temperature_impact, summarize_impact};
+
+#[test]
+fn test_matches_hand_computed_statistics() {
+    let values = [
+        Some(4.0),
+        None,
+        Some(1.0),
+        Some(10.0),
+        Some(3.0),
+        None,
+        Some(2.0),
+    ];
+    let summary = SummaryOptions::default().summarize(values);
+
+    assert_eq!((summary.n, summary.missing), (5, 2));
+    assert_eq!(summary.mean, Some(4.0));
+    let sd = 12.5f64.sqrt();
+    assert!((summary.sd.unwrap() - sd).abs() < 1e-12);
+    assert!((summary.se.unwrap() - sd / 5f64.sqrt()).abs() < 1e-12);
+    assert_eq!(
+        (summary.median, summary.q1, summary.q3),
+        (Some(3.0), Some(2.0), Some(4.0))
+    );
+    assert_eq!(summary.iqr, Some(2.0));
+    assert_eq!((summary.min, summary.max), (Some(1.0), Some(10.0)));
+    // Central moments m2 = 10 and m3 = 36
+    assert!((summary.skewness.unwrap() - 36.0 / 10f64.powf(1.5)).abs() < 1e-12);
+    assert!((summary.percentile(10.0).unwrap() - 1.4).abs() < 1e-12);
+    assert!((summary.percentile(95.0).unwrap() - 8.8).abs() < 1e-12);
+
+    let empty = summarize(&[]);
+    assert_eq!((empty.n, empty.mean, empty.mean_ci), (0, None, None));
+    assert!(empty.percentiles.is_empty());
+    assert!(empty.to_string().contains("No data available"));
+    let single = summarize(&[7.0]);
+    assert_eq!(
+        (single.median, single.sd, single.skewness),
+        (Some(7.0), None, None)
+    );
+}
+
+#[test]
+fn test_bootstrap_intervals_are_reproducible() {
+    let dataset = synthetic_dataset(9);
+    let options = SummaryOptions::default();
+    let summary = options
+        .summarize_column(dataset.denning(), "winter_tmax")
+        .unwrap();
+    let again = options
+        .summarize_column(dataset.denning(), "winter_tmax")
+        .unwrap();
+    assert_eq!(summary, again);
+
+    let (mean, mean_ci) = (summary.mean.unwrap(), summary.mean_ci.unwrap());
+    assert!(mean_ci.lower < mean && mean < mean_ci.upper);
+    let width = mean_ci.upper - mean_ci.lower;
+    let normal_width = 2.0 * 1.96 * summary.se.unwrap();
+    assert!(
+        (width / normal_width - 1.0).abs() < 0.2,
+        "{} vs {}",
+        width,
+        normal_width
+    );
+    let median_ci = summary.median_ci.unwrap();
+    assert!(median_ci.lower <= summary.median.unwrap());
+    assert!(summary.median.unwrap() <= median_ci.upper);
+
+    let reseeded = SummaryOptions {
+        seed: 1,
+        ..SummaryOptions::default()
+    }
+    .summarize_column(dataset.denning(), "winter_tmax")
+    .unwrap();
+    assert_ne!(reseeded.mean_ci, summary.mean_ci);
+    assert_eq!(reseeded.mean, summary.mean);
+
+    let skipped = SummaryOptions {
+        bootstrap: 0,
+        ..SummaryOptions::default()
+    }
+    .summarize_column(dataset.denning(), "winter_tmax")
+    .unwrap();
+    assert_eq!((skipped.mean_ci, skipped.median_ci), (None, None));
+}
+
+#[test]
+fn test_grouping_and_rendering() {
+    let dataset = synthetic_dataset(9);
+    let options = SummaryOptions {
+        bootstrap: 100,
+        ..SummaryOptions::default()
+    };
+    let by_study = options
+        .summarize_by(dataset.denning(), "denning_doy", Grouping::Study)
+        .unwrap();
+    let by_year = options
+        .summarize_by(dataset.reproduction(), "winter_swe", Grouping::Year)
+        .unwrap();
+    assert_eq!((by_study.groups.len(), by_year.groups.len()), (4, 20));
+    let n: usize = by_study.groups.values().map(|s| s.n).sum();
+    assert_eq!(n, by_study.overall.n);
+    assert!(by_year.groups.contains_key("2000"));
+
+    let table = by_study.table();
+    assert_eq!(table.len(), 4);
+    assert_eq!(table.columns()[0].name, "study");
+    assert!(table.column_index("p95").is_some());
+    assert_eq!(by_year.table().columns()[0].name, "year");
+    assert!(by_study.to_string().contains("denning_doy by study"));
+
+    let mut json = Vec::new();
+    by_year.write_json(&mut json).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(value["grouping"], "year");
+    assert!(value["groups"]["2019"]["mean_ci"]["lower"].is_f64());
+
+    let impact = analyze_temperature_impact(&WolfDataset::new(
+        mock_denning_data(),
+        mock_reproductive_data(),
+    ));
+    let summary = summarize_impact(&impact);
+    assert_eq!(summary.n, impact.len());
+    assert!(summary.to_string().contains("Records"));
+    assert_eq!(summary_table("series", [("impact", &summary)]).len(), 1);
+
+    let error = options
+        .summarize_by(dataset.denning(), "success", Grouping::Study)
+        .unwrap_err();
+    assert!(error.to_string().contains("success"), "{}", error);
+}
+
+#[test]
+fn test_missing_and_constant_series_and_custom_percentiles() {
+    let options = SummaryOptions {
+        percentiles: vec![0.0, 100.0],
+        bootstrap: 50,
+        ..SummaryOptions::default()
+    };
+    let all_missing = options.summarize([None, None, None]);
+    assert_eq!((all_missing.n, all_missing.missing), (0, 3));
+    assert_eq!((all_missing.min, all_missing.iqr), (None, None));
+    assert!(all_missing.percentiles.is_empty());
+
+    let constant = options.summarize([Some(2.5); 4]);
+    assert_eq!((constant.sd, constant.iqr), (Some(0.0), Some(0.0)));
+    assert_eq!(constant.skewness, None, "undefined without spread");
+    let ci = constant.mean_ci.unwrap();
+    assert_eq!((ci.lower, ci.upper), (2.5, 2.5));
+
+    let spread = options.summarize([Some(-1.0), Some(8.0), Some(3.0)]);
+    assert_eq!(spread.percentile(0.0), spread.min);
+    assert_eq!(spread.percentile(100.0), spread.max);
+    assert_eq!(spread.percentile(50.0), None, "not requested");
+
+    // Percentile columns come from the first summary that has any
+    let table = summary_table("series", [("none", &all_missing), ("spread", &spread)]);
+    assert_eq!(table.column_index("p100"), Some(table.columns().len() - 1));
+    assert!(table.column_index("p50").is_none());
+    let none = &table.rows()[0];
+    assert_eq!(none[1..3], [Value::Integer(0), Value::Integer(3)]);
+    assert!(none[3..].iter().all(|v| *v == Value::Null));
+    assert_eq!(
+        table.rows()[1][table.columns().len() - 1],
+        Value::Float(8.0)
+    );
+}
+
+#[test]
+fn test_groups_count_their_own_missing_values() {
+    let template = &mock_denning_data()[0];
+    let season = |uid, study: &str, year, tmax: Option<f64>| DenningPhenology {
+        uid,
+        study: study.to_string(),
+        denning_date: NaiveDate::from_ymd_opt(year, 5, 10).unwrap(),
+        winter_tmax: tmax.and_then(|t| Celsius::new(t).ok()),
+        ..template.clone()
+    };
+    let records = [
+        season(1, "Study B", 2019, Some(-4.0)),
+        season(2, "Study A", 2019, None),
+        season(3, "Study A", 2020, Some(-8.0)),
+        season(4, "Study B", 2020, Some(-6.0)),
+        season(5, "Study B", 2020, None),
+    ];
+    let options = SummaryOptions {
+        bootstrap: 0,
+        ..SummaryOptions::default()
+    };
+
+    let by_year = options
+        .summarize_by(&records, "winter_tmax", Grouping::Year)
+        .unwrap();
+    let counts: Vec<(&str, usize, usize)> = by_year
+        .groups
+        .iter()
+        .map(|(year, s)| (year.as_str(), s.n, s.missing))
+        .collect();
+    assert_eq!(counts, [("2019", 1, 1), ("2020", 2, 1)]);
+    assert_eq!((by_year.overall.n, by_year.overall.missing), (3, 2));
+    assert_eq!(by_year.groups["2020"].mean, Some(-7.0));
+    assert_eq!(by_year.groups["2019"].sd, None);
+
+    let by_study = options
+        .summarize_by(&records, "winter_tmax", Grouping::Study)
+        .unwrap();
+    let studies: Vec<&str> = by_study.groups.keys().map(String::as_str).collect();
+    assert_eq!(studies, ["Study A", "Study B"]);
+    assert_eq!(by_study.groups["Study B"].median, Some(-5.0));
+    let table = by_study.table();
+    assert_eq!(table.rows()[0][0], Value::Text("Study A".to_string()));
+    assert_eq!(
+        table.rows()[0][table.column_index("mean_lower").unwrap()],
+        Value::Null
+    );
+
+    let empty = options
+        .summarize_by(&records[..0], "winter_tmax", Grouping::Year)
+        .unwrap();
+    assert!(empty.groups.is_empty());
+    assert_eq!(empty.overall.n, 0);
+    assert!(empty.table().is_empty());
+    let error = options
+        .summarize_column(&records, "home_range_area")
+        .unwrap_err();
+    assert!(error.to_string().contains("home_range_area"), "{}", error);
+}
@@ -0,0 +1,308 @@
+use chrono::Datelike;
+use wolf_project_210::data::read_denning_csv;
+use wolf_project_210::export::{export_table, Table};
+use wolf_project_210::record::WolfRecord;
+use wolf_project_210::error::WolfDataError;
+use wolf_project_210::synthetic::{
+    synthetic_dataset, Distribution, PlantedEffects, SyntheticOptions,
+};
+use wolf_project_210::units::Quantity;
+use wolf_project_210::validation::{Validator, DENNING_SEASON};
+
+/// Least-squares slope of `y` on `x`
+fn slope(points: &[(f64, f64)]) -> f64 {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxy: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    sxy / sxx
+}
+
+#[test]
+fn test_same_seed_gives_same_valid_records() {
+    let table = |seed| Table::from_records(synthetic_dataset(seed).denning()).unwrap();
+    assert_eq!(table(7), table(7));
+    assert_ne!(table(7), table(8));
+
+    let dataset = synthetic_dataset(7);
+    assert_eq!(dataset.denning().len(), 4 * 10 * 20);
+    assert_eq!(dataset.reproduction().len(), dataset.denning().len());
+    assert_eq!(dataset.studies().len(), 4);
+    assert_eq!(dataset.years(), (2000..=2019).collect::<Vec<_>>());
+
+    assert!(Validator::builtin().validate(dataset.denning()).is_ok());
+    assert!(Validator::builtin()
+        .validate(dataset.reproduction())
+        .is_ok());
+
+    // The loader accepts the records unchanged
+    let path = std::env::temp_dir().join("wolf_synthetic_denning.csv");
+    export_table(&Table::from_records(dataset.denning()).unwrap(), &path).unwrap();
+    let (loaded, _) = read_denning_csv(path.to_str().unwrap()).unwrap();
+    assert_eq!(Table::from_records(&loaded).unwrap(), table(7));
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_missingness_and_design_follow_options() {
+    let options = SyntheticOptions {
+        studies: 3,
+        packs_per_study: 5,
+        years: 2010..=2014,
+        missing_rate: 0.2,
+        reproduction_rate: 0.5,
+        ..SyntheticOptions::with_seed(1)
+    };
+    let (denning, reproduction) = options.generate().unwrap();
+    assert_eq!(denning.len(), 3 * 5 * 5);
+    assert!(
+        (20..=55).contains(&reproduction.len()),
+        "{}",
+        reproduction.len()
+    );
+
+    let (missing, total) = denning
+        .iter()
+        .flat_map(|d| {
+            ["fall_tmax", "winter_swe", "annual_pdo", "los_prev1"].map(|c| d.covariate(c))
+        })
+        .fold((0, 0), |(m, t), value| {
+            (m + usize::from(value.is_none()), t + 1)
+        });
+    let rate = missing as f64 / total as f64;
+    assert!((0.14..0.26).contains(&rate), "{}", rate);
+
+    let unknown = SyntheticOptions {
+        denning_doy: PlantedEffects {
+            slopes: vec![("snow_depth".to_string(), 1.0)],
+            ..SyntheticOptions::default().denning_doy
+        },
+        ..SyntheticOptions::default()
+    };
+    assert!(unknown.generate().is_err());
+}
+
+#[test]
+fn test_planted_effects_are_recoverable() {
+    let options = SyntheticOptions {
+        studies: 8,
+        ..SyntheticOptions::with_seed(42)
+    };
+    let dataset = options.dataset().unwrap();
+
+    // winter_swe is drawn independently, so the simple slope is unbiased
+    let points: Vec<(f64, f64)> = dataset
+        .denning()
+        .iter()
+        .map(|d| (d.winter_tmax.unwrap().value(), f64::from(d.denning_doy)))
+        .collect();
+    let estimate = slope(&points);
+    assert!((estimate + 0.8).abs() < 0.2, "{}", estimate);
+
+    // Late denners succeed less often
+    let success_rate = |early: bool| {
+        let seasons = dataset
+            .denning()
+            .iter()
+            .zip(dataset.reproduction())
+            .filter(|(d, _)| (d.denning_doy < 123) == early);
+        let (wins, n) = seasons.fold((0, 0), |(w, n), (_, r)| (w + usize::from(r.success), n + 1));
+        wins as f64 / n as f64
+    };
+    assert!(success_rate(true) > success_rate(false) + 0.05);
+}
+
+#[test]
+fn test_covariate_overrides_are_clipped_and_clamped() {
+    assert_eq!(Distribution::Normal { mean: 2.0, sd: 1.0 }.mean(), 2.0);
+    assert_eq!(
+        Distribution::Uniform {
+            low: 1.0,
+            high: 3.0
+        }
+        .mean(),
+        2.0
+    );
+    assert!(
+        (Distribution::LogNormal {
+            mu: 0.0,
+            sigma: 0.0
+        }
+        .mean()
+            - 1.0)
+            .abs()
+            < 1e-12
+    );
+
+    let options = SyntheticOptions {
+        studies: 1,
+        years: 2000..=2004,
+        covariates: [
+            (
+                "winter_swe".to_string(),
+                Distribution::Normal {
+                    mean: -500.0,
+                    sd: 10.0,
+                },
+            ),
+            (
+                "annual_ao".to_string(),
+                Distribution::Uniform {
+                    low: 0.4,
+                    high: 0.4,
+                },
+            ),
+            (
+                "winter_tmax".to_string(),
+                Distribution::Normal {
+                    mean: -400.0,
+                    sd: 1.0,
+                },
+            ),
+        ]
+        .into_iter()
+        .collect(),
+        reproduction_rate: 0.0,
+        denning_doy: PlantedEffects {
+            intercept: 400.0,
+            slopes: Vec::new(),
+            ..SyntheticOptions::default().denning_doy
+        },
+        ..SyntheticOptions::with_seed(3)
+    };
+    let (denning, reproduction) = options.generate().unwrap();
+    assert_eq!(denning.len(), 10 * 5);
+    assert!(reproduction.is_empty());
+    for d in &denning {
+        assert_eq!(d.winter_swe.map(Quantity::value), Some(0.0));
+        assert_eq!(d.winter_tmax.map(Quantity::value), Some(-273.15));
+        assert_eq!(d.annual_ao, Some(0.4));
+        assert_eq!(d.denning_doy, *DENNING_SEASON.end());
+        assert_eq!(d.denning_date.ordinal(), 200);
+        assert!((2000..=2004).contains(&d.denning_date.year()));
+    }
+
+    let empty = SyntheticOptions {
+        studies: 0,
+        ..SyntheticOptions::default()
+    };
+    let (denning, reproduction) = empty.generate().unwrap();
+    assert!(denning.is_empty() && reproduction.is_empty());
+    let first = 2010;
+    let no_years = SyntheticOptions {
+        years: first..=first - 1,
+        ..SyntheticOptions::default()
+    };
+    assert!(no_years.generate().unwrap().0.is_empty());
+}
+
+#[test]
+fn test_planted_effects_must_name_drawn_covariates() {
+    let column = |options: SyntheticOptions| match options.generate() {
+        Err(WolfDataError::Validation { column, .. }) => column,
+        other => panic!("Expected a validation error, got {:?}", other.map(|_| ())),
+    };
+
+    let self_dependent = SyntheticOptions {
+        denning_doy: PlantedEffects {
+            slopes: vec![("denning_doy".to_string(), 0.5)],
+            ..SyntheticOptions::default().denning_doy
+        },
+        ..SyntheticOptions::default()
+    };
+    assert_eq!(column(self_dependent), Some("denning_doy".to_string()));
+
+    let unknown_success = SyntheticOptions {
+        success: PlantedEffects {
+            slopes: vec![("prey_density".to_string(), 0.1)],
+            ..SyntheticOptions::default().success
+        },
+        ..SyntheticOptions::default()
+    };
+    assert_eq!(
+        column(unknown_success.clone()),
+        Some("prey_density".to_string())
+    );
+
+    // Drawing the covariate makes the effect valid, even though no record
+    // column stores it
+    let drawn = SyntheticOptions {
+        covariates: [(
+            "prey_density".to_string(),
+            Distribution::Uniform {
+                low: 0.0,
+                high: 10.0,
+            },
+        )]
+        .into_iter()
+        .collect(),
+        ..unknown_success
+    };
+    assert_eq!(drawn.dataset().unwrap().denning().len(), 4 * 10 * 20);
+}
+
+#[test]
+fn test_invalid_options_are_errors_not_panics() {
+    let error = |options: SyntheticOptions| match options.generate() {
+        Err(WolfDataError::Validation {
+            column, message, ..
+        }) => (column, message),
+        other => panic!("Expected a validation error, got {:?}", other.map(|_| ())),
+    };
+    let with_covariate = |name: &str, distribution| SyntheticOptions {
+        covariates: [(name.to_string(), distribution)].into_iter().collect(),
+        ..SyntheticOptions::default()
+    };
+
+    for rate in [f64::NAN, -0.1, 1.5] {
+        let (_, message) = error(SyntheticOptions {
+            missing_rate: rate,
+            ..SyntheticOptions::default()
+        });
+        assert!(message.starts_with("missing_rate must be between 0 and 1"));
+    }
+    let (_, message) = error(SyntheticOptions {
+        reproduction_rate: 2.0,
+        ..SyntheticOptions::default()
+    });
+    assert!(message.starts_with("reproduction_rate"), "{}", message);
+
+    let infinite = Distribution::Uniform {
+        low: f64::INFINITY,
+        high: f64::INFINITY,
+    };
+    let negative_sd = Distribution::Normal {
+        mean: 0.0,
+        sd: -1.0,
+    };
+    let overflowing_mean = Distribution::LogNormal {
+        mu: 1000.0,
+        sigma: 0.1,
+    };
+    for distribution in [infinite, negative_sd, overflowing_mean] {
+        let (column, _) = error(with_covariate("winter_swe", distribution));
+        assert_eq!(column.as_deref(), Some("winter_swe"));
+    }
+
+    // Valid parameters whose draws overflow are caught when wrapped in a unit
+    let wide = Distribution::Normal {
+        mean: 1e308,
+        sd: 1e308,
+    };
+    let (column, message) = error(with_covariate("fall_prcp", wide));
+    assert_eq!(column.as_deref(), Some("fall_prcp"));
+    assert!(message.contains("out of range"), "{}", message);
+
+    let (_, message) = error(SyntheticOptions {
+        success: PlantedEffects {
+            intercept: f64::NAN,
+            ..SyntheticOptions::default().success
+        },
+        ..SyntheticOptions::default()
+    });
+    assert_eq!(message, "planted success effects must be finite");
+}
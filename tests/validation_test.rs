@@ -0,0 +1,217 @@
+mod common;
+
+use chrono::NaiveDate;
+use common::{mock_denning_data, mock_reproductive_data};
+use wolf_project_210::data::{DenningPhenology, LoadOptions};
+use wolf_project_210::units::{Celsius, Quantity};
+use wolf_project_210::validation::{
+    RecordRule, Rule, Severity, Validator, LATITUDE_RANGE, LONGITUDE_RANGE,
+};
+
+#[test]
+fn test_builtin_rules_flag_implausible_records() {
+    let mut denning = mock_denning_data();
+    denning[0].longitude_study = -150.0;
+    denning[0].latitude_study = 62.0;
+    // 2020-05-10 is day 131 of a leap year
+    denning[0].denning_doy = 131;
+    let mut wrong_doy = denning[0].clone();
+    wrong_doy.uid = 2;
+    wrong_doy.denning_doy = 100;
+    let mut duplicate = denning[0].clone();
+    duplicate.latitude_study = 20.0;
+    denning.extend([wrong_doy, duplicate]);
+
+    let report = Validator::builtin().validate(&denning);
+    assert_eq!(report.checked, 3);
+    let rules: Vec<_> = report.findings.iter().map(|f| (f.row, f.rule.as_str())).collect();
+    assert_eq!(
+        rules,
+        vec![
+            (1, "doy_matches_date"),
+            (2, "unique_uid"),
+            (2, "study_extent")
+        ]
+    );
+    assert!(!report.is_ok());
+    assert_eq!(report.failing_rows().into_iter().collect::<Vec<_>>(), vec![1, 2]);
+
+    let mut reproduction = mock_reproductive_data();
+    reproduction[0].longitude_study = -150.0;
+    reproduction[0].latitude_study = 62.0;
+    reproduction[0].success = 2;
+    reproduction[0].end_date = NaiveDate::from_ymd_opt(2020, 5, 1).unwrap();
+    let report = Validator::builtin().validate(&reproduction);
+    assert_eq!(report.count(Severity::Error), 2);
+    assert_eq!(
+        report.by_rule().keys().copied().collect::<Vec<_>>(),
+        vec!["start_before_end", "success_flag"]
+    );
+}
+
+#[test]
+fn test_user_defined_rule() {
+    let mut denning = mock_denning_data();
+    denning[0].longitude_study = -150.0;
+    denning[0].latitude_study = 62.0;
+
+    let validator = Validator::<DenningPhenology>::new().with_rule(
+        RecordRule::new("study_a_only_after_2021", Severity::Warning, |d: &DenningPhenology| {
+            (d.study == "Study A" && d.season_key().1 < 2021).then(|| "too early".to_string())
+        })
+        .on_column("denning_date"),
+    );
+    let report = validator.validate(&denning);
+    assert!(report.is_ok(), "Warnings should not fail validation");
+    assert_eq!(report.count(Severity::Warning), 1);
+    assert_eq!(report.findings[0].column.as_deref(), Some("denning_date"));
+}
+
+#[test]
+fn test_quarantine_mode_moves_bad_rows() {
+    let csv = "\
+uid,study,longitude_study,latitude_study,pack_id,start_date,end_date,success,summer_prcp,fall_prcp,winter_swe,fall_tmax,summer_tmax,winter_tmax,tiNDVI_prev1,tiNDVI,annual_pdo,annual_ao,home_range_area,denning_match_growing_season
+1,Study A,-150,62,1,2020-04-01,2020-08-31,1,100,50,200,10,20,5,0.5,0.6,1,0.5,100,0.8
+2,Study A,-150,62,2,2020-04-01,2020-08-31,3,100,50,200,10,20,5,0.5,0.6,1,0.5,100,0.8
+3,Study A,-150,62,3,2020-04-01,2020-08-31,1,NA,50,200,10,20,5,0.5,0.6,1,0.5,100,0.8
+";
+    let mut quarantine = Vec::new();
+    let (records, load, validation) = LoadOptions::default()
+        .quarantine_reproductive(csv.as_bytes(), &Validator::builtin(), &mut quarantine)
+        .unwrap();
+
+    assert_eq!(records.len(), 1);
+    assert_eq!(load.rejected_count(), 1);
+    assert_eq!(validation.quarantined, 2);
+    assert_eq!(validation.findings[0].line, Some(3));
+
+    let quarantine = String::from_utf8(quarantine).unwrap();
+    let lines: Vec<_> = quarantine.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].ends_with("denning_match_growing_season,quarantine_reason"));
+    assert!(lines[1].starts_with("3,Study A"), "Rejected rows are written first");
+    assert!(lines[2].starts_with("2,Study A"));
+    assert!(lines[2].contains("success_flag"));
+}
+
+/// A denning record that passes every built-in rule
+fn valid_denning(uid: u32) -> DenningPhenology {
+    DenningPhenology {
+        uid,
+        longitude_study: -150.0,
+        latitude_study: 62.0,
+        denning_doy: 131,
+        ..mock_denning_data()[0].clone()
+    }
+}
+
+#[test]
+fn test_warnings_and_range_boundaries() {
+    let mut records: Vec<DenningPhenology> = (1..=6).map(valid_denning).collect();
+    // The extent and the denning season include their end points
+    records[0].latitude_study = *LATITUDE_RANGE.end();
+    records[0].longitude_study = *LONGITUDE_RANGE.start();
+    records[1].denning_date = NaiveDate::from_yo_opt(2020, 60).unwrap();
+    records[1].denning_doy = 60;
+    records[2].denning_date = NaiveDate::from_yo_opt(2020, 201).unwrap();
+    records[2].denning_doy = 201;
+    // An individual latitude of 0 means "not collared" and is not checked
+    records[3].latitude_individual = 0.0;
+    records[4].latitude_individual = 30.0;
+    records[5].denned = -1;
+
+    let report = Validator::builtin().validate(&records);
+    let findings: Vec<_> = report
+        .findings
+        .iter()
+        .map(|f| (f.row, f.rule.as_str(), f.severity))
+        .collect();
+    assert_eq!(
+        findings,
+        [
+            (2, "denning_season", Severity::Warning),
+            (4, "individual_latitude", Severity::Warning),
+            (5, "denned_flag", Severity::Error),
+        ]
+    );
+    assert_eq!(report.failing_rows().into_iter().collect::<Vec<_>>(), [5]);
+    assert_eq!(
+        report.to_string(),
+        "6 records checked, 1 errors, 2 warnings"
+    );
+    assert_eq!(
+        report.findings[2].to_string(),
+        "error [denned_flag] uid 6 (Study A / pack 1) `denned`: expected 0 or 1, got -1"
+    );
+
+    let mut reproduction = mock_reproductive_data();
+    reproduction[0].longitude_study = -150.0;
+    reproduction[0].latitude_study = 62.0;
+    reproduction[0].home_range_area = Some(0.0);
+    reproduction[0].winter_tmax = Celsius::new(25.0).ok();
+    let report = Validator::builtin().validate(&reproduction);
+    assert!(report.is_ok());
+    assert_eq!(
+        report.by_rule().into_iter().collect::<Vec<_>>(),
+        [("home_range_positive", 1), ("winter_colder_than_summer", 1)]
+    );
+
+    let empty = Validator::<DenningPhenology>::builtin().validate(&[]);
+    assert_eq!((empty.checked, empty.findings.len()), (0, 0));
+    assert!(empty.is_ok());
+}
+
+/// Flags every record after the first of each study, as a whole-slice rule
+struct OneRecordPerStudy;
+
+impl Rule<DenningPhenology> for OneRecordPerStudy {
+    fn name(&self) -> &str {
+        "one_per_study"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Info
+    }
+
+    fn column(&self) -> Option<&str> {
+        None
+    }
+
+    fn check(&self, records: &[DenningPhenology]) -> Vec<(usize, String)> {
+        (1..records.len())
+            .filter(|&i| records[..i].iter().any(|r| r.study == records[i].study))
+            .map(|i| (i, format!("{} already has a record", records[i].study)))
+            .collect()
+    }
+}
+
+#[test]
+fn test_rules_run_in_order_and_findings_sort_by_severity() {
+    let mut records = vec![valid_denning(1), valid_denning(1)];
+    records[1].denning_doy = 140;
+    let validator = Validator::builtin().with_rule(OneRecordPerStudy);
+    assert_eq!(validator.rule_names().first(), Some(&"unique_uid"));
+    assert_eq!(validator.rule_names().last(), Some(&"one_per_study"));
+
+    let report = validator.validate(&records);
+    let row: Vec<_> = report
+        .findings
+        .iter()
+        .map(|f| (f.rule.as_str(), f.severity))
+        .collect();
+    assert_eq!(
+        row,
+        [
+            ("unique_uid", Severity::Error),
+            ("doy_matches_date", Severity::Error),
+            ("one_per_study", Severity::Info),
+        ]
+    );
+    assert!(report
+        .findings
+        .iter()
+        .all(|f| f.row == 1 && f.line.is_none()));
+    assert_eq!(report.findings[0].message, "uid 1 already used by row 0");
+    assert_eq!(report.with_severity(Severity::Info).count(), 1);
+    assert_eq!(report.findings[2].column, None);
+}